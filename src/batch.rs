@@ -0,0 +1,389 @@
+//Headless batch runner: load a scene file, run the physics with a fixed timestep without opening a window,
+//and write the final state (and optionally every step) to disk.
+//
+//Usage: batch <scene> (--steps N | --duration SECONDS) [--output FILE] [--csv FILE] [--json FILE]
+//             [--events FILE] [--timestep SECONDS] [--seed N] [--script FILE]
+//       batch --script FILE (--steps N | --duration SECONDS) [...]
+//       batch --experiment FILE [--csv FILE]
+
+//External libraries
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process::ExitCode;
+
+//Internal Modules
+mod measurements;
+mod objects;
+
+use crate::measurements::set_fixed_dt;
+use crate::objects::events::EventLog;
+use crate::objects::forces::ForceGenerator;
+use crate::objects::experiment::{export_results, format_results, load_experiment, run_experiment, ExperimentError};
+use crate::objects::physics::PhysicsObject;
+use crate::objects::scene::{load_scene, save_scene, Scene};
+use crate::objects::script::Script;
+use crate::objects::world::{checksum, step};
+
+//Exit codes, so scripts can tell what went wrong
+const EXIT_USAGE: u8 = 1;
+const EXIT_LOAD: u8 = 2;
+const EXIT_RUNTIME: u8 = 3;
+const EXIT_OUTPUT: u8 = 4;
+
+//The options given on the command line
+struct Options {
+    scene: String,
+    steps: Option<u64>,
+    duration: Option<f32>,
+    output: String,
+    csv: Option<String>,
+    json: Option<String>,
+    events: Option<String>,
+    timestep: Option<f32>,
+    seed: Option<u64>,
+    experiment: Option<String>,
+    script: Option<String>,
+}
+
+//Read the options from the command line arguments
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        scene: String::new(),
+        steps: None,
+        duration: None,
+        output: String::from("final_state.txt"),
+        csv: None,
+        json: None,
+        events: None,
+        timestep: None,
+        seed: None,
+        experiment: None,
+        script: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        //Every option apart from the scene takes a value
+        let mut value = |name: &str| args.next().cloned().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--steps" => options.steps = Some(value(arg)?.parse().map_err(|_| "--steps must be a whole number")?),
+            "--duration" => options.duration = Some(value(arg)?.parse().map_err(|_| "--duration must be a number")?),
+            "--output" => options.output = value(arg)?,
+            "--csv" => options.csv = Some(value(arg)?),
+            "--json" => options.json = Some(value(arg)?),
+            "--events" => options.events = Some(value(arg)?),
+            "--timestep" => options.timestep = Some(value(arg)?.parse().map_err(|_| "--timestep must be a number")?),
+            "--seed" => options.seed = Some(value(arg)?.parse().map_err(|_| "--seed must be a whole number")?),
+            "--experiment" => options.experiment = Some(value(arg)?),
+            "--script" => options.script = Some(value(arg)?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if options.scene.is_empty() => options.scene = arg.clone(),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    //An experiment names its own scene, and runs until its stop condition
+    if options.experiment.is_some() {
+        return Ok(options);
+    }
+    if options.scene.is_empty() && options.script.is_none() {
+        return Err(String::from("no scene or script file given"));
+    }
+    if options.steps.is_some() == options.duration.is_some() {
+        return Err(String::from("give exactly one of --steps or --duration"));
+    }
+    if options.timestep.is_some_and(|timestep| timestep <= 0.0) {
+        return Err(String::from("--timestep must be greater than 0"));
+    }
+    Ok(options)
+}
+
+//Writes the state of every object after each step, as CSV and/or JSON
+struct Recorder {
+    csv: Option<BufWriter<File>>,
+    json: Option<BufWriter<File>>,
+    first_json_step: bool,
+}
+
+impl Recorder {
+    fn new(csv_path: &Option<String>, json_path: &Option<String>) -> std::io::Result<Recorder> {
+        let mut csv = match csv_path {
+            Some(path) => Some(BufWriter::new(File::create(path)?)),
+            None => None,
+        };
+        let mut json = match json_path {
+            Some(path) => Some(BufWriter::new(File::create(path)?)),
+            None => None,
+        };
+        if let Some(file) = &mut csv {
+            writeln!(file, "step,time,checksum,handle,x,y,vx,vy,sleeping")?;
+        }
+        if let Some(file) = &mut json {
+            write!(file, "[")?;
+        }
+        Ok(Recorder { csv, json, first_json_step: true })
+    }
+
+    //Write the state of every object at this step
+    fn record(&mut self, step_number: u64, time: f32, objects: &mut [Box<dyn PhysicsObject>],
+              forces: &[Box<dyn ForceGenerator>]) -> std::io::Result<()> {
+        if self.csv.is_none() && self.json.is_none() {
+            return Ok(());
+        }
+        let hash = checksum(objects, forces);
+
+        if let Some(file) = &mut self.json {
+            if !self.first_json_step {
+                write!(file, ",")?;
+            }
+            write!(file, "\n{{\"step\":{},\"time\":{},\"checksum\":\"{:016x}\",\"objects\":[", step_number, time, hash)?;
+        }
+        for (index, object) in objects.iter_mut().enumerate() {
+            let pos = *object.get_render_shape_reference().get_pos();
+            let velocity = object.get_velocity();
+            let handle = object.get_handle();
+            let sleeping = object.get_sleeping();
+            if let Some(file) = &mut self.csv {
+                writeln!(file, "{},{},{:016x},{},{},{},{},{},{}", step_number, time, hash, handle, pos.x, pos.y,
+                         velocity.x, velocity.y, sleeping)?;
+            }
+            if let Some(file) = &mut self.json {
+                let separator = if index == 0 { "" } else { "," };
+                write!(file, "{}{{\"handle\":{},\"x\":{},\"y\":{},\"vx\":{},\"vy\":{},\"sleeping\":{}}}", separator, handle,
+                       pos.x, pos.y, velocity.x, velocity.y, sleeping)?;
+            }
+        }
+        if let Some(file) = &mut self.json {
+            write!(file, "]}}")?;
+        }
+        self.first_json_step = false;
+        Ok(())
+    }
+
+    //Close the JSON array and make sure everything is written
+    fn finish(&mut self) -> std::io::Result<()> {
+        if let Some(file) = &mut self.csv {
+            file.flush()?;
+        }
+        if let Some(file) = &mut self.json {
+            writeln!(file, "\n]")?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+//Find the first object whose position or velocity is no longer a real number
+fn find_invalid_object(objects: &mut [Box<dyn PhysicsObject>]) -> Option<usize> {
+    for object in objects.iter_mut() {
+        let pos = *object.get_render_shape_reference().get_pos();
+        if !pos.is_finite() || !object.get_velocity().is_finite() {
+            return Some(object.get_handle());
+        }
+    }
+    None
+}
+
+//Run every variant of an experiment, and print the table of results
+fn run_experiment_file(path: &str, csv: &Option<String>) -> ExitCode {
+    let experiment = match load_experiment(path) {
+        Ok(experiment) => experiment,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_LOAD);
+        }
+    };
+    let results = match run_experiment(&experiment) {
+        Ok(results) => results,
+        Err(ExperimentError::Load(error)) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_LOAD);
+        }
+        Err(ExperimentError::Runtime(error)) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_RUNTIME);
+        }
+    };
+
+    print!("{}", format_results(&experiment, &results));
+    if let Some(csv) = csv && let Err(error) = export_results(csv, &experiment, &results) {
+        eprintln!("error: could not write {}: {}", csv, error);
+        return ExitCode::from(EXIT_OUTPUT);
+    }
+    ExitCode::SUCCESS
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            eprintln!("usage: batch <scene> (--steps N | --duration SECONDS) [--output FILE] [--csv FILE] [--json FILE] [--events FILE] [--timestep SECONDS] [--seed N] [--script FILE]");
+            eprintln!("       batch --experiment FILE [--csv FILE]");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    if let Some(path) = &options.experiment {
+        return run_experiment_file(path, &options.csv);
+    }
+
+    //Load the scene (a script can start from an empty one), and apply any settings given on the command line
+    let scene = if options.scene.is_empty() {
+        Ok(Scene::new())
+    } else {
+        load_scene(&options.scene)
+    };
+    let mut scene = match scene {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_LOAD);
+        }
+    };
+    let mut script = match options.script.as_deref().map(Script::load).transpose() {
+        Ok(script) => script,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_LOAD);
+        }
+    };
+    let mut settings = scene.settings;
+    settings.deterministic = true;
+    if let Some(timestep) = options.timestep { settings.timestep = timestep; }
+    //A seed given on the command line starts the random numbers again, instead of carrying on from the scene
+    if let Some(seed) = options.seed {
+        settings.seed = seed;
+        settings.random_state = None;
+    }
+
+    //Always run with a fixed timestep and seed, so every run of the same scene is identical
+    set_fixed_dt(Some(settings.timestep));
+    settings.start_random();
+    let steps = match (options.steps, options.duration) {
+        (Some(steps), _) => steps,
+        (None, Some(duration)) => (duration / settings.timestep).ceil() as u64,
+        (None, None) => 0,
+    };
+
+    let mut recorder = match Recorder::new(&options.csv, &options.json) {
+        Ok(recorder) => recorder,
+        Err(error) => {
+            eprintln!("error: could not create output file: {}", error);
+            return ExitCode::from(EXIT_OUTPUT);
+        }
+    };
+    let mut events = EventLog::new();
+    let objects = &mut scene.objects;
+    let forces = &mut scene.forces;
+
+    //Let the script set up the scene before anything is recorded
+    if let Some(script) = &mut script && let Err(error) = script.setup(objects, forces, events.time) {
+        eprintln!("error: {}", error);
+        return ExitCode::from(EXIT_RUNTIME);
+    }
+
+    //Run the simulation, stopping if anything stops being a real number
+    let mut output_result = recorder.record(0, 0.0, objects, forces);
+    for step_number in 1..=steps {
+        let result = match &mut script {
+            Some(script) => script.step(objects, forces, &mut events),
+            None => {
+                step(objects, forces, &mut events);
+                Ok(())
+            }
+        };
+        if let Err(error) = result {
+            eprintln!("error: {} at step {} ({}s)", error, step_number, events.time);
+            return ExitCode::from(EXIT_RUNTIME);
+        }
+        if let Some(handle) = find_invalid_object(objects) {
+            eprintln!("error: object #{} has an invalid position or velocity at step {} ({}s)", handle, step_number, events.time);
+            return ExitCode::from(EXIT_RUNTIME);
+        }
+        if output_result.is_ok() {
+            output_result = recorder.record(step_number, events.time, objects, forces);
+        }
+    }
+
+    //Write the final state of the scene, and anything else that was asked for
+    output_result = output_result.and_then(|_| recorder.finish());
+    if let Err(error) = output_result {
+        eprintln!("error: could not write the per-step output: {}", error);
+        return ExitCode::from(EXIT_OUTPUT);
+    }
+    if let Err(error) = save_scene(&options.output, &settings, objects, forces) {
+        eprintln!("error: {}", error);
+        return ExitCode::from(EXIT_OUTPUT);
+    }
+    if let Some(path) = &options.events && let Err(error) = events.export(path) {
+        eprintln!("error: could not write {}: {}", path, error);
+        return ExitCode::from(EXIT_OUTPUT);
+    }
+
+    let name = match &script {
+        Some(script) if options.scene.is_empty() => script.get_path().to_string(),
+        Some(script) => format!("{} with {}", options.scene, script.get_path()),
+        None => options.scene.clone(),
+    };
+    println!("Ran {} steps ({}s) of {}, checksum {:016x}", steps, events.time, name, checksum(objects, forces));
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::objects::events::EventLog;
+    use crate::objects::scene::Scene;
+    use super::{parse_options, Recorder};
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn reads_options() {
+        let options = parse_options(&args("drop.txt --duration 2.5 --csv steps.csv --seed 5 --timestep 0.01")).unwrap();
+        assert_eq!(options.scene, "drop.txt");
+        assert_eq!((options.steps, options.duration), (None, Some(2.5)));
+        assert_eq!(options.output, "final_state.txt");
+        assert_eq!(options.csv.as_deref(), Some("steps.csv"));
+        assert_eq!((options.seed, options.timestep), (Some(5), Some(0.01)));
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        for (text, error) in [("drop.txt", "give exactly one of --steps or --duration"),
+                              ("drop.txt --steps 10 --duration 1", "give exactly one of --steps or --duration"),
+                              ("--steps 10", "no scene or script file given"),
+                              ("drop.txt --steps ten", "--steps must be a whole number"),
+                              ("drop.txt --steps 10 --timestep 0", "--timestep must be greater than 0"),
+                              ("drop.txt --steps 10 --fast", "unknown option --fast"),
+                              ("drop.txt --steps", "--steps needs a value")] {
+            assert_eq!(parse_options(&args(text)).err().as_deref(), Some(error));
+        }
+    }
+
+    #[test]
+    fn recorder_writes_every_step() {
+        let mut scene = Scene::load_test("recorder", "circle x=0 y=0 radius=0.2 type=dynamic\ncircle x=1 y=0 radius=0.2 type=static\n");
+        let path = std::env::temp_dir().join(format!("rv_recorder_test_{}.csv", std::process::id())).to_string_lossy().to_string();
+        let mut recorder = Recorder::new(&Some(path.clone()), &None).unwrap();
+        let mut events = EventLog::new();
+        recorder.record(0, 0.0, &mut scene.objects, &scene.forces).unwrap();
+        for step_number in 1..=3 {
+            scene.run(&mut events, 1);
+            recorder.record(step_number, events.time, &mut scene.objects, &scene.forces).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        //A header, then a row for each object at each step
+        let csv = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows[0], "step,time,checksum,handle,x,y,vx,vy,sleeping");
+        assert_eq!(rows.len(), 1 + 4 * 2);
+        assert!(rows[7].starts_with("3,"));
+    }
+}
@@ -7,28 +7,54 @@ mod measurements;
 mod objects;
 mod uis;
 
+use std::any::Any;
 use crate::objects::create_objects::{
-    draw_process_ball, draw_process_rectangle, draw_process_square,
+    MouseMode, draw_process_ball, draw_process_fluid, draw_process_magnetic, draw_process_rectangle, draw_process_square,
+    draw_process_cloth, draw_process_gas, draw_process_joint, draw_process_rope, draw_process_sph, draw_process_zone, place_process_emitter,
+    draw_process_spring, draw_process_wind, place_process_attractor, draw_process_electric,
+    draw_process_fluid_polygon,
 };
 use crate::objects::physics::{PhysicsObject, PhysicsType};
 use crate::objects::shapes::{Circle, Rectangle};
-use crate::uis::build_ui;
+use crate::uis::{build_ui, OrbitSettings, Overlays};
 //use measurements::*;
 use objects::*;
-use uis::build_hot_bar;
+use uis::{
+    build_actuator_panel, build_cloth_panel, build_emitter_panel, build_energy_hud, build_event_log, build_gas_panel,
+    build_gravity_panel, build_hot_bar, build_impulse_panel, build_joint_panel, build_magnetic_panel, build_zone_panel,
+    build_force_panel, build_electric_panel, build_fluid_panel,
+};
 #[allow(unused)]
-use crate::measurements::{dt, QuadTree, Rect, Particle, Point};
-use crate::measurements::meter;
+use crate::measurements::{dt, QuadTree, Rect, Point};
+use crate::measurements::{meter, seed_random, set_fixed_dt};
 use crate::objects::physics::PhysicsType::Static;
-
-#[derive(Clone)]
-#[allow(unused)]
-enum MouseMode {
-    Drag,
-    DrawSquare,
-    DrawRectangele,
-    DrawBall,
-}
+use crate::objects::events::EventLog;
+use crate::measurements::diagnostics::Diagnostics;
+use crate::measurements::trails::Trails;
+use crate::objects::electrostatics::{draw_electric_field, ElectricField};
+use crate::objects::forces::{default_forces, draw_forces, find_force, find_forces, has_force, remove_nth_force, ForceGenerator, RadialAttractor, Spring, Wind};
+use crate::objects::cloth::Cloth;
+use crate::objects::fluids::FluidRegion;
+use crate::objects::explosions::{impulse_process, ImpulseSettings};
+use crate::objects::gas::GasBox;
+use crate::objects::slingshot::slingshot_process;
+use crate::objects::actuators::{Actuator, ActuatorSettings};
+use crate::objects::joints::{add_joint, JointSystem};
+use crate::objects::magnetism::MagneticField;
+use crate::objects::particles::ParticleEmitter;
+use crate::objects::soft_bodies::make_soft_body;
+use crate::objects::zones::ForceZone;
+use crate::objects::gravitation::{set_nbody, NBodyGravity};
+use crate::objects::scene::{load_scene, save_scene};
+use crate::objects::script::Script;
+use crate::objects::world::{checksum, resolve_collisions, step, SimulationSettings};
+
+//The file scenes are saved to and loaded from
+const SCENE_PATH: &str = "scene.txt";
+//The script run by the script button
+const SCRIPT_PATH: &str = "script.rhai";
+//The most fixed steps that can be run in one frame, so a slow frame can't freeze the program
+const MAX_STEPS_PER_FRAME: u32 = 8;
 
 // Set up the config for the project window
 fn conf() -> Conf {
@@ -39,6 +65,22 @@ fn conf() -> Conf {
     }
 }
 
+//Move the simulation forward one step, through the script if one is running.
+//If the script fails it is stopped, and the simulation is paused so the user can see the error
+fn run_step(script: &mut Option<Script>, objects: &mut Vec<Box<dyn PhysicsObject>>, forces: &mut Vec<Box<dyn ForceGenerator>>,
+            events: &mut EventLog, simulate: &mut bool, status: &mut String) {
+    match script {
+        Some(running) => {
+            if let Err(error) = running.step(objects, forces, events) {
+                *status = error;
+                *script = None;
+                *simulate = false;
+            }
+        }
+        None => step(objects, forces, events),
+    }
+}
+
 //Main function called by macroquad as to allow the program to render.
 #[macroquad::main(conf)]
  async fn main() {
@@ -67,60 +109,57 @@ fn conf() -> Conf {
     // pause or play the program
     let mut pauorpla = false;
     let mut draw_mouse_storage: Option<Vec2> = None;
+    //The corners of the fluid polygon being drawn
+    let mut polygon_corners: Vec<Vec2> = Vec::new();
 
     //Create a list of all physics objects
     let mut phys_object: Vec<Box<dyn PhysicsObject>> = Vec::new();
+    //Create a list of the forces acting on them, starting with gravity and drag
+    let mut forces: Vec<Box<dyn ForceGenerator>> = default_forces();
     
     let mut ui_id: String = String::from("");
     let mut ui_text_save: String = String::from("");
     
     let mut selected_object_index: Option<usize> = None;
-
-    let mut boundary = Rect::new(0., 0., 100.0, 100.0);
+    //The settings for putting the selected object into orbit
+    let mut orbit = OrbitSettings::new();
+    //The settings for the impulse and explosion tool
+    let mut impulse_settings = ImpulseSettings::new();
+    //What the actuator panel gives the next keyframe
+    let mut actuator_settings = ActuatorSettings::new();
+    //The handle and physics type of the object being pulled back with the slingshot
+    let mut slingshot_handle: Option<(usize, PhysicsType)> = None;
 
     let mut last_mouse_drag_pos: Option<Vec2> = None;
     let mut before_phys_type: Option<PhysicsType> = None;
 
-    //Main loop function
-    loop {
+    //Log of every collision and sensor event
+    let mut event_log = EventLog::new();
+    let mut log_export_status = String::new();
 
-        let mut corner1: Vec2;
-        let mut corner2: Vec2;
-        if phys_object.is_empty() {
-            corner1 = Vec2::new(0., 0.);
-            corner2 = Vec2::new(0., 0.);
-        }else {
-            corner1 = Vec2::new(meter(phys_object.get_mut(0).unwrap().get_render_shape().get_pos().x), meter(phys_object.get_mut(0).unwrap().get_render_shape().get_pos().y));
-            corner2 = Vec2::new(meter(phys_object.get_mut(0).unwrap().get_render_shape().get_pos().x), meter(phys_object.get_mut(0).unwrap().get_render_shape().get_pos().y));
-        }
+    //Energy and momentum of the scene
+    let mut diagnostics = Diagnostics::new();
 
+    //Which panels and overlays should be shown, and the paths objects have taken
+    let mut overlays = Overlays::new();
+    let mut trails = Trails::new();
 
+    //Settings for stepping the simulation, and the state of deterministic mode
+    let mut settings = SimulationSettings::new();
+    let mut step_accumulator: f32 = 0.0;
+    let mut step_count: u64 = 0;
+    let mut last_checksum: u64 = checksum(&mut phys_object, &forces);
+    let mut scene_status = String::new();
+
+    //The script controlling the scene, if one is running
+    let mut script: Option<Script> = None;
+
+    //Main loop function
+    loop {
         clear_background(Color::from_rgba(30, 30, 30, 255));
         // set camera and produce the next frame
         set_camera(&camera);
 
-        for i in &mut phys_object {
-            let p = i.get_render_shape().get_pos().clone();
-            if meter(p.x) < corner1.x { corner1.x = meter(p.x); }
-            if meter(p.y) < corner1.y { corner1.y = meter(p.y); }
-            if meter(p.x) > corner2.x { corner2.x = meter(p.x); }
-            if meter(p.y) > corner2.y { corner2.y = meter(p.y); }
-        }
-
-        let w = (corner2.x - corner1.x).abs();
-        let h = (corner2.y - corner1.y).abs();
-
-        boundary.x = corner1.x + w/2.;
-        boundary.y = corner2.y - h/2.;
-        boundary.w = w/2.;
-        boundary.h = h/2.;
-
-        let mut qtree = QuadTree::new(boundary, 4);
-
-        for p in 0..phys_object.len() {
-            qtree.insert(Point::new(meter(phys_object[p].get_render_shape().get_pos().x), meter(phys_object[p].get_render_shape().get_pos().y), p));
-        }
-
         //Create the list of objects to render
         let mut render: Vec<Box<dyn Render + 'static>> = Vec::new();
 
@@ -167,12 +206,9 @@ fn conf() -> Conf {
                 }
             }
         }
-        //Physics function for all physics objects
+        //Render and select any of the physics objects
         for index in 0..phys_object.len() {
             render.push(phys_object.get_mut(index).unwrap().get_render_shape());
-            if pauorpla {
-                phys_object.get_mut(index).unwrap().physics_process(&camera);
-            }
             if is_mouse_button_down(MouseButton::Left) && matches!(mouse_mode, MouseMode::Drag) &&
                 ((mouse_position().0 < screen_width() - 400.) || selected_object_index.is_none())
                 && phys_object.get_mut(index).unwrap().get_render_shape_reference().mouse_in_area(camera.screen_to_world(Vec2::from(mouse_position())))
@@ -184,45 +220,226 @@ fn conf() -> Conf {
             }
         }
 
-        let len = phys_object.len();
-
         for i in &mut phys_object {
             i.get_render_shape_reference().set_colour(WHITE);
         }
 
-        for i in 0..len {
-            {
-                let (left, right_side) = phys_object.split_at_mut(i);
-
-                let (item, right) = right_side.split_first_mut().expect("Index out of bounds");
-
-                // 3. Build the references to every OTHER object
-                let mut others_vec: Vec<&mut Box<dyn PhysicsObject>> = Vec::with_capacity(len - 1);
-                others_vec.extend(left.iter_mut());
-                others_vec.extend(right.iter_mut());
-
-                item.detect_near_object(&mut qtree, others_vec);
+        //Physics function for all physics objects
+        if pauorpla && settings.deterministic {
+            //Run as many fixed steps as fit into the time since the last frame
+            set_fixed_dt(Some(settings.timestep));
+            step_accumulator += get_frame_time();
+            let mut steps = 0;
+            while step_accumulator >= settings.timestep && steps < MAX_STEPS_PER_FRAME {
+                run_step(&mut script, &mut phys_object, &mut forces, &mut event_log, &mut pauorpla, &mut scene_status);
+                step_accumulator -= settings.timestep;
+                step_count += 1;
+                steps += 1;
             }
+            step_accumulator = step_accumulator.min(settings.timestep);
+            last_checksum = checksum(&mut phys_object, &forces);
+        } else if pauorpla {
+            set_fixed_dt(None);
+            run_step(&mut script, &mut phys_object, &mut forces, &mut event_log, &mut pauorpla, &mut scene_status);
+        } else if !settings.deterministic {
+            //Keep objects from overlapping while the user moves them around
+            resolve_collisions(&mut phys_object, &mut event_log);
+        }
+        diagnostics.update(&mut phys_object, &forces, event_log.time);
+        if overlays.trails && pauorpla {
+            trails.update(&mut phys_object);
+        } else if !overlays.trails {
+            trails.clear();
         }
 
         //Allow the user to unselect any objects they have selected
         if is_key_pressed(KeyCode::Escape) { selected_object_index = None; }
 
         //Build the UI, and render any objects hte player would like to use
-        build_ui(&camera, &mut ui_id, &mut phys_object, selected_object_index, &mut ui_text_save);
+        let gravitational_constant = find_force::<NBodyGravity>(&mut forces).map(|nbody| nbody.gravitational_constant);
+        let (soft, balloon) = build_ui(&camera, &mut ui_id, &mut phys_object, selected_object_index, &mut ui_text_save,
+                                       gravitational_constant, &mut orbit);
+        //Swap the selected object for a soft body made of point masses
+        if let Some(index) = selected_object_index && (soft || balloon)
+            && let Some(soft_body) = make_soft_body(&mut phys_object, index, balloon) {
+            forces.push(Box::new(soft_body));
+            selected_object_index = None;
+        }
+        if overlays.log {
+            build_event_log(&mut event_log, &mut log_export_status);
+        }
+        if overlays.energy {
+            build_energy_hud(&mut diagnostics);
+        }
+        if let Some(nbody) = find_force::<NBodyGravity>(&mut forces) {
+            build_gravity_panel(nbody);
+        }
+        let mut fluids = find_forces::<FluidRegion>(&mut forces);
+        if !fluids.is_empty() {
+            build_fluid_panel(&mut fluids);
+        }
+        let mut magnetic_fields = find_forces::<MagneticField>(&mut forces);
+        if !magnetic_fields.is_empty() && let Some(index) = build_magnetic_panel(&mut magnetic_fields) {
+            remove_nth_force::<MagneticField>(&mut forces, index);
+        }
+        let mut electric_fields = find_forces::<ElectricField>(&mut forces);
+        if !electric_fields.is_empty() {
+            build_electric_panel(&mut electric_fields);
+        }
+        let mut zones = find_forces::<ForceZone>(&mut forces);
+        if !zones.is_empty() && let Some(index) = build_zone_panel(&mut zones) {
+            remove_nth_force::<ForceZone>(&mut forces, index);
+        }
+        //Springs, wind and attractors share one panel, so they are all found in one pass over the forces
+        let (mut springs, mut winds, mut attractors) = (Vec::new(), Vec::new(), Vec::new());
+        for generator in forces.iter_mut() {
+            let generator = generator.as_mut() as &mut dyn Any;
+            if generator.is::<Spring>() {
+                springs.extend(generator.downcast_mut::<Spring>());
+            } else if generator.is::<Wind>() {
+                winds.extend(generator.downcast_mut::<Wind>());
+            } else {
+                attractors.extend(generator.downcast_mut::<RadialAttractor>());
+            }
+        }
+        if !springs.is_empty() || !winds.is_empty() || !attractors.is_empty() {
+            build_force_panel(&mut springs, &mut winds, &mut attractors);
+        }
+        let mut emitters = find_forces::<ParticleEmitter>(&mut forces);
+        if !emitters.is_empty() {
+            build_emitter_panel(&mut emitters);
+        }
+        let mut gases = find_forces::<GasBox>(&mut forces);
+        if !gases.is_empty() {
+            build_gas_panel(&mut gases);
+        }
+        let mut cloths = find_forces::<Cloth>(&mut forces);
+        if !cloths.is_empty() {
+            build_cloth_panel(&mut cloths);
+        }
+        if let Some(joints) = find_force::<JointSystem>(&mut forces) {
+            build_joint_panel(joints);
+        }
+        //Show the actuators while a kinematic object is selected, as only they can be driven, or one that already has an actuator
+        let mut actuators = find_forces::<Actuator>(&mut forces);
+        let mut selected = selected_object_index.and_then(|index| phys_object.get_mut(index));
+        let drivable = selected.as_mut().is_some_and(|object| matches!(object.get_physics_type(), PhysicsType::Kinematic) ||
+            actuators.iter().any(|actuator| actuator.handle == object.get_handle()));
+        if drivable && let Some(actuator) = build_actuator_panel(&mut actuators, selected, &mut actuator_settings) {
+            forces.push(Box::new(actuator));
+        }
+        if matches!(mouse_mode, MouseMode::Impulse) {
+            build_impulse_panel(&mut impulse_settings);
+        }
+        if overlays.field {
+            draw_electric_field(&forces, &mut phys_object, &camera);
+        }
+        if overlays.trails {
+            trails.draw();
+        }
         render_objects(&render);
+        draw_forces(&forces, &mut phys_object);
+        draw_sleep_indicators(&mut phys_object);
+        draw_belt_arrows(&mut phys_object);
+
+        //Show the step and checksum in deterministic mode, or the result of saving/loading a scene
+        let status = if settings.deterministic {
+            format!("step {}  checksum {:016x}  {}", step_count, last_checksum, scene_status)
+        } else {
+            scene_status.clone()
+        };
 
         //Build the hotbar, and figure out if the software should close
-        let (stop, clear) = build_hot_bar(&mut pauorpla, &mut mouse_mode);
+        let was_deterministic = settings.deterministic;
+        let mut nbody = has_force::<NBodyGravity>(&forces);
+        let was_nbody = nbody;
+        let (stop, clear, save, load, run_script) = build_hot_bar(&mut pauorpla, &mut mouse_mode, &mut overlays,
+                                                      &mut settings.deterministic, &mut nbody, &status);
         if stop {
             active = false;
         }
+
+        //Swap between N-body gravitation and the normal gravity and drag
+        if nbody != was_nbody {
+            set_nbody(&mut forces, nbody.then(|| NBodyGravity::new(1.0, 0.05, 0.5)));
+            diagnostics.clear();
+        }
+
+        //Start deterministic mode from a known seed and step count
+        if settings.deterministic && !was_deterministic {
+            seed_random(settings.seed);
+            step_accumulator = 0.0;
+            step_count = 0;
+            last_checksum = checksum(&mut phys_object, &forces);
+        }
+
+        //Save or load the scene
+        if save {
+            scene_status = match save_scene(SCENE_PATH, &settings, &mut phys_object, &forces) {
+                Ok(()) => format!("Saved {}", SCENE_PATH),
+                Err(error) => error,
+            };
+        }
+        if load {
+            match load_scene(SCENE_PATH) {
+                Ok(scene) => {
+                    phys_object = scene.objects;
+                    forces = scene.forces;
+                    settings = scene.settings;
+                    script = None;
+                    selected_object_index = None;
+                    pauorpla = false;
+                    event_log = EventLog::new();
+                    diagnostics.clear();
+                    trails.clear();
+                    settings.start_random();
+                    step_accumulator = 0.0;
+                    step_count = 0;
+                    last_checksum = checksum(&mut phys_object, &forces);
+                    scene_status = format!("Loaded {}", SCENE_PATH);
+                }
+                Err(error) => scene_status = error,
+            }
+        }
         
+        //Start the script from an empty scene, letting it create all the objects it needs
+        if run_script {
+            match Script::load(SCRIPT_PATH) {
+                Ok(mut new_script) => {
+                    phys_object.clear();
+                    forces = default_forces();
+                    selected_object_index = None;
+                    pauorpla = false;
+                    event_log = EventLog::new();
+                    diagnostics.clear();
+                    trails.clear();
+                    seed_random(settings.seed);
+                    step_accumulator = 0.0;
+                    step_count = 0;
+                    scene_status = match new_script.setup(&mut phys_object, &mut forces, event_log.time) {
+                        Ok(()) => {
+                            let status = format!("Running {}", new_script.get_path());
+                            script = Some(new_script);
+                            status
+                        }
+                        Err(error) => error,
+                    };
+                    last_checksum = checksum(&mut phys_object, &forces);
+                }
+                Err(error) => scene_status = error,
+            }
+        }
+
         if clear {
             selected_object_index = None;
+            script = None;
+            forces = default_forces();
             for _i in 0..phys_object.len() {
                 phys_object.pop();
             }
+            event_log.clear();
+            diagnostics.clear();
+            trails.clear();
         }
 
         //Change the level of the cameras zoom
@@ -286,8 +503,106 @@ fn conf() -> Conf {
             }
             MouseMode::DrawBall => {
                 ball = draw_process_ball(&mut draw_mouse_storage, &camera);
+            }
+            //Fluid regions are a force on the objects, rather than an object themselves
+            MouseMode::DrawFluid => {
+                if let Some(fluid) = draw_process_fluid(&mut draw_mouse_storage, &camera) {
+                    forces.push(Box::new(fluid));
+                    mouse_mode = MouseMode::Drag;
+                }
+            }
+            //Stay in polygon mode until the polygon is finished, as it takes a click for every corner
+            MouseMode::DrawFluidPolygon => {
+                if let Some(fluid) = draw_process_fluid_polygon(&mut polygon_corners, &camera) {
+                    forces.push(Box::new(fluid));
+                    mouse_mode = MouseMode::Drag;
+                }
+            }
+            MouseMode::DrawMagnetic => {
+                if let Some(field) = draw_process_magnetic(&mut draw_mouse_storage, &camera) {
+                    forces.push(Box::new(field));
+                    mouse_mode = MouseMode::Drag;
+                }
+            }
+            MouseMode::DrawElectric => {
+                if let Some(field) = draw_process_electric(&mut draw_mouse_storage, &camera) {
+                    forces.push(Box::new(field));
+                    mouse_mode = MouseMode::Drag;
+                }
+            }
+            MouseMode::DrawZone => {
+                if let Some(zone) = draw_process_zone(&mut draw_mouse_storage, &camera) {
+                    forces.push(Box::new(zone));
+                    mouse_mode = MouseMode::Drag;
+                }
+            }
+            MouseMode::DrawGas => {
+                if let Some(gas) = draw_process_gas(&mut draw_mouse_storage, &camera) {
+                    forces.push(Box::new(gas));
+                    mouse_mode = MouseMode::Drag;
+                }
+            }
+            MouseMode::DrawRope => {
+                if let Some(rope) = draw_process_rope(&mut draw_mouse_storage, &camera) {
+                    forces.push(Box::new(rope));
+                    mouse_mode = MouseMode::Drag;
+                }
+            }
+            MouseMode::DrawCloth => {
+                if let Some(cloth) = draw_process_cloth(&mut draw_mouse_storage, &camera) {
+                    forces.push(Box::new(cloth));
+                    mouse_mode = MouseMode::Drag;
+                }
+            }
+            //Stay in joint mode after making a joint, as a structure needs lots of them
+            MouseMode::DrawJoint => {
+                if let Some(joint) = draw_process_joint(&mut draw_mouse_storage, &camera, &mut phys_object) {
+                    add_joint(&mut forces, joint);
+                }
+            }
+            //Stay in spring mode, as a structure can need lots of them
+            MouseMode::DrawSpring => {
+                if let Some(spring) = draw_process_spring(&mut draw_mouse_storage, &camera, &mut phys_object) {
+                    forces.push(Box::new(spring));
+                }
+            }
+            MouseMode::DrawWind => {
+                if let Some(wind) = draw_process_wind(&mut draw_mouse_storage, &camera) {
+                    forces.push(Box::new(wind));
+                    mouse_mode = MouseMode::Drag;
+                }
+            }
+            MouseMode::PlaceAttractor => {
+                if let Some(attractor) = place_process_attractor(&camera) {
+                    forces.push(Box::new(attractor));
+                    mouse_mode = MouseMode::Drag;
+                }
+            }
+            MouseMode::DrawSph => {
+                if let Some(fluid) = draw_process_sph(&mut draw_mouse_storage, &camera) {
+                    forces.push(Box::new(fluid));
+                    mouse_mode = MouseMode::Drag;
+                }
+            }
+            //Stay in impulse mode, so the user can keep knocking objects around
+            MouseMode::Impulse => {
+                impulse_process(&mut draw_mouse_storage, &camera, &mut phys_object, &impulse_settings);
+            }
+            //Stay in slingshot mode, so the user can keep launching objects
+            MouseMode::Slingshot => {
+                slingshot_process(&mut slingshot_handle, &camera, &mut phys_object);
+            }
+            MouseMode::PlaceEmitter => {
+                if let Some(emitter) = place_process_emitter(&camera) {
+                    forces.push(Box::new(emitter));
+                    mouse_mode = MouseMode::Drag;
+                }
             } //_ => {}
         }
+        //Forget an unfinished fluid polygon if the user swaps to another tool
+        if !matches!(mouse_mode, MouseMode::DrawFluidPolygon) {
+            polygon_corners.clear();
+        }
         //Push the square circle or rectangle into the physics objects list
         if let Some(sqr) = square {
             phys_object.push(Box::new(sqr));
@@ -0,0 +1,150 @@
+use macroquad::math::Vec2;
+use crate::objects::forces::{get_bodies, has_force, Body, ForceGenerator, UniformGravity};
+use crate::objects::physics::{PhysicsObject, PhysicsType};
+
+//The energy and momentum of a single object
+#[derive(Clone, Copy)]
+pub(crate) struct ObjectEnergy {
+    pub(crate) handle: usize,
+    pub(crate) kinetic: f32,
+    pub(crate) potential: f32,
+    pub(crate) momentum: Vec2,
+}
+
+//The totals for the whole scene at one point in time
+#[derive(Clone, Copy)]
+pub(crate) struct Totals {
+    pub(crate) time: f32,
+    pub(crate) kinetic: f32,
+    pub(crate) potential: f32,
+    pub(crate) spring: f32,
+    pub(crate) momentum: Vec2,
+    pub(crate) angular_momentum: f32,
+}
+
+//Store the energy and momentum of every object, the totals, and the totals to compare against
+pub(crate) struct Diagnostics {
+    pub(crate) objects: Vec<ObjectEnergy>,
+    pub(crate) totals: Totals,
+    pub(crate) reference: Option<Totals>,
+}
+
+impl Totals {
+    fn new(time: f32) -> Totals {
+        Totals { time, kinetic: 0.0, potential: 0.0, spring: 0.0, momentum: Vec2::ZERO, angular_momentum: 0.0 }
+    }
+
+    //The total mechanical energy of the scene
+    pub(crate) fn get_energy(&self) -> f32 {
+        self.kinetic + self.potential + self.spring
+    }
+}
+
+impl Diagnostics {
+    pub(crate) fn new() -> Diagnostics {
+        Diagnostics { objects: Vec::new(), totals: Totals::new(0.0), reference: None }
+    }
+
+    //Measure the energy and momentum of every object that can move.
+    //Heights are measured upwards from y = 0, and angular momentum is taken about the origin
+    pub(crate) fn update(&mut self, objects: &mut Vec<Box<dyn PhysicsObject>>, forces: &[Box<dyn ForceGenerator>], time: f32) {
+        self.objects.clear();
+        self.totals = Totals::new(time);
+        //Other kinds of gravity (e.g. between every object) work out their own potential energy
+        let uniform_gravity = has_force::<UniformGravity>(forces);
+        let bodies = get_bodies(objects);
+        self.totals.potential += forces.iter().map(|generator| generator.get_field_energy(&bodies)).sum::<f32>();
+
+        for object in objects {
+            if matches!(object.get_physics_type(), PhysicsType::Static) {
+                continue;
+            }
+            let mass = object.get_material().mass;
+            let velocity = object.get_velocity();
+            let centre = object.get_render_shape_reference().get_centre();
+
+            //KE = 1/2 m v^2, GPE = m g h and p = m v
+            let kinetic = 0.5 * mass * velocity.length_squared();
+            let potential = if uniform_gravity && matches!(object.get_physics_type(), PhysicsType::Dynamic) {
+                mass * *object.get_gravity() * -centre.y
+            } else { 0.0 };
+            let momentum = velocity * mass;
+
+            self.totals.kinetic += kinetic;
+            self.totals.potential += potential;
+            //Energy stored in springs attached to the object
+            let body = Body::new(object.as_mut());
+            self.totals.spring += forces.iter().map(|generator| generator.get_stored_energy(&body)).sum::<f32>();
+            self.totals.momentum += momentum;
+            //L = r x p
+            self.totals.angular_momentum += centre.perp_dot(momentum);
+
+            self.objects.push(ObjectEnergy { handle: object.get_handle(), kinetic, potential, momentum });
+        }
+
+        //Use the first measurement with something in it as the reference, if the user hasn't chosen one
+        if self.reference.is_none() && !self.objects.is_empty() {
+            self.reference = Some(self.totals);
+        }
+    }
+
+    //Compare the scene against its current state from now on
+    pub(crate) fn set_reference(&mut self) {
+        self.reference = Some(self.totals);
+    }
+
+    //Forget the reference, e.g. when the scene is cleared
+    pub(crate) fn clear(&mut self) {
+        self.objects.clear();
+        self.reference = None;
+    }
+
+    //How much the total energy has changed since the reference, as a percentage
+    pub(crate) fn get_energy_drift(&self) -> Option<f32> {
+        let reference = self.reference?;
+        get_drift(self.totals.get_energy() - reference.get_energy(), reference.get_energy().abs())
+    }
+
+    //How much the total momentum has changed since the reference, as a percentage
+    pub(crate) fn get_momentum_drift(&self) -> Option<f32> {
+        let reference = self.reference?;
+        get_drift((self.totals.momentum - reference.momentum).length(), reference.momentum.length())
+    }
+
+    //How much the total angular momentum has changed since the reference, as a percentage
+    pub(crate) fn get_angular_momentum_drift(&self) -> Option<f32> {
+        let reference = self.reference?;
+        get_drift(self.totals.angular_momentum - reference.angular_momentum, reference.angular_momentum.abs())
+    }
+}
+
+//Turn a change into a percentage of the reference, unless the reference is (almost) zero
+fn get_drift(change: f32, reference: f32) -> Option<f32> {
+    if reference < 1e-6 {
+        return None;
+    }
+    Some(change / reference * 100.)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::events::EventLog;
+    use crate::objects::scene::Scene;
+    use super::Diagnostics;
+
+    #[test]
+    fn falling_object_keeps_its_energy() {
+        let mut scene = Scene::load_test("falling_energy", "circle x=0 y=-20 radius=0.2 mass=2 type=dynamic air=false\n");
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.update(&mut scene.objects, &scene.forces, 0.0);
+        let gravity = *scene.objects[0].get_gravity();
+        assert!((diagnostics.totals.potential - 2. * gravity * 20.).abs() < 1e-3);
+
+        //Potential energy turns into kinetic energy as it falls, and it gains m g t of momentum
+        scene.run(&mut EventLog::new(), 60);
+        diagnostics.update(&mut scene.objects, &scene.forces, 1.0);
+        assert!(diagnostics.totals.kinetic > 0.0);
+        assert!(diagnostics.get_energy_drift().unwrap().abs() < 1.0, "energy drifted by {}%", diagnostics.get_energy_drift().unwrap());
+        assert!((diagnostics.totals.momentum.y - 2. * gravity).abs() < 1e-2);
+    }
+}
@@ -1,10 +1,13 @@
 #![allow(unused)]
-use macroquad::color::{RED, YELLOW};
 use macroquad::time::get_frame_time;
 use macroquad::math::Vec2;
-use macroquad::prelude::{rand, WHITE};
-use macroquad::rand::gen_range;
+use macroquad::prelude::WHITE;
 use macroquad::shapes::{draw_circle, draw_rectangle_lines};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+pub(crate) mod diagnostics;
+pub(crate) mod trails;
 
 //Rect used to store the area for a Q-Tree, or
 #[derive(Clone, Copy)]
@@ -22,11 +25,19 @@ pub struct Point {
     pub index: usize,
 }
 
+//The smallest a QuadTree can be split down to, so points in the same place can't make it split forever
+const MIN_QUAD_SIZE: f32 = 1e-4;
+
 pub struct QuadTree {
     boundary: Rect,
     capacity: usize,
     points: Vec<Point>,
     subdivided: bool,
+    //Whether every point is kept in exactly one quad, instead of in every quad whose edge it is on
+    unique: bool,
+    //Total mass and centre of mass of every point inside, used for Barnes-Hut gravity
+    mass: f32,
+    centre_of_mass: Vec2,
     ne: Option<Box<QuadTree>>,
     nw: Option<Box<QuadTree>>,
     se: Option<Box<QuadTree>>,
@@ -63,6 +74,9 @@ impl QuadTree {
             capacity,
             points: vec,
             subdivided: false,
+            unique: false,
+            mass: 0.0,
+            centre_of_mass: Vec2::new(boundary.x, boundary.y),
             ne: None,
             nw: None,
             se: None,
@@ -70,22 +84,38 @@ impl QuadTree {
         }
     }
 
+    //Make a QuadTree where every point is in exactly one quad, even if it is on the edge between them.
+    //Barnes-Hut gravity and the fluids add up every point they find, so they can't have a point in two quads
+    pub(crate) fn new_unique(boundary: Rect, capacity: usize) -> QuadTree {
+        QuadTree { unique: true, ..QuadTree::new(boundary, capacity) }
+    }
+
     fn subdivide(&mut self) {
         let x = self.boundary.x;
         let y = self.boundary.y;
         let w = self.boundary.w;
         let h = self.boundary.h;
+        let quad = |boundary: Rect| Some(Box::new(QuadTree { unique: self.unique, ..QuadTree::new(boundary, self.capacity) }));
 
-        self.ne = Some(Box::new(QuadTree::new(Rect::new(x+w/2., y-h/2., w/2., h/2.), self.capacity)));
-        self.nw = Some(Box::new(QuadTree::new(Rect::new(x-w/2., y-h/2., w/2., h/2.), self.capacity)));
-        self.se = Some(Box::new(QuadTree::new(Rect::new(x+w/2., y+h/2., w/2., h/2.), self.capacity)));
-        self.sw = Some(Box::new(QuadTree::new(Rect::new(x-w/2., y+h/2., w/2., h/2.), self.capacity)));
+        self.ne = quad(Rect::new(x+w/2., y-h/2., w/2., h/2.));
+        self.nw = quad(Rect::new(x-w/2., y-h/2., w/2., h/2.));
+        self.se = quad(Rect::new(x+w/2., y+h/2., w/2., h/2.));
+        self.sw = quad(Rect::new(x-w/2., y+h/2., w/2., h/2.));
 
-        for i in &mut self.points {
-            self.ne.as_mut().unwrap().insert(i.clone());
-            self.nw.as_mut().unwrap().insert(i.clone());
-            self.se.as_mut().unwrap().insert(i.clone());
-            self.sw.as_mut().unwrap().insert(i.clone());
+        if self.unique {
+            //Move each point into only one of the new quads
+            for i in std::mem::take(&mut self.points) {
+                let _ = self.ne.as_mut().unwrap().insert(i) || self.nw.as_mut().unwrap().insert(i)
+                    || self.se.as_mut().unwrap().insert(i) || self.sw.as_mut().unwrap().insert(i);
+            }
+            return;
+        }
+        //Points on the edge between the new quads go into all of them, so collisions across the edge are still found
+        for i in &self.points {
+            self.ne.as_mut().unwrap().insert(*i);
+            self.nw.as_mut().unwrap().insert(*i);
+            self.se.as_mut().unwrap().insert(*i);
+            self.sw.as_mut().unwrap().insert(*i);
         }
         self.points.clear();
     }
@@ -96,7 +126,7 @@ impl QuadTree {
             return false;
         }
 
-        if self.points.len() < self.capacity  && !self.subdivided {
+        if (self.points.len() < self.capacity || self.boundary.w.max(self.boundary.h) < MIN_QUAD_SIZE) && !self.subdivided {
             self.points.push(point);
             return true;
         } else {
@@ -141,6 +171,60 @@ impl QuadTree {
         found
     }
 
+    //Work out the total mass and centre of mass of every quad, from the mass of each point's object
+    pub(crate) fn compute_mass(&mut self, masses: &[f32]) {
+        let mut mass = 0.0;
+        let mut moment = Vec2::ZERO;
+        for p in &self.points {
+            mass += masses[p.index];
+            moment += Vec2::new(p.x, p.y) * masses[p.index];
+        }
+        if self.subdivided {
+            for quad in [&mut self.ne, &mut self.nw, &mut self.se, &mut self.sw].into_iter().flatten() {
+                quad.compute_mass(masses);
+                mass += quad.mass;
+                moment += quad.centre_of_mass * quad.mass;
+            }
+        }
+        self.mass = mass;
+        if mass > 0.0 {
+            self.centre_of_mass = moment / mass;
+        }
+    }
+
+    //Find the gravitational field (per unit G) at a point from every other point, using Barnes-Hut:
+    //quads that are small compared to their distance (size / distance < theta) are treated as one mass at their centre of mass.
+    //A theta of 0 adds up every point exactly. The tree must be made with new_unique, and compute_mass called first
+    pub(crate) fn gravity_at(&self, target: Point, masses: &[f32], theta: f32, softening: f32) -> Vec2 {
+        let position = Vec2::new(target.x, target.y);
+        let field = |mass: f32, offset: Vec2| {
+            let distance_sqr = offset.length_squared() + softening * softening;
+            if distance_sqr == 0.0 { Vec2::ZERO } else { offset * mass / (distance_sqr * distance_sqr.sqrt()) }
+        };
+        if self.mass == 0.0 {
+            return Vec2::ZERO;
+        }
+
+        //A quad with the target inside is always opened, so the target is never part of a mass pulling on itself
+        let offset = self.centre_of_mass - position;
+        if self.subdivided && !self.boundary.contains(target) && self.boundary.w.max(self.boundary.h) * 2. < theta * offset.length() {
+            return field(self.mass, offset);
+        }
+
+        let mut total = Vec2::ZERO;
+        for p in &self.points {
+            if p.index != target.index {
+                total += field(masses[p.index], Vec2::new(p.x, p.y) - position);
+            }
+        }
+        if self.subdivided {
+            for quad in [&self.ne, &self.nw, &self.se, &self.sw].into_iter().flatten() {
+                total += quad.gravity_at(target, masses, theta, softening);
+            }
+        }
+        total
+    }
+
     //Optional test feature, allows the user to see the QuadTree
     pub(crate) fn show(&mut self) {
         draw_rectangle_lines(self.boundary.x - self.boundary.w, self.boundary.y - self.boundary.h,
@@ -159,45 +243,60 @@ impl QuadTree {
     }
 }
 
-//PLEASE DELETE NON PERMINANT TEST STRUCT!
+//The fixed timestep used in deterministic mode, stored as the bits of an f32 (0 means it is not being used)
+static FIXED_DT: AtomicU32 = AtomicU32::new(0);
 
-//NON PERMINANT TEST STRUCT: PARTICLE
-#[derive(Clone, Copy)]
-pub struct Particle {
-    pub(crate) x: f32,
-    pub(crate) y: f32,
-    pub(crate) r: f32,
-    pub highlight: bool,
+//Use a fixed timestep instead of the frame time, or go back to the frame time with None
+pub(crate) fn set_fixed_dt(timestep: Option<f32>) {
+    FIXED_DT.store(timestep.map_or(0, f32::to_bits), Ordering::Relaxed);
 }
 
-impl Particle {
-    pub fn new(x: f32, y: f32, r: f32) -> Particle {
-        Particle {x, y, r, highlight: false}
+//Get the delta time
+pub(crate) fn dt() -> f32 {
+    let fixed = FIXED_DT.load(Ordering::Relaxed);
+    if fixed != 0 {
+        return f32::from_bits(fixed);
     }
+    get_frame_time()
+}
 
-    pub fn move_process(&mut self) {
-        self.x += rand::gen_range(gen_range(-1.0, -0.1), 0.4);
-        self.y += rand::gen_range(gen_range(-1.0, -0.1), 0.4);
-    }
+//The random number generator used by the simulation: the same PCG generator as macroquad's, but with a state that
+//can be read, so it can be saved with a scene. It is kept per thread, so tests running side by side don't share it
+const RANDOM_MULTIPLIER: u64 = 6364136223846793005;
+const RANDOM_INCREMENT: u64 = 1442695040888963407;
+thread_local! {
+    static RANDOM_STATE: Cell<u64> = const { Cell::new(0) };
+}
 
-    pub fn render(&self) {
-        if self.highlight {
-            draw_circle(self.x, self.y, self.r+0.1, RED);
-        }else {
-            draw_circle(self.x, self.y, self.r, YELLOW);
-        }
-    }
-    pub fn intersects(&self, other: &Particle) -> bool {
-        let dist = distance_sqr(Vec2::new(self.x, self.y), Vec2::new(other.x, other.y));
-        dist <= (self.r + other.r).powf(2.0)
-    }
+//Start the random numbers again from a seed
+pub(crate) fn seed_random(seed: u64) {
+    set_random_state(0);
+    next_random();
+    set_random_state(get_random_state().wrapping_add(seed));
+    next_random();
+}
 
+//Get the state of the random number generator, e.g. to save it
+pub(crate) fn get_random_state() -> u64 {
+    RANDOM_STATE.with(|state| state.get())
 }
 
+//Carry on from a saved state of the random number generator
+pub(crate) fn set_random_state(new_state: u64) {
+    RANDOM_STATE.with(|state| state.set(new_state));
+}
 
-//Get the delta time
-pub(crate) fn dt() -> f32 {
-    get_frame_time()
+fn next_random() -> u32 {
+    let old = get_random_state();
+    set_random_state(old.wrapping_mul(RANDOM_MULTIPLIER).wrapping_add(RANDOM_INCREMENT));
+    let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+    xorshifted.rotate_right((old >> 59) as u32)
+}
+
+//Get a random number from low up to (but not including) high
+pub(crate) fn random_range(low: f32, high: f32) -> f32 {
+    let fraction = next_random() as f64 / (u32::MAX as f64 + 1.0);
+    (low as f64 + (high as f64 - low as f64) * fraction) as f32
 }
 
 //Convert meters into pixels
@@ -0,0 +1,61 @@
+use std::collections::{HashMap, VecDeque};
+use macroquad::color::Color;
+use macroquad::math::Vec2;
+use macroquad::shapes::draw_line;
+use crate::measurements::meter;
+use crate::objects::physics::{PhysicsObject, PhysicsType};
+
+//The most points kept for each trail, so old parts of the path fade away
+const MAX_TRAIL_POINTS: usize = 600;
+//How far an object must move before another point is added (m)
+const MIN_TRAIL_SPACING: f32 = 0.02;
+
+//The recent path of every moving object, drawn as a fading line behind it
+pub(crate) struct Trails {
+    paths: HashMap<usize, (Color, VecDeque<Vec2>)>,
+}
+
+impl Trails {
+    pub(crate) fn new() -> Trails {
+        Trails { paths: HashMap::new() }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.paths.clear();
+    }
+
+    //Add where every dynamic object is now to its trail, and forget the trails of objects that have been deleted
+    pub(crate) fn update(&mut self, objects: &mut [Box<dyn PhysicsObject>]) {
+        let handles: Vec<usize> = objects.iter().map(|object| object.get_handle()).collect();
+        self.paths.retain(|handle, _| handles.contains(handle));
+
+        for object in objects.iter_mut() {
+            if !matches!(object.get_physics_type(), PhysicsType::Dynamic) {
+                continue;
+            }
+            let (centre, colour) = {
+                let shape = object.get_render_shape_reference();
+                (shape.get_centre(), shape.get_colour())
+            };
+            let (path_colour, path) = self.paths.entry(object.get_handle()).or_insert((colour, VecDeque::new()));
+            *path_colour = colour;
+            if path.back().is_none_or(|last| last.distance(centre) >= MIN_TRAIL_SPACING) {
+                path.push_back(centre);
+                if path.len() > MAX_TRAIL_POINTS {
+                    path.pop_front();
+                }
+            }
+        }
+    }
+
+    //Draw every trail, fading out towards the oldest point
+    pub(crate) fn draw(&self) {
+        for (colour, path) in self.paths.values() {
+            let length = path.len() as f32;
+            for (index, (start, end)) in path.iter().zip(path.iter().skip(1)).enumerate() {
+                let fade = Color::new(colour.r, colour.g, colour.b, 0.8 * (index + 1) as f32 / length);
+                draw_line(meter(start.x), meter(start.y), meter(end.x), meter(end.y), 2., fade);
+            }
+        }
+    }
+}
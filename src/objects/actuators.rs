@@ -0,0 +1,298 @@
+use macroquad::color::{Color, GRAY};
+use macroquad::math::Vec2;
+use macroquad::shapes::{draw_circle, draw_circle_lines, draw_line};
+use crate::measurements::{dt, meter};
+use crate::objects::forces::{Body, ForceGenerator};
+use crate::objects::physics::{PhysicsObject, PhysicsType};
+
+//How long (s) between keyframes added with the mouse
+const KEYFRAME_GAP: f32 = 1.0;
+
+//What the keyframes of an actuator give
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ActuatorMode {
+    //Positions (m) for the centre of the object to move through
+    Path,
+    //The velocity (m/s) of the object
+    Velocity,
+    //The angular velocity (rad/s, in x) the object orbits the pivot at. The object itself doesn't turn, it just moves
+    //around a circle, like a car on a big wheel
+    Orbit,
+}
+
+//What happens once the last keyframe has been reached
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Repeat {
+    //Stay at the last keyframe
+    Once,
+    //Jump back to the first keyframe and go again
+    Loop,
+    //Go back through the keyframes the other way, like a piston
+    PingPong,
+}
+
+//Drives a kinematic object along a keyframed path, or at a velocity or around a pivot at a speed that changes over time.
+//The object is given the velocity to reach the next point of its motion, so it pushes dynamic objects out of the way
+//(e.g. a lift or a piston) and does exactly the same thing every time it is run
+pub(crate) struct Actuator {
+    pub(crate) handle: usize,
+    pub(crate) mode: ActuatorMode,
+    pub(crate) repeat: Repeat,
+    //The time (s) of each keyframe and its value, in time order. Values between keyframes are found by linear interpolation
+    pub(crate) keyframes: Vec<(f32, Vec2)>,
+    pub(crate) pivot: Vec2,
+    pub(crate) running: bool,
+    //How far through the motion (s), and how far round the pivot the object has gone (rad)
+    time: f32,
+    angle: f32,
+    //Where the object's centre was before it started moving, which velocity and orbit actuators go back to
+    start: Option<Vec2>,
+    //Move the object back to the start of its motion on the next step
+    restart: bool,
+}
+
+//What the actuator panel gives the next keyframe it adds
+#[allow(dead_code)]
+pub(crate) struct ActuatorSettings {
+    pub(crate) mode: ActuatorMode,
+    //The velocity (m/s) for velocity keyframes
+    pub(crate) velocity: Vec2,
+    //The angular velocity (rad/s) for orbit keyframes
+    pub(crate) angular_velocity: f32,
+    //How far (m) to the right of the object the pivot of a new orbit is put
+    pub(crate) radius: f32,
+}
+
+#[allow(dead_code)]
+impl ActuatorSettings {
+    pub(crate) fn new() -> ActuatorSettings {
+        ActuatorSettings { mode: ActuatorMode::Path, velocity: Vec2::new(1.0, 0.0), angular_velocity: 1.0, radius: 1.0 }
+    }
+
+    //The value of a keyframe added now, for an object with its centre at a point
+    pub(crate) fn get_keyframe_value(&self, centre: Vec2) -> Vec2 {
+        match self.mode {
+            ActuatorMode::Path => centre,
+            ActuatorMode::Velocity => self.velocity,
+            ActuatorMode::Orbit => Vec2::new(self.angular_velocity, 0.0),
+        }
+    }
+}
+
+impl ActuatorMode {
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            ActuatorMode::Path => "path",
+            ActuatorMode::Velocity => "velocity",
+            ActuatorMode::Orbit => "orbit",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<ActuatorMode> {
+        match name {
+            "path" => Some(ActuatorMode::Path),
+            "velocity" => Some(ActuatorMode::Velocity),
+            "orbit" => Some(ActuatorMode::Orbit),
+            _ => None,
+        }
+    }
+
+    //The next mode, for the button that cycles through them
+    #[allow(dead_code)]
+    pub(crate) fn next(&self) -> ActuatorMode {
+        match self {
+            ActuatorMode::Path => ActuatorMode::Velocity,
+            ActuatorMode::Velocity => ActuatorMode::Orbit,
+            ActuatorMode::Orbit => ActuatorMode::Path,
+        }
+    }
+}
+
+impl Repeat {
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            Repeat::Once => "once",
+            Repeat::Loop => "loop",
+            Repeat::PingPong => "pingpong",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Repeat> {
+        match name {
+            "once" => Some(Repeat::Once),
+            "loop" => Some(Repeat::Loop),
+            "pingpong" => Some(Repeat::PingPong),
+            _ => None,
+        }
+    }
+
+    //The next way of repeating, for the button that cycles through them
+    #[allow(dead_code)]
+    pub(crate) fn next(&self) -> Repeat {
+        match self {
+            Repeat::Once => Repeat::Loop,
+            Repeat::Loop => Repeat::PingPong,
+            Repeat::PingPong => Repeat::Once,
+        }
+    }
+}
+
+impl Actuator {
+    pub(crate) fn new(handle: usize, mode: ActuatorMode, keyframes: Vec<(f32, Vec2)>) -> Actuator {
+        Actuator {
+            handle,
+            mode,
+            repeat: Repeat::Loop,
+            keyframes,
+            pivot: Vec2::ZERO,
+            running: true,
+            time: 0.0,
+            angle: 0.0,
+            start: None,
+            restart: true,
+        }
+    }
+
+    //Add a keyframe a second after the last one
+    #[allow(dead_code)]
+    pub(crate) fn add_keyframe(&mut self, value: Vec2) {
+        let time = self.keyframes.last().map_or(0.0, |(time, _)| time + KEYFRAME_GAP);
+        self.keyframes.push((time, value));
+    }
+
+    //Move the object back to the start of its motion
+    #[allow(dead_code)]
+    pub(crate) fn reset(&mut self) {
+        self.restart = true;
+    }
+
+    //How long (s) it takes to go through every keyframe once
+    pub(crate) fn get_duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |(time, _)| *time)
+    }
+
+    //How far (s) through the keyframes the motion is now
+    #[allow(dead_code)]
+    pub(crate) fn get_time(&self) -> f32 {
+        self.get_profile_time().0
+    }
+
+    //How far (s) through the keyframes the motion is, and whether it is going back through them
+    fn get_profile_time(&self) -> (f32, bool) {
+        let duration = self.get_duration();
+        if duration <= 0.0 {
+            return (0.0, false);
+        }
+        match self.repeat {
+            Repeat::Once => (self.time.min(duration), false),
+            Repeat::Loop => (self.time % duration, false),
+            Repeat::PingPong => {
+                let time = self.time % (2. * duration);
+                if time > duration { (2. * duration - time, true) } else { (time, false) }
+            }
+        }
+    }
+
+    //Find the value at a time, between the keyframes either side of it
+    fn sample(&self, time: f32) -> Vec2 {
+        match self.keyframes.iter().position(|(key_time, _)| *key_time > time) {
+            None => self.keyframes.last().map_or(Vec2::ZERO, |(_, value)| *value),
+            Some(0) => self.keyframes[0].1,
+            Some(index) => {
+                let ((time_1, value_1), (time_2, value_2)) = (self.keyframes[index - 1], self.keyframes[index]);
+                value_1.lerp(value_2, (time - time_1) / (time_2 - time_1))
+            }
+        }
+    }
+}
+
+impl ForceGenerator for Actuator {
+    //Actuators move objects by setting their velocity, rather than pushing them with a force
+    fn get_force(&self, _body: &Body) -> Vec2 {
+        Vec2::ZERO
+    }
+
+    //Give the object the velocity that takes it to where it should be at the end of this step.
+    //Only kinematic objects are driven, so changing the object to dynamic lets it go
+    fn drive(&mut self, objects: &mut [Box<dyn PhysicsObject>]) {
+        let Some(object) = objects.iter_mut().find(|object| object.get_handle() == self.handle) else { return; };
+        if !matches!(object.get_physics_type(), PhysicsType::Kinematic) || self.keyframes.is_empty() {
+            return;
+        }
+        let mut centre = object.get_render_shape_reference().get_centre();
+        let start = *self.start.get_or_insert(centre);
+        if self.restart {
+            self.restart = false;
+            self.time = 0.0;
+            self.angle = 0.0;
+            let home = if self.mode == ActuatorMode::Path { self.keyframes[0].1 } else { start };
+            *object.get_render_shape_reference().get_pos() += home - centre;
+            centre = home;
+        }
+        if !self.running {
+            object.set_velocity(Vec2::ZERO);
+            return;
+        }
+
+        let (last_time, _) = self.get_profile_time();
+        self.time += dt();
+        let (time, backwards) = self.get_profile_time();
+        //A looping path jumps from its last keyframe back to its first. Moving there in one step would fling anything
+        //riding on the object, so it is put there directly instead
+        if self.mode == ActuatorMode::Path && self.repeat == Repeat::Loop && time < last_time {
+            *object.get_render_shape_reference().get_pos() += self.sample(time) - centre;
+            object.set_velocity(Vec2::ZERO);
+            return;
+        }
+        //Going back through a velocity profile goes the other way, so the object ends up where it started
+        let direction = if backwards { -1. } else { 1. };
+        let velocity = match self.mode {
+            ActuatorMode::Path => (self.sample(time) - centre) / dt(),
+            ActuatorMode::Velocity => self.sample(time) * direction,
+            ActuatorMode::Orbit => {
+                self.angle += self.sample(time).x * direction * dt();
+                let target = self.pivot + Vec2::from_angle(self.angle).rotate(start - self.pivot);
+                (target - centre) / dt()
+            }
+        };
+        object.set_velocity(velocity);
+    }
+
+    //Draw the path the object follows, or the pivot it orbits
+    fn draw(&self, bodies: &[Body]) {
+        let colour = Color::new(GRAY.r, GRAY.g, GRAY.b, 0.8);
+        match self.mode {
+            ActuatorMode::Path => {
+                for pair in self.keyframes.windows(2) {
+                    let (a, b) = (pair[0].1, pair[1].1);
+                    draw_line(meter(a.x), meter(a.y), meter(b.x), meter(b.y), 2., colour);
+                }
+                for (_, point) in &self.keyframes {
+                    draw_circle(meter(point.x), meter(point.y), 4., colour);
+                }
+            }
+            ActuatorMode::Orbit => {
+                draw_circle(meter(self.pivot.x), meter(self.pivot.y), 4., colour);
+                if let Some(body) = bodies.iter().find(|body| body.handle == self.handle) {
+                    draw_circle_lines(meter(self.pivot.x), meter(self.pivot.y), meter(body.centre.distance(self.pivot)), 1., colour);
+                }
+            }
+            ActuatorMode::Velocity => {}
+        }
+    }
+
+    fn hash_state(&self, add: &mut dyn FnMut(u32)) {
+        add(self.time.to_bits());
+        add(self.angle.to_bits());
+        add(self.running as u32);
+    }
+
+    //Keyframes are written as time:x:y, or time:angular velocity for orbiting
+    fn to_line(&self, index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        let keys: Vec<String> = self.keyframes.iter()
+            .map(|(time, value)| if self.mode == ActuatorMode::Orbit { format!("{}:{}", time, value.x) } else { format!("{}:{}:{}", time, value.x, value.y) })
+            .collect();
+        Some(format!("actuator object={} mode={} repeat={} keys={} px={} py={} running={}", index_of(self.handle)?, self.mode.get_name(),
+                     self.repeat.get_name(), keys.join(","), self.pivot.x, self.pivot.y, self.running))
+    }
+}
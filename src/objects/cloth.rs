@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use macroquad::color::{Color, RED, WHITE};
+use macroquad::math::Vec2;
+use macroquad::shapes::{draw_line, draw_rectangle};
+use macroquad::text::draw_text;
+use crate::measurements::{dt, meter};
+use crate::objects::forces::{Body, ForceGenerator};
+use crate::objects::particles::get_contact;
+
+//How many times each step the links are pulled back to their length. Fewer and long ropes stretch like elastic
+const ITERATIONS: usize = 30;
+//The radius (m) of each point when it hits an object
+const POINT_RADIUS: f32 = 0.03;
+//Points are treated as small spheres when working out drag
+const POINT_DRAG_COEFFICIENT: f32 = 0.47;
+//Rope and cloth points aren't objects, so they are given a handle no object can have
+const CLOTH_HANDLE: usize = usize::MAX;
+
+//Whether the points are joined in a line or a grid
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ClothKind {
+    Rope,
+    Cloth,
+}
+
+//A point mass, moved by Verlet integration so its velocity is how far it moved last step
+struct VerletPoint {
+    pos: Vec2,
+    old_pos: Vec2,
+    //Pinned points are the anchors, which never move
+    pinned: bool,
+}
+
+//A link between two points that can go slack but can't stretch past its length, like a piece of string
+struct Link {
+    a: usize,
+    b: usize,
+    rest_length: f32,
+    //The force (N) the link pulled its points together with this step
+    tension: f32,
+}
+
+//A rope or a hanging cloth, made of point masses joined by links. It hangs between two anchors, is pushed by every other
+//force generator (gravity, drag, wind...), collides with objects and pushes dynamic objects back.
+//A link snaps if its tension is more than the tear tension
+pub(crate) struct Cloth {
+    pub(crate) kind: ClothKind,
+    //The two anchors the rope hangs between, or the top corners of the cloth
+    pub(crate) start: Vec2,
+    pub(crate) end: Vec2,
+    //The length of a rope, or how far down a cloth hangs (m)
+    pub(crate) length: f32,
+    //The distance between points (m) and the mass of each one (kg)
+    pub(crate) spacing: f32,
+    pub(crate) point_mass: f32,
+    //The tension (N) a link snaps at, 0 for links that never break
+    pub(crate) tear_tension: f32,
+    //The acceleration (m/s^2) of gravity on the points, the same as an object's gravity
+    pub(crate) gravity: f32,
+    pub(crate) colour: Color,
+    points: Vec<VerletPoint>,
+    links: Vec<Link>,
+    //How many links there were before any snapped
+    link_count: usize,
+    //The force on every object from the points that hit it this step
+    body_forces: HashMap<usize, Vec2>,
+}
+
+impl ClothKind {
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            ClothKind::Rope => "rope",
+            ClothKind::Cloth => "cloth",
+        }
+    }
+}
+
+impl Cloth {
+    pub(crate) fn new(kind: ClothKind, start: Vec2, end: Vec2, length: f32) -> Cloth {
+        let mut cloth = Cloth {
+            kind,
+            start,
+            end,
+            length,
+            spacing: 0.1,
+            point_mass: 0.02,
+            tear_tension: 0.0,
+            gravity: 9.81,
+            colour: WHITE,
+            points: Vec::new(),
+            links: Vec::new(),
+            link_count: 0,
+            body_forces: HashMap::new(),
+        };
+        cloth.reset();
+        cloth
+    }
+
+    //Make the points and links again from the settings, joining back together any that have snapped
+    pub(crate) fn reset(&mut self) {
+        self.points.clear();
+        self.links.clear();
+        let spacing = self.spacing.max(0.01);
+        let width = self.start.distance(self.end);
+        let point = |pos: Vec2, pinned: bool| VerletPoint { pos, old_pos: pos, pinned };
+        match self.kind {
+            //A line of points from one anchor to the other. A rope longer than the gap starts slack and sags into a catenary
+            ClothKind::Rope => {
+                let length = self.length.max(width);
+                let segments = (length / spacing).ceil().max(1.0) as usize;
+                for index in 0..=segments {
+                    self.points.push(point(self.start.lerp(self.end, index as f32 / segments as f32), index == 0 || index == segments));
+                }
+                for index in 0..segments {
+                    self.links.push(Link { a: index, b: index + 1, rest_length: length / segments as f32, tension: 0.0 });
+                }
+            }
+            //A grid hanging straight down from the line between the anchors, pinned at its top corners
+            ClothKind::Cloth => {
+                let columns = (width / spacing).ceil().max(1.0) as usize;
+                let rows = (self.length / spacing).ceil().max(1.0) as usize;
+                let across = (self.end - self.start) / columns as f32;
+                let down = Vec2::new(0.0, self.length.max(spacing) / rows as f32);
+                let index = |column: usize, row: usize| row * (columns + 1) + column;
+                for row in 0..=rows {
+                    for column in 0..=columns {
+                        self.points.push(point(self.start + across * column as f32 + down * row as f32,
+                                               row == 0 && (column == 0 || column == columns)));
+                    }
+                }
+                for row in 0..=rows {
+                    for column in 0..=columns {
+                        if column < columns {
+                            self.links.push(Link { a: index(column, row), b: index(column + 1, row), rest_length: across.length(), tension: 0.0 });
+                        }
+                        if row < rows {
+                            self.links.push(Link { a: index(column, row), b: index(column, row + 1), rest_length: down.y, tension: 0.0 });
+                        }
+                    }
+                }
+            }
+        }
+        self.link_count = self.links.len();
+    }
+
+    //How many links there are, and how many have snapped
+    #[allow(dead_code)]
+    pub(crate) fn get_link_counts(&self) -> (usize, usize) {
+        (self.links.len(), self.link_count - self.links.len())
+    }
+
+    //The highest tension (N) in any link this step
+    #[allow(dead_code)]
+    pub(crate) fn get_max_tension(&self) -> f32 {
+        self.links.iter().fold(0.0, |highest, link| highest.max(link.tension))
+    }
+
+    //Describe a point the same way as an object, so the other generators can push it
+    fn get_body(&self, point: &VerletPoint) -> Body {
+        Body {
+            handle: CLOTH_HANDLE,
+            centre: point.pos,
+            velocity: (point.pos - point.old_pos) / dt(),
+            mass: self.point_mass,
+            area: PI * POINT_RADIUS * POINT_RADIUS,
+            gravity: self.gravity,
+            drag_coefficient: POINT_DRAG_COEFFICIENT,
+            do_air_resistance: true,
+            half_size: Vec2::splat(POINT_RADIUS),
+            circle: true,
+            charge: 0.0,
+            dynamic: true,
+        }
+    }
+
+    //Pull every stretched link back to its length, moving the free ends (position based dynamics), then push the points out of
+    //any objects they have gone into. Moving a point changes its velocity, as Verlet integration works the velocity out from how
+    //far it moved. A dynamic object is moved back too, sharing the push by mass, and it is moved by that much more than it would
+    //have been with the force F = m x / dt^2. The tension is found the same way, from how far the link moved its points
+    fn solve(&mut self, bodies: &[Body]) -> Vec<Vec2> {
+        for link in &mut self.links {
+            link.tension = 0.0;
+        }
+        //Only the objects near the points can be hit, and they are checked where they will be at the end of the step
+        let corner1 = self.points.iter().fold(Vec2::splat(f32::MAX), |corner, point| corner.min(point.pos)) - POINT_RADIUS;
+        let corner2 = self.points.iter().fold(Vec2::splat(f32::MIN), |corner, point| corner.max(point.pos)) + POINT_RADIUS;
+        let mut near: Vec<(usize, Body)> = bodies.iter().enumerate()
+            .map(|(index, body)| (index, Body { centre: body.centre + body.velocity * dt(), ..*body }))
+            .filter(|(_, body)| (body.centre - body.half_size).cmple(corner2).all() && (body.centre + body.half_size).cmpge(corner1).all())
+            .collect();
+        let mut offsets = vec![Vec2::ZERO; bodies.len()];
+
+        for _ in 0..ITERATIONS {
+            for link in &mut self.links {
+                let (a, b) = (&self.points[link.a], &self.points[link.b]);
+                let offset = b.pos - a.pos;
+                let length = offset.length();
+                let free = (!a.pinned as u8 + !b.pinned as u8) as f32;
+                if length <= link.rest_length || free == 0.0 {
+                    continue;
+                }
+                let correction = (length - link.rest_length) / free;
+                let direction = offset / length;
+                if !self.points[link.a].pinned {
+                    self.points[link.a].pos += direction * correction;
+                }
+                if !self.points[link.b].pinned {
+                    self.points[link.b].pos -= direction * correction;
+                }
+                link.tension += self.point_mass * correction / (dt() * dt());
+            }
+            for point in self.points.iter_mut().filter(|point| !point.pinned) {
+                for (index, body) in near.iter_mut() {
+                    let Some((normal, depth)) = get_contact(body, point.pos, POINT_RADIUS) else { continue; };
+                    let point_share = if body.dynamic { body.mass / (body.mass + self.point_mass) } else { 1.0 };
+                    point.pos += normal * depth * point_share;
+                    body.centre -= normal * depth * (1.0 - point_share);
+                    offsets[*index] -= normal * depth * (1.0 - point_share);
+                }
+            }
+        }
+        offsets
+    }
+}
+
+impl ForceGenerator for Cloth {
+    //The push of the points on an object, from the ones that hit it this step
+    fn get_force(&self, body: &Body) -> Vec2 {
+        self.body_forces.get(&body.handle).copied().unwrap_or(Vec2::ZERO)
+    }
+
+    //Wake any object the rope or cloth is pushing, e.g. one resting on it
+    fn keep_awake(&self, body: &Body) -> bool {
+        self.body_forces.contains_key(&body.handle)
+    }
+
+    fn update(&mut self, others: &dyn Fn(&Body) -> Vec2, bodies: &[Body]) {
+        self.body_forces.clear();
+        //Move every free point by Verlet integration, x' = x + (x - x_old) + a dt^2, using the other generators for a = F / m
+        let forces: Vec<Vec2> = self.points.iter().map(|point| if point.pinned { Vec2::ZERO } else { others(&self.get_body(point)) }).collect();
+        for (point, force) in self.points.iter_mut().zip(forces) {
+            if point.pinned {
+                continue;
+            }
+            let movement = point.pos - point.old_pos;
+            point.old_pos = point.pos;
+            point.pos += movement + force / self.point_mass * dt() * dt();
+        }
+
+        let offsets = self.solve(bodies);
+        if self.tear_tension > 0.0 {
+            let tear_tension = self.tear_tension;
+            self.links.retain(|link| link.tension < tear_tension);
+        }
+        for (body, offset) in bodies.iter().zip(offsets) {
+            if body.dynamic && offset != Vec2::ZERO {
+                self.body_forces.insert(body.handle, offset * body.mass / (dt() * dt()));
+            }
+        }
+    }
+
+    //Draw every link, getting redder the closer it is to snapping, and the anchors with the force pulling on each one
+    fn draw(&self, _bodies: &[Body]) {
+        let thickness = if self.kind == ClothKind::Rope { 3. } else { 1.5 };
+        for link in &self.links {
+            let (a, b) = (self.points[link.a].pos, self.points[link.b].pos);
+            let strain = if self.tear_tension > 0.0 { (link.tension / self.tear_tension).min(1.0) } else { 0.0 };
+            let colour = Color::from_vec(self.colour.to_vec().lerp(RED.to_vec(), strain));
+            draw_line(meter(a.x), meter(a.y), meter(b.x), meter(b.y), thickness, colour);
+        }
+        for (index, point) in self.points.iter().enumerate().filter(|(_, point)| point.pinned) {
+            draw_rectangle(meter(point.pos.x) - 5., meter(point.pos.y) - 5., 10., 10., self.colour);
+            let pull = self.links.iter()
+                .filter_map(|link| {
+                    let other = if link.a == index { link.b } else if link.b == index { link.a } else { return None; };
+                    Some((self.points[other].pos - point.pos).normalize_or_zero() * link.tension)
+                })
+                .fold(Vec2::ZERO, |total, force| total + force);
+            draw_text(&format!("{:.2} N", pull.length()), meter(point.pos.x) + 8., meter(point.pos.y) - 8., 20., WHITE);
+        }
+    }
+
+    //The number of links is included, so a cloth that has torn differently gives a different checksum
+    fn hash_state(&self, add: &mut dyn FnMut(u32)) {
+        add(self.links.len() as u32);
+        for point in &self.points {
+            for value in [point.pos.x, point.pos.y, point.old_pos.x, point.old_pos.y] {
+                add(value.to_bits());
+            }
+        }
+    }
+
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        Some(format!("{} x={} y={} x2={} y2={} length={} spacing={} mass={} tear={} gravity={} colour={},{},{},{}", self.kind.get_name(),
+                     self.start.x, self.start.y, self.end.x, self.end.y, self.length, self.spacing, self.point_mass, self.tear_tension,
+                     self.gravity, self.colour.r, self.colour.g, self.colour.b, self.colour.a))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::events::EventLog;
+    use crate::objects::forces::find_forces;
+    use crate::objects::scene::Scene;
+    use super::Cloth;
+
+    #[test]
+    fn slack_rope_hangs_without_stretching() {
+        let mut scene = Scene::load_test("hanging_rope", "rope x=0 y=0 x2=2 y2=0 length=3 spacing=0.1\n");
+        scene.run(&mut EventLog::new(), 600);
+        let rope = &find_forces::<Cloth>(&mut scene.forces)[0];
+        //The links only stretch by as much as the iterations leave over
+        for link in &rope.links {
+            let length = rope.points[link.a].pos.distance(rope.points[link.b].pos);
+            assert!(length < link.rest_length * 1.02, "a link stretched to {} m", length);
+        }
+        //A 3 m rope between anchors 2 m apart sags about 1 m in the middle
+        let lowest = rope.points.iter().fold(0.0_f32, |lowest, point| lowest.max(point.pos.y));
+        assert!(lowest > 0.8 && lowest < 1.2, "the rope sagged {} m", lowest);
+        assert!(rope.get_max_tension() > 0.0);
+    }
+
+    #[test]
+    fn links_snap_past_the_tear_tension() {
+        let mut scene = Scene::load_test("tearing_cloth", "cloth x=0 y=0 x2=1 y2=0 length=1 spacing=0.1 mass=0.05 tear=0.5\n\
+            cloth x=0 y=0 x2=1 y2=0 length=1 spacing=0.1 mass=0.05\n");
+        scene.run(&mut EventLog::new(), 120);
+        let cloths = find_forces::<Cloth>(&mut scene.forces);
+        assert!(cloths[0].get_link_counts().1 > 0);
+        assert_eq!(cloths[1].get_link_counts().1, 0);
+    }
+
+    #[test]
+    fn ropes_catch_falling_objects() {
+        //The ball lands on the rope and pulls it down, but doesn't fall through
+        let mut scene = Scene::load_test("rope_catch", "rope x=-1 y=0 x2=1 y2=0 length=2.1 spacing=0.05 gravity=0\n\
+            circle x=0 y=-0.5 radius=0.2 mass=0.5 type=dynamic air=false\n");
+        scene.run(&mut EventLog::new(), 240);
+        let ball = scene.objects[0].get_render_shape_reference().get_centre();
+        assert!(ball.y < 0.5, "the ball fell to {} m", ball.y);
+        assert!(scene.objects[0].get_velocity().length() < 0.5);
+    }
+}
@@ -1,13 +1,72 @@
 use macroquad::input::mouse_position;
 use macroquad::prelude::*;
 
-use crate::MouseMode;
-use crate::measurements::vec2_meter;
-use crate::objects::physics::{Material, PhysicsType};
+use crate::measurements::{meter, vec2_meter};
+use crate::objects::cloth::{Cloth, ClothKind};
+use crate::objects::electrostatics::ElectricField;
+use crate::objects::fluids::FluidRegion;
+use crate::objects::forces::{Body, RadialAttractor, Spring, Wind, AIR_DENSITY};
+use crate::objects::gas::GasBox;
+use crate::objects::joints::Joint;
+use crate::objects::magnetism::MagneticField;
+use crate::objects::particles::{EmitterPreset, ParticleEmitter};
+use crate::objects::physics::{Material, PhysicsObject, PhysicsType};
+use crate::objects::sph::SphFluid;
 use crate::objects::shapes::{Circle, Rectangle};
+use crate::objects::zones::{ForceZone, ZoneKind};
 use crate::objects::{Object, Render};
 
+//The settings new springs, wind and attractors start with, which can be changed in the forces panel
+#[allow(dead_code)]
+const SPRING_STIFFNESS: f32 = 10.0;
+#[allow(dead_code)]
+const SPRING_DAMPING: f32 = 1.0;
+//How fast (m/s) the wind blows for every metre the user drags
+#[allow(dead_code)]
+const WIND_SPEED_PER_METER: f32 = 5.0;
+#[allow(dead_code)]
+const ATTRACTOR_STRENGTH: f32 = 10.0;
+#[allow(dead_code)]
+const ATTRACTOR_SOFTENING: f32 = 0.1;
+//The strength (N/C) new electric field regions start with, pushing a 1e-5 C charge with 1 N
+#[allow(dead_code)]
+const ELECTRIC_FIELD_STRENGTH: f32 = 1e5;
+//The strength (T) new magnetic field regions start with, turning a charge of 1 C per kg at 1 rad/s
+#[allow(dead_code)]
+const MAGNETIC_FIELD_STRENGTH: f32 = 1.0;
+
+//How close (px) a click must be to the first corner of a fluid polygon to finish it
+#[allow(dead_code)]
+const POLYGON_CLOSE_DISTANCE: f32 = 10.0;
+
+//The different things the user can do with the mouse
+#[derive(Clone)]
+#[allow(unused)]
+pub(crate) enum MouseMode {
+    Drag,
+    DrawSquare,
+    DrawRectangele,
+    DrawBall,
+    DrawFluid,
+    DrawFluidPolygon,
+    DrawMagnetic,
+    DrawElectric,
+    DrawZone,
+    PlaceEmitter,
+    DrawGas,
+    DrawSph,
+    DrawRope,
+    DrawCloth,
+    DrawJoint,
+    DrawSpring,
+    DrawWind,
+    PlaceAttractor,
+    Impulse,
+    Slingshot,
+}
+
 //Created the rendered shape for a Square
+#[allow(dead_code)]
 fn create_square_render(pos1: Vec2, pos2: Vec2, colour: Color) -> Rectangle {
     //The top corner of the shape
     let pos1 = vec2_meter(pos1);
@@ -37,6 +96,7 @@ fn create_square_render(pos1: Vec2, pos2: Vec2, colour: Color) -> Rectangle {
 }
 
 //Create the render shape of a rectangle
+#[allow(dead_code)]
 fn create_rectangle_render(pos1: Vec2, pos2: Vec2, colour: Color) -> Rectangle {
     //The top and bottom corners of the rectangle
     let pos1 = vec2_meter(pos1);
@@ -51,6 +111,7 @@ fn create_rectangle_render(pos1: Vec2, pos2: Vec2, colour: Color) -> Rectangle {
 }
 
 //Create the render shape of a ball
+#[allow(dead_code)]
 fn create_ball_render(pos1: Vec2, pos2: Vec2, colour: Color) -> Circle {
     //Center and radius positions of the mouse
     let pos1 = vec2_meter(pos1);
@@ -66,6 +127,7 @@ fn create_ball_render(pos1: Vec2, pos2: Vec2, colour: Color) -> Circle {
 }
 
 //Create a square object, by creating the render shape, and the material for the object
+#[allow(dead_code)]
 fn create_square(pos_1: Vec2, pos_2: Vec2) -> Object<Rectangle> {
     let square = create_square_render(pos_1, pos_2, WHITE);
     let material = Material::new(square.get_area() * 0.98, square.get_area());
@@ -73,6 +135,7 @@ fn create_square(pos_1: Vec2, pos_2: Vec2) -> Object<Rectangle> {
 }
 
 //Create a rectangle, by creating the render shape, and the material for the object
+#[allow(dead_code)]
 fn create_rectangle(pos1: Vec2, pos2: Vec2) -> Object<Rectangle> {
     let rect = create_rectangle_render(pos1, pos2, WHITE);
     let material = Material::new(rect.get_area() * 0.89, rect.get_area());
@@ -80,6 +143,7 @@ fn create_rectangle(pos1: Vec2, pos2: Vec2) -> Object<Rectangle> {
 }
 
 //Create a ball, by creating the render shape, and the material for the object
+#[allow(dead_code)]
 fn create_ball(pos1: Vec2, pos2: Vec2) -> Object<Circle> {
     let circle = create_ball_render(pos1, pos2, WHITE);
     let material = Material::new(circle.get_area() * 0.98, circle.get_area());
@@ -87,6 +151,7 @@ fn create_ball(pos1: Vec2, pos2: Vec2) -> Object<Circle> {
 }
 
 //Create the object the user would like to produce
+#[allow(dead_code)]
 pub fn draw_process_square(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<Object<Rectangle>> {
     if draw_process(MouseMode::DrawSquare, first_mouse_pos, camera) {
         //Use the mouse position saved
@@ -104,6 +169,7 @@ pub fn draw_process_square(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D
 }
 
 //Create the object the user would like to produce
+#[allow(dead_code)]
 pub fn draw_process_rectangle(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<Object<Rectangle>> {
     if draw_process(MouseMode::DrawRectangele, first_mouse_pos, camera) {
         //Use the mouse position saved
@@ -121,6 +187,7 @@ pub fn draw_process_rectangle(first_mouse_pos: &mut Option<Vec2>, camera: &Camer
 }
 
 //Create the object the user would like to produce
+#[allow(dead_code)]
 pub fn draw_process_ball(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<Object<Circle>> {
     let pos2 = camera.screen_to_world(Vec2::from(mouse_position()));
     if draw_process(MouseMode::DrawBall, first_mouse_pos, camera) {
@@ -137,7 +204,241 @@ pub fn draw_process_ball(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D)
     None
 }
 
+//Create the fluid region the user would like to produce, filled with water-like fluid by default
+#[allow(dead_code)]
+pub fn draw_process_fluid(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<FluidRegion> {
+    if draw_process(MouseMode::DrawFluid, first_mouse_pos, camera) {
+        //Use the mouse position saved
+        if let Some(pos1) = *first_mouse_pos {
+            //Create the region between the two mouse positions
+            let pos1 = vec2_meter(pos1);
+            let pos2 = vec2_meter(camera.screen_to_world(Vec2::from(mouse_position())));
+            //Clear the saved mouse position
+            *first_mouse_pos = None;
+            return Some(FluidRegion::new(pos1, pos2 - pos1, 1.0, 0.5));
+        }
+    }
+    None
+}
+
+//Add a corner to the fluid polygon wherever the user clicks. Clicking the first corner again, or right clicking, finishes it
+#[allow(dead_code)]
+pub fn draw_process_fluid_polygon(corners: &mut Vec<Vec2>, camera: &Camera2D) -> Option<FluidRegion> {
+    let mouse = camera.screen_to_world(Vec2::from(mouse_position()));
+    //Draw the edges so far, and the edge that would be added next
+    for (start, end) in corners.iter().zip(corners.iter().skip(1).chain([&vec2_meter(mouse)])) {
+        draw_line(meter(start.x), meter(start.y), meter(end.x), meter(end.y), 3., PURPLE);
+    }
+    if mouse_position().1 <= 40. {
+        return None;
+    }
+    let close = corners.first().is_some_and(|first| meter(first.distance(vec2_meter(mouse))) < POLYGON_CLOSE_DISTANCE);
+    let finished = (is_mouse_button_pressed(MouseButton::Left) && close) || is_mouse_button_pressed(MouseButton::Right);
+    if finished && corners.len() >= 3 {
+        return Some(FluidRegion::polygon(std::mem::take(corners), 1.0, 0.5));
+    }
+    if is_mouse_button_pressed(MouseButton::Left) {
+        corners.push(vec2_meter(mouse));
+    }
+    None
+}
+
+//Create the magnetic field region the user would like to produce, out of the screen by default
+#[allow(dead_code)]
+pub fn draw_process_magnetic(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<MagneticField> {
+    if draw_process(MouseMode::DrawMagnetic, first_mouse_pos, camera) {
+        //Use the mouse position saved
+        if let Some(pos1) = *first_mouse_pos {
+            //Create the region between the two mouse positions
+            let pos1 = vec2_meter(pos1);
+            let pos2 = vec2_meter(camera.screen_to_world(Vec2::from(mouse_position())));
+            //Clear the saved mouse position
+            *first_mouse_pos = None;
+            return Some(MagneticField::new(pos1, pos2 - pos1, MAGNETIC_FIELD_STRENGTH));
+        }
+    }
+    None
+}
+
+//Create the electric field region the user would like to produce, pointing down like the field between two flat plates
+#[allow(dead_code)]
+pub fn draw_process_electric(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<ElectricField> {
+    if draw_process(MouseMode::DrawElectric, first_mouse_pos, camera) && let Some(pos1) = *first_mouse_pos {
+        //Create the region between the two mouse positions
+        let pos1 = vec2_meter(pos1);
+        let pos2 = vec2_meter(camera.screen_to_world(Vec2::from(mouse_position())));
+        //Clear the saved mouse position
+        *first_mouse_pos = None;
+        return Some(ElectricField::new(pos1, pos2 - pos1, Vec2::new(0.0, ELECTRIC_FIELD_STRENGTH)));
+    }
+    None
+}
+
+//Create the force zone the user would like to produce, a wind blowing to the right by default
+#[allow(dead_code)]
+pub fn draw_process_zone(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<ForceZone> {
+    if draw_process(MouseMode::DrawZone, first_mouse_pos, camera) {
+        //Use the mouse position saved
+        if let Some(pos1) = *first_mouse_pos {
+            //Create the zone between the two mouse positions
+            let pos1 = vec2_meter(pos1);
+            let pos2 = vec2_meter(camera.screen_to_world(Vec2::from(mouse_position())));
+            //Clear the saved mouse position
+            *first_mouse_pos = None;
+            return Some(ForceZone::new(pos1, pos2 - pos1, ZoneKind::Wind, 10.0, 0.0));
+        }
+    }
+    None
+}
+
+//Create the container of gas the user would like to produce, with 100 molecules at room temperature
+#[allow(dead_code)]
+pub fn draw_process_gas(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<GasBox> {
+    if draw_process(MouseMode::DrawGas, first_mouse_pos, camera) {
+        //Use the mouse position saved
+        if let Some(pos1) = *first_mouse_pos {
+            //Create the container between the two mouse positions
+            let pos1 = vec2_meter(pos1);
+            let pos2 = vec2_meter(camera.screen_to_world(Vec2::from(mouse_position())));
+            //Clear the saved mouse position
+            *first_mouse_pos = None;
+            return Some(GasBox::new(pos1, pos2 - pos1, 100, 300.0));
+        }
+    }
+    None
+}
+
+//Create the block of SPH fluid the user would like to produce, with particles 10cm apart
+#[allow(dead_code)]
+pub fn draw_process_sph(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<SphFluid> {
+    if draw_process(MouseMode::DrawSph, first_mouse_pos, camera) {
+        //Use the mouse position saved
+        if let Some(pos1) = *first_mouse_pos {
+            //Fill the block between the two mouse positions
+            let pos1 = vec2_meter(pos1);
+            let pos2 = vec2_meter(camera.screen_to_world(Vec2::from(mouse_position())));
+            //Clear the saved mouse position
+            *first_mouse_pos = None;
+            return Some(SphFluid::new(pos1, pos2 - pos1, 0.1, 1.0, 15.0, 0.1));
+        }
+    }
+    None
+}
+
+//Create the rope the user would like to produce between the two mouse positions, a fifth longer than the gap so it sags
+#[allow(dead_code)]
+pub fn draw_process_rope(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<Cloth> {
+    if draw_process(MouseMode::DrawRope, first_mouse_pos, camera) {
+        //Use the mouse position saved
+        if let Some(pos1) = *first_mouse_pos {
+            //Hang the rope between the two mouse positions
+            let pos1 = vec2_meter(pos1);
+            let pos2 = vec2_meter(camera.screen_to_world(Vec2::from(mouse_position())));
+            //Clear the saved mouse position
+            *first_mouse_pos = None;
+            return Some(Cloth::new(ClothKind::Rope, pos1, pos2, pos1.distance(pos2) * 1.2));
+        }
+    }
+    None
+}
+
+//Create the cloth the user would like to produce, hanging down from the two mouse positions as far as it is wide
+#[allow(dead_code)]
+pub fn draw_process_cloth(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<Cloth> {
+    if draw_process(MouseMode::DrawCloth, first_mouse_pos, camera) {
+        //Use the mouse position saved
+        if let Some(pos1) = *first_mouse_pos {
+            //Hang the cloth from the two mouse positions
+            let pos1 = vec2_meter(pos1);
+            let pos2 = vec2_meter(camera.screen_to_world(Vec2::from(mouse_position())));
+            //Clear the saved mouse position
+            *first_mouse_pos = None;
+            let mut cloth = Cloth::new(ClothKind::Cloth, pos1, pos2, pos1.distance(pos2));
+            //Wide cloths have their points further apart, so there aren't too many to simulate
+            cloth.spacing = (pos1.distance(pos2) / 20.).max(0.1);
+            cloth.reset();
+            return Some(cloth);
+        }
+    }
+    None
+}
+
+//Join the object the user started dragging from to the object they let go over, at the distance they are apart now
+#[allow(dead_code)]
+pub fn draw_process_joint(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D, objects: &mut [Box<dyn PhysicsObject>]) -> Option<Joint> {
+    if draw_process(MouseMode::DrawJoint, first_mouse_pos, camera) {
+        //Use the mouse position saved
+        if let Some(pos1) = *first_mouse_pos {
+            let pos2 = camera.screen_to_world(Vec2::from(mouse_position()));
+            //Clear the saved mouse position
+            *first_mouse_pos = None;
+            //Find the objects under both ends of the line, which must be two different objects
+            let mut find_object = |pos: Vec2| objects.iter_mut().position(|object| object.get_render_shape_reference().mouse_in_area(pos));
+            let (Some(a), Some(b)) = (find_object(pos1), find_object(pos2)) else { return None; };
+            if a == b {
+                return None;
+            }
+            return Some(Joint::new(&Body::new(objects[a].as_mut()), &Body::new(objects[b].as_mut())));
+        }
+    }
+    None
+}
+
+//Attach a spring from the object the user started dragging from to where they let go, which is the spring's anchor.
+//The spring starts at its rest length, so the object only moves once it is pulled or pushed
+#[allow(dead_code)]
+pub fn draw_process_spring(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D, objects: &mut [Box<dyn PhysicsObject>]) -> Option<Spring> {
+    if draw_process(MouseMode::DrawSpring, first_mouse_pos, camera) && let Some(pos1) = *first_mouse_pos {
+        let pos2 = camera.screen_to_world(Vec2::from(mouse_position()));
+        *first_mouse_pos = None;
+        let index = objects.iter_mut().position(|object| object.get_render_shape_reference().mouse_in_area(pos1))?;
+        let object = &mut objects[index];
+        let anchor = vec2_meter(pos2);
+        let centre = object.get_render_shape_reference().get_centre();
+        return Some(Spring {
+            handle: object.get_handle(),
+            anchor,
+            stiffness: SPRING_STIFFNESS,
+            rest_length: anchor.distance(centre),
+            damping: SPRING_DAMPING,
+        });
+    }
+    None
+}
+
+//Make wind blowing the way the user drags, faster the further they drag
+#[allow(dead_code)]
+pub fn draw_process_wind(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> Option<Wind> {
+    if draw_process(MouseMode::DrawWind, first_mouse_pos, camera) && let Some(pos1) = *first_mouse_pos {
+        let pos2 = camera.screen_to_world(Vec2::from(mouse_position()));
+        *first_mouse_pos = None;
+        return Some(Wind { velocity: vec2_meter(pos2 - pos1) * WIND_SPEED_PER_METER, density: AIR_DENSITY });
+    }
+    None
+}
+
+//Place an attractor where the user clicks
+#[allow(dead_code)]
+pub fn place_process_attractor(camera: &Camera2D) -> Option<RadialAttractor> {
+    if is_mouse_button_pressed(MouseButton::Left) && mouse_position().1 > 40. {
+        let centre = vec2_meter(camera.screen_to_world(Vec2::from(mouse_position())));
+        return Some(RadialAttractor { centre, strength: ATTRACTOR_STRENGTH, softening: ATTRACTOR_SOFTENING });
+    }
+    None
+}
+
+//Place a particle emitter where the user clicks, making smoke by default
+#[allow(dead_code)]
+pub fn place_process_emitter(camera: &Camera2D) -> Option<ParticleEmitter> {
+    if is_mouse_button_pressed(MouseButton::Left) && mouse_position().1 > 40. {
+        let pos = vec2_meter(camera.screen_to_world(Vec2::from(mouse_position())));
+        return Some(ParticleEmitter::new(pos, EmitterPreset::Smoke));
+    }
+    None
+}
+
 //Draw the shape of the object that the user would like to draw in preview mode
+#[allow(dead_code)]
 pub fn draw_process(mouse_mode: MouseMode, first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D) -> bool {
     //Find the mouse mode the user is using.
     match mouse_mode {
@@ -162,8 +463,9 @@ pub fn draw_process(mouse_mode: MouseMode, first_mouse_pos: &mut Option<Vec2>, c
                 *first_mouse_pos = None;
             }
         }
-        //The user is drawing a rectangle
-        MouseMode::DrawRectangele => {
+        //The user is drawing a rectangle, or anything else that is drawn the same way (fluids, fields, zones, gas...)
+        MouseMode::DrawRectangele | MouseMode::DrawFluid | MouseMode::DrawMagnetic | MouseMode::DrawElectric | MouseMode::DrawZone | MouseMode::DrawGas
+        | MouseMode::DrawSph => {
             if is_mouse_button_down(MouseButton::Left) && mouse_position().1 > 40. {
                 //Get the current mouse position, or save the current mouse position the user has used
                 let pos2 = camera.screen_to_world(Vec2::from(mouse_position()));
@@ -184,6 +486,26 @@ pub fn draw_process(mouse_mode: MouseMode, first_mouse_pos: &mut Option<Vec2>, c
                 *first_mouse_pos = None;
             }
         }
+        //The user is drawing a rope, a cloth, a joint, a spring or wind, which are drawn as a line between their two ends
+        MouseMode::DrawRope | MouseMode::DrawCloth | MouseMode::DrawJoint | MouseMode::DrawSpring | MouseMode::DrawWind => {
+            if is_mouse_button_down(MouseButton::Left) && mouse_position().1 > 40. {
+                //Get the current mouse position, or save the current mouse position the user has used
+                let pos2 = camera.screen_to_world(Vec2::from(mouse_position()));
+                if let Some(pos1) = *first_mouse_pos {
+                    //Draw the line between the two anchors
+                    draw_line(pos1.x, pos1.y, pos2.x, pos2.y, 3., PURPLE);
+                } else {
+                    //Save the first anchor
+                    *first_mouse_pos = Some(pos2);
+                }
+            } else {
+                //Return true if the user has finished drawing the line, or clear the first mouse position
+                if let Some(_pos1) = *first_mouse_pos && mouse_position().1 > 40. {
+                    return true;
+                }
+                *first_mouse_pos = None;
+            }
+        }
         //The user is drawing a ball
         MouseMode::DrawBall => {
             if is_mouse_button_down(MouseButton::Left) {
@@ -0,0 +1,274 @@
+use std::any::Any;
+use macroquad::camera::Camera2D;
+use macroquad::color::{Color, WHITE};
+use macroquad::math::Vec2;
+use macroquad::shapes::{draw_line, draw_rectangle, draw_rectangle_lines};
+use macroquad::text::draw_text;
+use macroquad::window::{screen_height, screen_width};
+use crate::measurements::{meter, vec2_meter};
+use crate::objects::forces::{get_bodies, Body, ForceGenerator};
+use crate::objects::physics::PhysicsObject;
+
+//Coulomb's constant, k = 1 / (4 pi e0) (N m^2 / C^2)
+pub(crate) const COULOMB_CONSTANT: f32 = 8.99e9;
+//Stops the force becoming infinite when two charges get very close (m)
+const CHARGE_SOFTENING: f32 = 0.05;
+//How the field is drawn: the number of potential squares across the screen, and the step along a field line (m)
+const POTENTIAL_CELLS: usize = 48;
+const FIELD_LINE_STEP: f32 = 0.05;
+const FIELD_LINE_STEPS: usize = 400;
+
+//Coulomb's law between every pair of charged objects (F = k q1 q2 / r^2). Like charges push apart, opposite charges pull together
+pub(crate) struct Coulomb {
+    //The handle, centre and charge of every charged object at the start of the step
+    charges: Vec<(usize, Vec2, f32)>,
+}
+
+//A region with the same electric field everywhere inside it (e.g. between two charged plates), pushing charges with F = q E
+pub(crate) struct ElectricField {
+    //The top left corner and size of the region, the same as a rectangle
+    pub(crate) pos: Vec2,
+    pub(crate) size: Vec2,
+    //The electric field strength (N/C)
+    pub(crate) field: Vec2,
+}
+
+impl Coulomb {
+    pub(crate) fn new() -> Coulomb {
+        Coulomb { charges: Vec::new() }
+    }
+}
+
+impl ForceGenerator for Coulomb {
+    fn prepare(&mut self, bodies: &[Body]) {
+        self.charges = bodies.iter().filter(|body| body.charge != 0.0).map(|body| (body.handle, body.centre, body.charge)).collect();
+    }
+
+    fn get_force(&self, body: &Body) -> Vec2 {
+        //Nothing is pushed unless at least two objects are charged
+        if body.charge == 0.0 || self.charges.len() < 2 {
+            return Vec2::ZERO;
+        }
+        let mut force = Vec2::ZERO;
+        for (handle, centre, charge) in &self.charges {
+            if *handle != body.handle {
+                force += get_point_field(body.centre, *centre, *charge) * body.charge;
+            }
+        }
+        force
+    }
+
+    //U = k q1 q2 / r for every pair of charged objects
+    fn get_field_energy(&self, bodies: &[Body]) -> f32 {
+        //Only the charged objects are paired up, so a scene with no charges skips the pairs completely
+        let charged: Vec<&Body> = bodies.iter().filter(|body| body.charge != 0.0).collect();
+        let mut energy = 0.0;
+        for (index, body) in charged.iter().enumerate() {
+            for other in &charged[index + 1..] {
+                energy += other.charge * get_point_potential(other.centre, body.centre, body.charge);
+            }
+        }
+        energy
+    }
+}
+
+impl ElectricField {
+    //Create a field region, turning a negative width or height (from drawing it backwards) into a positive one
+    pub(crate) fn new(pos: Vec2, size: Vec2, field: Vec2) -> ElectricField {
+        ElectricField { pos: pos.min(pos + size), size: size.abs(), field }
+    }
+
+    //Check if a point is inside the region
+    fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.pos).all() && point.cmple(self.pos + self.size).all()
+    }
+}
+
+impl ForceGenerator for ElectricField {
+    fn get_force(&self, body: &Body) -> Vec2 {
+        if self.contains(body.centre) { self.field * body.charge } else { Vec2::ZERO }
+    }
+
+    //Draw the region with arrows showing the direction of the field
+    fn draw(&self, _bodies: &[Body]) {
+        let colour = Color::from_rgba(255, 220, 60, 200);
+        draw_rectangle(meter(self.pos.x), meter(self.pos.y), meter(self.size.x), meter(self.size.y), Color::from_rgba(255, 220, 60, 40));
+        draw_rectangle_lines(meter(self.pos.x), meter(self.pos.y), meter(self.size.x), meter(self.size.y), 2., colour);
+        let direction = self.field.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return;
+        }
+        //One arrow for every half meter, at most 10 in each direction
+        let columns = (self.size.x * 2.).clamp(1., 10.) as usize;
+        let rows = (self.size.y * 2.).clamp(1., 10.) as usize;
+        for column in 0..columns {
+            for row in 0..rows {
+                let centre = self.pos + self.size * Vec2::new((column as f32 + 0.5) / columns as f32, (row as f32 + 0.5) / rows as f32);
+                draw_arrow(centre - direction * 0.15, centre + direction * 0.15, colour);
+            }
+        }
+    }
+
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        Some(format!("efield x={} y={} width={} height={} ex={} ey={}", self.pos.x, self.pos.y, self.size.x, self.size.y,
+                     self.field.x, self.field.y))
+    }
+}
+
+//The electric field (N/C) at a point from a charge (E = k q / r^2), pointing away from positive charges
+fn get_point_field(point: Vec2, centre: Vec2, charge: f32) -> Vec2 {
+    let offset = point - centre;
+    let distance_sqr = offset.length_squared() + CHARGE_SOFTENING * CHARGE_SOFTENING;
+    offset.normalize_or_zero() * COULOMB_CONSTANT * charge / distance_sqr
+}
+
+//The electric potential (V) at a point from a charge (V = k q / r)
+fn get_point_potential(point: Vec2, centre: Vec2, charge: f32) -> f32 {
+    let distance = (point.distance_squared(centre) + CHARGE_SOFTENING * CHARGE_SOFTENING).sqrt();
+    COULOMB_CONSTANT * charge / distance
+}
+
+//The total electric field at a point, from every charged object and every field region
+fn get_field_at(point: Vec2, charges: &[Body], regions: &[&ElectricField]) -> Vec2 {
+    let mut field = Vec2::ZERO;
+    for body in charges {
+        field += get_point_field(point, body.centre, body.charge);
+    }
+    for region in regions {
+        if region.contains(point) {
+            field += region.field;
+        }
+    }
+    field
+}
+
+//Draw a line with an arrow head at the end
+pub(crate) fn draw_arrow(start: Vec2, end: Vec2, colour: Color) {
+    let direction = (end - start).normalize_or_zero();
+    let head = direction * 0.08;
+    draw_line(meter(start.x), meter(start.y), meter(end.x), meter(end.y), 2., colour);
+    for side in [head.perp(), -head.perp()] {
+        let corner = end - head + side * 0.6;
+        draw_line(meter(end.x), meter(end.y), meter(corner.x), meter(corner.y), 2., colour);
+    }
+}
+
+//Follow the field from a point, until it reaches another charge or leaves the screen.
+//Lines from negative charges are followed backwards, so they still point along the field
+fn draw_field_line(start: Vec2, sign: f32, charges: &[Body], regions: &[&ElectricField], corner1: Vec2, corner2: Vec2) {
+    let colour = Color::from_rgba(255, 255, 255, 150);
+    let mut point = start;
+    for step in 0..FIELD_LINE_STEPS {
+        let direction = get_field_at(point, charges, regions).normalize_or_zero() * sign;
+        if direction == Vec2::ZERO {
+            return;
+        }
+        let next = point + direction * FIELD_LINE_STEP;
+        //Show which way the field points every so often
+        if step % 40 == 20 && sign > 0.0 {
+            draw_arrow(point, next, colour);
+        } else if step % 40 == 20 {
+            draw_arrow(next, point, colour);
+        } else {
+            draw_line(meter(point.x), meter(point.y), meter(next.x), meter(next.y), 1., colour);
+        }
+        point = next;
+        let inside_charge = charges.iter().any(|body| body.centre.distance(point) < body.half_size.min_element());
+        if inside_charge || point.cmplt(corner1).any() || point.cmpgt(corner2).any() {
+            return;
+        }
+    }
+}
+
+//Draw the electric potential as coloured squares (red for positive, blue for negative),
+//and the field lines coming out of every charged object
+#[allow(dead_code)]
+pub(crate) fn draw_electric_field(forces: &[Box<dyn ForceGenerator>], objects: &mut [Box<dyn PhysicsObject>], camera: &Camera2D) {
+    let charges: Vec<Body> = get_bodies(objects).into_iter().filter(|body| body.charge != 0.0).collect();
+    if charges.is_empty() {
+        return;
+    }
+    let regions: Vec<&ElectricField> = forces.iter().filter_map(|generator| (generator.as_ref() as &dyn Any).downcast_ref::<ElectricField>()).collect();
+
+    //The part of the world on screen, below the hotbar
+    let corner1 = vec2_meter(camera.screen_to_world(Vec2::new(0., 40.)));
+    let corner2 = vec2_meter(camera.screen_to_world(Vec2::new(screen_width(), screen_height())));
+    let (corner1, corner2) = (corner1.min(corner2), corner1.max(corner2));
+
+    //Only the point charges make a potential here, as a uniform field has no zero point to measure from.
+    //The colour is scaled by the potential 1 m from the largest charge
+    let largest_charge = charges.iter().fold(0.0_f32, |largest, body| largest.max(body.charge.abs()));
+    let reference = COULOMB_CONSTANT * largest_charge;
+    let cell = (corner2 - corner1) / POTENTIAL_CELLS as f32;
+    let rows = ((corner2.y - corner1.y) / cell.x).ceil() as usize;
+    for column in 0..POTENTIAL_CELLS {
+        for row in 0..rows {
+            let pos = corner1 + Vec2::new(column as f32, row as f32) * cell.x;
+            let centre = pos + cell.x / 2.;
+            let potential: f32 = charges.iter().map(|body| get_point_potential(centre, body.centre, body.charge)).sum();
+            let strength = potential.abs() / (potential.abs() + reference);
+            let colour = if potential > 0.0 { Color::new(1., 0.2, 0.2, strength * 0.5) } else { Color::new(0.2, 0.4, 1., strength * 0.5) };
+            draw_rectangle(meter(pos.x), meter(pos.y), meter(cell.x), meter(cell.x), colour);
+        }
+    }
+
+    //Start more lines from larger charges
+    for body in &charges {
+        let lines = (16. * body.charge.abs() / largest_charge).round().max(4.) as usize;
+        let radius = body.half_size.max_element();
+        for line in 0..lines {
+            let angle = line as f32 / lines as f32 * std::f32::consts::TAU;
+            let start = body.centre + Vec2::from_angle(angle) * radius;
+            draw_field_line(start, body.charge.signum(), &charges, &regions, corner1, corner2);
+        }
+        let sign = if body.charge > 0.0 { "+" } else { "-" };
+        draw_text(sign, meter(body.centre.x) - 6., meter(body.centre.y) + 8., 30., WHITE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::math::Vec2;
+    use crate::objects::events::EventLog;
+    use crate::objects::forces::{Body, ForceGenerator};
+    use crate::objects::scene::Scene;
+    use super::{Coulomb, COULOMB_CONSTANT};
+
+    #[test]
+    fn field_regions_push_charges_inside_them() {
+        //a = q E / m = 0.001 * 100 / 1 = 0.1 m/s^2 for 1 s, and the uncharged object isn't pushed
+        let mut scene = Scene::load_test("efield_inside", "efield x=-1 y=-1 width=4 height=2 ex=100 ey=0\n\
+            circle x=0 y=0 radius=0.1 mass=1 type=dynamic gravity=0 air=false charge=0.001\n\
+            circle x=0 y=0.5 radius=0.1 mass=1 type=dynamic gravity=0 air=false\n");
+        scene.run(&mut EventLog::new(), 60);
+        assert!((scene.objects[0].get_velocity() - Vec2::new(0.1, 0.0)).length() < 1e-4);
+        assert_eq!(scene.objects[1].get_velocity(), Vec2::ZERO);
+
+        //Charges outside the region aren't pushed at all
+        let mut scene = Scene::load_test("efield_outside", "efield x=-1 y=-1 width=4 height=2 ex=100 ey=0\n\
+            circle x=0 y=5 radius=0.1 mass=1 type=dynamic gravity=0 air=false charge=0.001\n");
+        scene.run(&mut EventLog::new(), 60);
+        assert_eq!(scene.objects[0].get_velocity(), Vec2::ZERO);
+    }
+
+    #[test]
+    fn like_charges_push_apart() {
+        let body = |handle: usize, x: f32, charge: f32| Body {
+            handle, centre: Vec2::new(x, 0.0), velocity: Vec2::ZERO, mass: 1.0, area: 1.0, gravity: 0.0, drag_coefficient: 1.0,
+            do_air_resistance: false, half_size: Vec2::splat(0.1), circle: true, charge, dynamic: true,
+        };
+        let mut coulomb = Coulomb::new();
+        let bodies = [body(0, 0.0, 1e-6), body(1, 2.0, 2e-6), body(2, 4.0, 0.0)];
+        coulomb.prepare(&bodies);
+        //F = k q1 q2 / r^2, slightly less because of the softening
+        let force = coulomb.get_force(&bodies[0]);
+        let expected = COULOMB_CONSTANT * 2e-12 / 4.;
+        assert!(force.x < 0.0 && (force.x + expected).abs() < expected * 0.01);
+        assert_eq!(coulomb.get_force(&bodies[2]), Vec2::ZERO);
+
+        //Opposite charges pull together
+        let bodies = [body(0, 0.0, 1e-6), body(1, 2.0, -2e-6)];
+        coulomb.prepare(&bodies);
+        assert!(coulomb.get_force(&bodies[0]).x > 0.0);
+    }
+}
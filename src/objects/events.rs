@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::fs;
+use macroquad::math::Vec2;
+
+//The different kinds of events that can happen between two objects
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum EventType {
+    BeginContact,
+    EndContact,
+    SensorEnter,
+    SensorExit,
+    //An object was hit hard enough to break, or a joint was pulled hard enough to snap
+    Break,
+}
+
+//A single event, with the time it happened, the handles of both objects, and how hard they hit
+#[derive(Clone, Copy)]
+pub(crate) struct CollisionEvent {
+    pub(crate) event_type: EventType,
+    pub(crate) time: f32,
+    pub(crate) object_1: usize,
+    pub(crate) object_2: usize,
+    pub(crate) impulse: f32,
+    pub(crate) point: Vec2,
+}
+
+//A pair of objects that are currently touching
+#[derive(Clone, Copy)]
+struct Contact {
+    sensor: bool,
+    point: Vec2,
+}
+
+//Store every event that has happened, and which objects are currently touching
+pub(crate) struct EventLog {
+    pub(crate) events: Vec<CollisionEvent>,
+    pub(crate) time: f32,
+    contacts: HashMap<(usize, usize), Contact>,
+    current_contacts: HashMap<(usize, usize), Contact>,
+}
+
+impl EventType {
+    //Get the name of the event, used for the log panel and the exported file
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            EventType::BeginContact => "Begin contact",
+            EventType::EndContact => "End contact",
+            EventType::SensorEnter => "Sensor enter",
+            EventType::SensorExit => "Sensor exit",
+            EventType::Break => "Break",
+        }
+    }
+}
+
+impl CollisionEvent {
+    //Create a line of text describing the event
+    #[allow(dead_code)]
+    pub(crate) fn describe(&self) -> String {
+        format!("{:.3}s  {}  #{} - #{}  J={:.3}Ns  ({:.2}, {:.2})", self.time, self.event_type.get_name(),
+                self.object_1, self.object_2, self.impulse, self.point.x, self.point.y)
+    }
+}
+
+impl EventLog {
+    pub(crate) fn new() -> EventLog {
+        EventLog {
+            events: Vec::new(),
+            time: 0.0,
+            contacts: HashMap::new(),
+            current_contacts: HashMap::new(),
+        }
+    }
+
+    //Record that two objects are touching this step, and create an event if they weren't touching before
+    pub(crate) fn contact(&mut self, object_1: usize, object_2: usize, sensor: bool, impulse: f32, point: Vec2) {
+        let key = (object_1.min(object_2), object_1.max(object_2));
+        if self.current_contacts.contains_key(&key) {
+            return;
+        }
+        self.current_contacts.insert(key, Contact { sensor, point });
+
+        if !self.contacts.contains_key(&key) {
+            let event_type = if sensor { EventType::SensorEnter } else { EventType::BeginContact };
+            self.events.push(CollisionEvent { event_type, time: self.time, object_1: key.0, object_2: key.1, impulse, point });
+        }
+    }
+
+    //Record that an object broke (object 1, hit by object 2), or that the joint between two objects snapped
+    pub(crate) fn record_break(&mut self, object_1: usize, object_2: usize, impulse: f32, point: Vec2) {
+        self.events.push(CollisionEvent { event_type: EventType::Break, time: self.time, object_1, object_2, impulse, point });
+    }
+
+    //Keep a contact between two objects that were not checked this step (e.g. both are asleep)
+    pub(crate) fn keep_contact(&mut self, object_1: usize, object_2: usize) {
+        let key = (object_1.min(object_2), object_1.max(object_2));
+        if let Some(contact) = self.contacts.get(&key) {
+            self.current_contacts.insert(key, *contact);
+        }
+    }
+
+    //Get every pair of objects touching at the end of the last step, not counting sensors
+    pub(crate) fn get_contacts(&self) -> Vec<(usize, usize)> {
+        let mut pairs: Vec<(usize, usize)> = self.contacts.iter().filter(|(_, contact)| !contact.sensor).map(|(key, _)| *key).collect();
+        pairs.sort();
+        pairs
+    }
+
+    //Finish a step, creating an event for every pair of objects that has stopped touching
+    pub(crate) fn end_step(&mut self) {
+        let mut ended: Vec<((usize, usize), Contact)> = self.contacts.iter()
+            .filter(|(key, _)| !self.current_contacts.contains_key(key))
+            .map(|(key, contact)| (*key, *contact))
+            .collect();
+        ended.sort_by_key(|(key, _)| *key);
+
+        for (key, contact) in ended {
+            let event_type = if contact.sensor { EventType::SensorExit } else { EventType::EndContact };
+            self.events.push(CollisionEvent { event_type, time: self.time, object_1: key.0, object_2: key.1, impulse: 0.0, point: contact.point });
+        }
+        self.contacts = std::mem::take(&mut self.current_contacts);
+    }
+
+    //Remove every event from the log. The objects that are touching are kept, so they don't begin contact again
+    #[allow(dead_code)]
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    //Save the log as a CSV file, so it can be opened in a spreadsheet
+    pub(crate) fn export(&self, path: &str) -> std::io::Result<()> {
+        let mut csv = String::from("time,event,object_1,object_2,impulse,contact_x,contact_y\n");
+        for event in &self.events {
+            csv.push_str(&format!("{},{},{},{},{},{},{}\n", event.time, event.event_type.get_name(), event.object_1,
+                                  event.object_2, event.impulse, event.point.x, event.point.y));
+        }
+        fs::write(path, csv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::scene::Scene;
+    use super::{EventLog, EventType};
+
+    #[test]
+    fn contact_impulse_includes_the_bounce() {
+        //A 1 kg ball hitting the floor at 2 m/s with e = 0.5 leaves at 1 m/s, so it is given 3 Ns
+        let mut scene = Scene::load_test("bounce_impulse", "circle x=0 y=0 radius=0.2 mass=1 type=dynamic vy=2 gravity=0 air=false bounce=0.5\n\
+            rectangle x=-1 y=0.25 width=2 height=0.5 type=static\n");
+        let mut events = EventLog::new();
+        scene.run(&mut events, 10);
+        let contacts: Vec<_> = events.events.iter().filter(|event| event.event_type == EventType::BeginContact).collect();
+        assert_eq!(contacts.len(), 1);
+        assert!((contacts[0].impulse - 3.0).abs() < 1e-3, "the bounce gave {} Ns", contacts[0].impulse);
+        assert!((scene.objects[0].get_velocity().y + 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn clearing_keeps_current_contacts() {
+        let mut scene = Scene::load_test("clear_contacts", "rectangle x=-1 y=1 width=2 height=0.5 type=static\n\
+            rectangle x=0 y=0.5 width=0.5 height=0.5 type=dynamic\n");
+        let mut events = EventLog::new();
+        scene.run(&mut events, 10);
+        assert!(events.events.iter().any(|event| event.event_type == EventType::BeginContact));
+        events.clear();
+        scene.run(&mut events, 10);
+        assert!(events.events.is_empty(), "the box resting on the floor began contact again");
+    }
+
+    #[test]
+    fn sensors_report_overlaps_without_stopping_objects() {
+        let mut scene = Scene::load_test("sensor_floor", "circle x=0 y=0 radius=0.2 mass=1 type=dynamic vy=2 gravity=0 air=false\n\
+            rectangle x=-1 y=0.25 width=2 height=0.5 type=static sensor=true\n");
+        let mut events = EventLog::new();
+        scene.run(&mut events, 60);
+        assert!(events.events.iter().any(|event| event.event_type == EventType::SensorEnter));
+        assert!(events.events.iter().any(|event| event.event_type == EventType::SensorExit));
+        assert!(!events.events.iter().any(|event| event.event_type == EventType::BeginContact));
+        assert!((scene.objects[0].get_velocity().y - 2.0).abs() < 1e-3, "the sensor changed the ball's velocity");
+    }
+
+    #[test]
+    fn masks_skip_other_layers() {
+        //The ball only collides with layer 2, so it falls through the floor on layer 1 and lands on the one on layer 2
+        let mut scene = Scene::load_test("layer_mask", "circle x=0 y=0 radius=0.2 mass=1 type=dynamic vy=2 gravity=0 air=false bounce=0 mask=2\n\
+            rectangle x=-1 y=0.25 width=2 height=0.5 type=static layers=1\n\
+            rectangle x=-1 y=1.25 width=2 height=0.5 type=static layers=2\n");
+        let mut events = EventLog::new();
+        scene.run(&mut events, 60);
+        let contacts: Vec<_> = events.events.iter().filter(|event| event.event_type == EventType::BeginContact).collect();
+        assert_eq!(contacts.len(), 1);
+        let (ball, floor) = (scene.objects[0].get_handle(), scene.objects[2].get_handle());
+        assert!([(ball, floor), (floor, ball)].contains(&(contacts[0].object_1, contacts[0].object_2)));
+        assert!(scene.objects[0].get_velocity().y.abs() < 1e-3);
+    }
+}
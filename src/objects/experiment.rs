@@ -0,0 +1,454 @@
+use std::fs;
+use std::path::Path;
+use macroquad::math::Vec2;
+use crate::measurements::set_fixed_dt;
+use crate::objects::events::{EventLog, EventType};
+use crate::objects::physics::PhysicsObject;
+use crate::objects::scene::{load_scene, Scene};
+use crate::objects::world::step;
+
+//The property of the object that is changed between each run
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub(crate) enum Parameter {
+    Angle,
+    Speed,
+    Mass,
+    Gravity,
+    X,
+    Y,
+    Vx,
+    Vy,
+}
+
+//The quantity that is measured at the end of each run
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub(crate) enum Measurement {
+    Range,
+    Time,
+    MaxHeight,
+    FinalSpeed,
+    FinalX,
+    FinalY,
+}
+
+//When each run should stop
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub(crate) enum StopCondition {
+    Ground(f32),
+    Time(f32),
+    Sleep,
+    Contact,
+}
+
+//A description of an experiment: which scene and object to use, what to change, what to measure and when to stop
+#[allow(dead_code)]
+pub(crate) struct Experiment {
+    pub(crate) scene: String,
+    pub(crate) object: usize,
+    pub(crate) parameter: Parameter,
+    pub(crate) from: f32,
+    pub(crate) to: f32,
+    pub(crate) step: f32,
+    //The speed (m/s) the object is launched at when the angle is varied, or None to keep the speed it has in the scene
+    pub(crate) launch_speed: Option<f32>,
+    pub(crate) measurement: Measurement,
+    pub(crate) stop: StopCondition,
+    pub(crate) max_time: f32,
+}
+
+//The result of a single run of the experiment
+#[allow(dead_code)]
+pub(crate) struct ExperimentResult {
+    pub(crate) value: f32,
+    pub(crate) measured: f32,
+    pub(crate) time: f32,
+    pub(crate) stopped: bool,
+}
+
+//Errors from loading the experiment or its scene, kept apart from errors while running it
+#[allow(dead_code)]
+pub(crate) enum ExperimentError {
+    Load(String),
+    Runtime(String),
+}
+
+#[allow(dead_code)]
+impl Parameter {
+    fn from_name(name: &str) -> Option<Parameter> {
+        match name {
+            "angle" => Some(Parameter::Angle),
+            "speed" => Some(Parameter::Speed),
+            "mass" => Some(Parameter::Mass),
+            "gravity" => Some(Parameter::Gravity),
+            "x" => Some(Parameter::X),
+            "y" => Some(Parameter::Y),
+            "vx" => Some(Parameter::Vx),
+            "vy" => Some(Parameter::Vy),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            Parameter::Angle => "angle (deg)",
+            Parameter::Speed => "speed (m/s)",
+            Parameter::Mass => "mass (kg)",
+            Parameter::Gravity => "gravity (m/s^2)",
+            Parameter::X => "x (m)",
+            Parameter::Y => "y (m)",
+            Parameter::Vx => "vx (m/s)",
+            Parameter::Vy => "vy (m/s)",
+        }
+    }
+
+    //Change the object to use the given value. Angles are measured anticlockwise from the positive x-axis,
+    //so 45 degrees is up and to the right (the y-axis points down the screen). The object is launched at the launch speed
+    //if there is one, otherwise at the speed it already has, which can't be 0 as every angle would give the same run
+    //Check a value makes sense for this parameter, so a bad range is caught before anything runs
+    fn check(&self, value: f32) -> Result<(), String> {
+        match self {
+            Parameter::Mass if value <= 0.0 => Err(String::from("mass must be greater than 0")),
+            Parameter::Speed if value < 0.0 => Err(String::from("speed can't be negative (vary the angle to change the direction)")),
+            _ => Ok(()),
+        }
+    }
+
+    fn apply(&self, object: &mut Box<dyn PhysicsObject>, value: f32, launch_speed: Option<f32>) -> Result<(), String> {
+        let velocity = object.get_velocity();
+        match self {
+            Parameter::Angle => {
+                let speed = launch_speed.unwrap_or(velocity.length());
+                if speed == 0.0 {
+                    return Err(String::from("Varying the angle needs a speed: give the object a velocity in the scene, or add a 'launch_speed' line"));
+                }
+                let angle = value.to_radians();
+                object.set_velocity(Vec2::new(angle.cos(), -angle.sin()) * speed);
+            }
+            Parameter::Speed => {
+                let direction = if velocity.length() > 0.0 { velocity.normalize() } else { Vec2::new(1., 0.) };
+                object.set_velocity(direction * value);
+            }
+            Parameter::Mass => {
+                let material = object.get_material();
+                material.mass = value;
+                material.density = value / material.area;
+            }
+            Parameter::Gravity => *object.get_gravity() = value,
+            Parameter::X => object.get_render_shape_reference().get_pos().x = value,
+            Parameter::Y => object.get_render_shape_reference().get_pos().y = value,
+            Parameter::Vx => object.set_velocity(Vec2::new(value, velocity.y)),
+            Parameter::Vy => object.set_velocity(Vec2::new(velocity.x, value)),
+        }
+        Ok(())
+    }
+}
+
+#[allow(dead_code)]
+impl Measurement {
+    fn from_name(name: &str) -> Option<Measurement> {
+        match name {
+            "range" => Some(Measurement::Range),
+            "time" => Some(Measurement::Time),
+            "max_height" => Some(Measurement::MaxHeight),
+            "final_speed" => Some(Measurement::FinalSpeed),
+            "final_x" => Some(Measurement::FinalX),
+            "final_y" => Some(Measurement::FinalY),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            Measurement::Range => "range (m)",
+            Measurement::Time => "time (s)",
+            Measurement::MaxHeight => "max height (m)",
+            Measurement::FinalSpeed => "final speed (m/s)",
+            Measurement::FinalX => "final x (m)",
+            Measurement::FinalY => "final y (m)",
+        }
+    }
+}
+
+//Read a number from the experiment file
+#[allow(dead_code)]
+fn parse_number(text: Option<&&str>, name: &str, line_number: usize) -> Result<f32, String> {
+    text.and_then(|text| text.parse::<f32>().ok())
+        .ok_or(format!("Line {}: {} needs a number", line_number, name))
+}
+
+//Read a number that has to be greater than 0, like a time or a speed
+#[allow(dead_code)]
+fn parse_positive(text: Option<&&str>, name: &str, line_number: usize) -> Result<f32, String> {
+    let number = parse_number(text, name, line_number)?;
+    if number <= 0.0 {
+        return Err(format!("Line {}: {} must be greater than 0", line_number, name));
+    }
+    Ok(number)
+}
+
+//Load an experiment from a file. For example:
+//  scene projectile.txt
+//  object 0
+//  vary angle from 10 to 80 step 10
+//  launch_speed 20
+//  measure range
+//  stop ground 0
+//  max_time 30
+#[allow(dead_code)]
+pub(crate) fn load_experiment(path: &str) -> Result<Experiment, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+    let mut scene: Option<String> = None;
+    let mut experiment = Experiment {
+        scene: String::new(),
+        object: 0,
+        parameter: Parameter::Angle,
+        from: 0.0,
+        to: 0.0,
+        step: 1.0,
+        launch_speed: None,
+        measurement: Measurement::Range,
+        stop: StopCondition::Time(10.0),
+        max_time: 60.0,
+    };
+    let mut has_vary = false;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() || words[0].starts_with('#') {
+            continue;
+        }
+
+        match words[0] {
+            //The scene is found relative to the experiment file
+            "scene" => {
+                let name = words.get(1).ok_or(format!("Line {}: scene needs a file", line_number))?;
+                let folder = Path::new(path).parent().unwrap_or(Path::new(""));
+                scene = Some(folder.join(name).to_string_lossy().to_string());
+            }
+            "object" => {
+                experiment.object = words.get(1).and_then(|text| text.parse::<usize>().ok())
+                    .ok_or(format!("Line {}: object needs the number of the object in the scene (starting at 0)", line_number))?;
+            }
+            //vary <parameter> from <start> to <end> step <size>
+            "vary" => {
+                let name = words.get(1).copied().unwrap_or("");
+                experiment.parameter = Parameter::from_name(name)
+                    .ok_or(format!("Line {}: unknown parameter '{}' (use angle, speed, mass, gravity, x, y, vx or vy)", line_number, name))?;
+                if words.get(2) != Some(&"from") || words.get(4) != Some(&"to") || words.get(6) != Some(&"step") {
+                    return Err(format!("Line {}: write vary as 'vary <parameter> from <start> to <end> step <size>'", line_number));
+                }
+                experiment.from = parse_number(words.get(3), "from", line_number)?;
+                experiment.to = parse_number(words.get(5), "to", line_number)?;
+                experiment.step = parse_number(words.get(7), "step", line_number)?;
+                if experiment.step <= 0.0 {
+                    return Err(format!("Line {}: step must be greater than 0", line_number));
+                }
+                if experiment.to < experiment.from {
+                    return Err(format!("Line {}: to must not be less than from", line_number));
+                }
+                //The range only grows from 'from', so checking both ends covers every value in between
+                for value in [experiment.from, experiment.to] {
+                    experiment.parameter.check(value).map_err(|error| format!("Line {}: {}", line_number, error))?;
+                }
+                has_vary = true;
+            }
+            "measure" => {
+                let name = words.get(1).copied().unwrap_or("");
+                experiment.measurement = Measurement::from_name(name)
+                    .ok_or(format!("Line {}: unknown measurement '{}' (use range, time, max_height, final_speed, final_x or final_y)", line_number, name))?;
+            }
+            "stop" => {
+                experiment.stop = match words.get(1).copied().unwrap_or("") {
+                    "ground" => StopCondition::Ground(parse_number(words.get(2), "stop ground", line_number)?),
+                    "time" => StopCondition::Time(parse_positive(words.get(2), "stop time", line_number)?),
+                    "sleep" => StopCondition::Sleep,
+                    "contact" => StopCondition::Contact,
+                    other => return Err(format!("Line {}: unknown stop condition '{}' (use ground, time, sleep or contact)", line_number, other)),
+                };
+            }
+            "launch_speed" => experiment.launch_speed = Some(parse_positive(words.get(1), "launch_speed", line_number)?),
+            "max_time" => experiment.max_time = parse_positive(words.get(1), "max_time", line_number)?,
+            other => return Err(format!("Line {}: unknown setting '{}'", line_number, other)),
+        }
+    }
+
+    experiment.scene = scene.ok_or("The experiment needs a scene")?;
+    if !has_vary {
+        return Err(String::from("The experiment needs a 'vary' line"));
+    }
+    Ok(experiment)
+}
+
+//Run the simulation for one value of the parameter, until the stop condition is met
+#[allow(dead_code)]
+fn run_variant(experiment: &Experiment, mut scene: Scene, value: f32) -> Result<ExperimentResult, String> {
+    set_fixed_dt(Some(scene.settings.timestep));
+    scene.settings.start_random();
+
+    let objects = &mut scene.objects;
+    experiment.parameter.apply(&mut objects[experiment.object], value, experiment.launch_speed)?;
+
+    let mut events = EventLog::new();
+    let start = objects[experiment.object].get_render_shape_reference().get_centre();
+    let mut highest = start.y;
+    let mut previous = start;
+    let mut stopped = false;
+
+    while events.time < experiment.max_time {
+        let event_count = events.events.len();
+        step(objects, &mut scene.forces, &mut events);
+
+        let object = &mut objects[experiment.object];
+        let centre = object.get_render_shape_reference().get_centre();
+        if !centre.is_finite() {
+            return Err(format!("The object's position stopped being a number at {}s (value {})", events.time, value));
+        }
+        highest = highest.min(centre.y);
+
+        //Check if the run should stop
+        let handle = object.get_handle();
+        stopped = match experiment.stop {
+            //Stop the first time the object falls through the ground line, wherever it started
+            StopCondition::Ground(ground) => previous.y < ground && centre.y >= ground,
+            StopCondition::Time(time) => events.time >= time,
+            StopCondition::Sleep => object.get_sleeping(),
+            StopCondition::Contact => events.events[event_count..].iter().any(|event| {
+                event.event_type == EventType::BeginContact && (event.object_1 == handle || event.object_2 == handle)
+            }),
+        };
+        if stopped {
+            break;
+        }
+        previous = centre;
+    }
+
+    //Measure the quantity the experiment is looking for
+    let object = &mut objects[experiment.object];
+    let end = object.get_render_shape_reference().get_centre();
+    let measured = match experiment.measurement {
+        Measurement::Range => (end.x - start.x).abs(),
+        Measurement::Time => events.time,
+        Measurement::MaxHeight => start.y - highest,
+        Measurement::FinalSpeed => object.get_velocity().length(),
+        Measurement::FinalX => end.x,
+        Measurement::FinalY => end.y,
+    };
+    Ok(ExperimentResult { value, measured, time: events.time, stopped })
+}
+
+//Run the experiment once for every value of the parameter
+#[allow(dead_code)]
+pub(crate) fn run_experiment(experiment: &Experiment) -> Result<Vec<ExperimentResult>, ExperimentError> {
+    let mut results = Vec::new();
+    let mut run = 0;
+    loop {
+        //Work out each value from the start, so rounding errors don't build up
+        let value = experiment.from + experiment.step * run as f32;
+        if value > experiment.to + experiment.step * 1e-3 {
+            break;
+        }
+
+        //Load the scene fresh for every run, so each one starts from exactly the same state
+        let scene = load_scene(&experiment.scene).map_err(ExperimentError::Load)?;
+        if experiment.object >= scene.objects.len() {
+            return Err(ExperimentError::Load(format!("The scene only has {} objects, so there is no object {}", scene.objects.len(), experiment.object)));
+        }
+        results.push(run_variant(experiment, scene, value).map_err(ExperimentError::Runtime)?);
+        run += 1;
+    }
+    Ok(results)
+}
+
+//Lay out the results as a table that lines up in a terminal
+#[allow(dead_code)]
+pub(crate) fn format_results(experiment: &Experiment, results: &[ExperimentResult]) -> String {
+    let mut table = format!("{:>18} {:>18} {:>10} {}\n", experiment.parameter.get_name(), experiment.measurement.get_name(), "time (s)", "stopped");
+    for result in results {
+        let stopped = if result.stopped { "yes" } else { "max time" };
+        table.push_str(&format!("{:>18.4} {:>18.4} {:>10.3} {}\n", result.value, result.measured, result.time, stopped));
+    }
+    table
+}
+
+//Save the results as a CSV file, so they can be graphed in a spreadsheet
+#[allow(dead_code)]
+pub(crate) fn export_results(path: &str, experiment: &Experiment, results: &[ExperimentResult]) -> std::io::Result<()> {
+    let mut csv = format!("{},{},time (s),stopped\n", experiment.parameter.get_name(), experiment.measurement.get_name());
+    for result in results {
+        csv.push_str(&format!("{},{},{},{}\n", result.value, result.measured, result.time, result.stopped));
+    }
+    fs::write(path, csv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::{load_experiment, run_experiment, Measurement, Parameter, StopCondition};
+
+    //Write an experiment to a file and load it
+    fn load(name: &str, text: &str) -> Result<super::Experiment, String> {
+        let path = std::env::temp_dir().join(format!("rv_experiment_test_{}_{}.txt", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let experiment = load_experiment(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        experiment
+    }
+
+    #[test]
+    fn reads_every_setting() {
+        let experiment = load("full", "# Range against angle\nscene projectile.txt\nobject 2\n\
+            vary angle from 10 to 80 step 5\nlaunch_speed 20\nmeasure max_height\nstop ground 1.5\nmax_time 30\n").unwrap();
+        let folder = std::env::temp_dir();
+        assert_eq!(experiment.scene, folder.join("projectile.txt").to_string_lossy());
+        assert_eq!(experiment.object, 2);
+        assert!(matches!(experiment.parameter, Parameter::Angle));
+        assert_eq!((experiment.from, experiment.to, experiment.step), (10.0, 80.0, 5.0));
+        assert_eq!(experiment.launch_speed, Some(20.0));
+        assert!(matches!(experiment.measurement, Measurement::MaxHeight));
+        assert!(matches!(experiment.stop, StopCondition::Ground(ground) if ground == 1.5));
+        assert_eq!(experiment.max_time, 30.0);
+    }
+
+    #[test]
+    fn bad_experiments_give_errors() {
+        for (text, error) in [("vary mass from 1 to 2 step 1\n", "The experiment needs a scene"),
+                              ("scene a.txt\n", "The experiment needs a 'vary' line"),
+                              ("scene a.txt\nvary colour from 1 to 2 step 1\n", "Line 2: unknown parameter 'colour' (use angle, speed, mass, gravity, x, y, vx or vy)"),
+                              ("scene a.txt\nvary mass from 1 to 2 step 0\n", "Line 2: step must be greater than 0"),
+                              ("scene a.txt\nvary mass 1 2 1\n", "Line 2: write vary as 'vary <parameter> from <start> to <end> step <size>'"),
+                              ("scene a.txt\nvary mass from 1 to 2 step 1\nstop never\n", "Line 3: unknown stop condition 'never' (use ground, time, sleep or contact)"),
+                              ("scene a.txt\nvary mass from 0 to 2 step 1\n", "Line 2: mass must be greater than 0"),
+                              ("scene a.txt\nvary mass from -2 to -1 step 1\n", "Line 2: mass must be greater than 0"),
+                              ("scene a.txt\nvary speed from -1 to 2 step 1\n", "Line 2: speed can't be negative (vary the angle to change the direction)"),
+                              ("scene a.txt\nvary x from 2 to 1 step 1\n", "Line 2: to must not be less than from"),
+                              ("scene a.txt\nvary angle from 0 to 90 step 10\nlaunch_speed 0\n", "Line 3: launch_speed must be greater than 0"),
+                              ("scene a.txt\nvary x from 1 to 2 step 1\nmax_time -5\n", "Line 3: max_time must be greater than 0"),
+                              ("scene a.txt\nvary x from 1 to 2 step 1\nstop time 0\n", "Line 3: stop time must be greater than 0")] {
+            assert_eq!(load("bad", text).err().as_deref(), Some(error));
+        }
+    }
+
+    #[test]
+    fn runs_each_variant() {
+        //A ball drifting with no gravity or air ends up vx metres along after 1s
+        let folder = std::env::temp_dir();
+        let scene = format!("rv_experiment_test_drift_scene_{}.txt", std::process::id());
+        fs::write(folder.join(&scene), "circle x=0 y=0 radius=0.1 type=dynamic gravity=0 air=false\n").unwrap();
+        let experiment = load("drift", &format!("scene {}\nvary vx from 1 to 2 step 1\nmeasure final_x\nstop time 1\n", scene));
+        let results = run_experiment(&experiment.unwrap());
+        fs::remove_file(folder.join(&scene)).unwrap();
+
+        let results = results.ok().unwrap();
+        assert_eq!(results.len(), 2);
+        for (result, vx) in results.iter().zip([1.0, 2.0]) {
+            assert_eq!(result.value, vx);
+            assert!(result.stopped);
+            assert!((result.time - 1.0).abs() < 0.02, "stopped at {}s", result.time);
+            assert!((result.measured - vx * result.time).abs() < 0.01, "vx {} went {}m", vx, result.measured);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use macroquad::camera::Camera2D;
+use macroquad::color::{Color, ORANGE};
+use macroquad::input::{is_mouse_button_down, is_mouse_button_pressed, mouse_position, MouseButton};
+use macroquad::math::Vec2;
+use macroquad::shapes::draw_circle_lines;
+use macroquad::ui::root_ui;
+use crate::measurements::{meter, vec2_meter};
+use crate::objects::electrostatics::draw_arrow;
+use crate::objects::physics::{apply_impulse, PhysicsObject, PhysicsType};
+
+//Whether the impulse tool pushes everything near the click outwards, or hits one object in the direction it is dragged
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub(crate) enum ImpulseMode {
+    Radial,
+    Directional,
+}
+
+//How the impulse of an explosion gets weaker with distance, reaching nothing at its radius (except for constant)
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub(crate) enum Falloff {
+    Constant,
+    Linear,
+    Quadratic,
+}
+
+//The settings for the impulse tool, kept between frames
+#[allow(dead_code)]
+pub(crate) struct ImpulseSettings {
+    pub(crate) mode: ImpulseMode,
+    //The impulse (Ns) given at the centre of an explosion, or given to the object hit for every metre the mouse is dragged
+    pub(crate) strength: f32,
+    pub(crate) radius: f32,
+    pub(crate) falloff: Falloff,
+}
+
+#[allow(dead_code)]
+impl ImpulseMode {
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            ImpulseMode::Radial => "explosion",
+            ImpulseMode::Directional => "single object",
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Falloff {
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            Falloff::Constant => "constant",
+            Falloff::Linear => "linear",
+            Falloff::Quadratic => "quadratic",
+        }
+    }
+
+    //The next falloff, for the button that cycles through them
+    pub(crate) fn next(&self) -> Falloff {
+        match self {
+            Falloff::Constant => Falloff::Linear,
+            Falloff::Linear => Falloff::Quadratic,
+            Falloff::Quadratic => Falloff::Constant,
+        }
+    }
+
+    //How much of the full impulse is given at a distance from the centre, as a fraction of the radius
+    fn get_factor(&self, fraction: f32) -> f32 {
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear => 1. - fraction,
+            Falloff::Quadratic => (1. - fraction).powi(2),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl ImpulseSettings {
+    pub(crate) fn new() -> ImpulseSettings {
+        ImpulseSettings { mode: ImpulseMode::Radial, strength: 5.0, radius: 2.0, falloff: Falloff::Linear }
+    }
+}
+
+//Push every dynamic object within the radius directly away from the centre of the explosion
+#[allow(dead_code)]
+pub(crate) fn apply_radial_impulse(objects: &mut [Box<dyn PhysicsObject>], centre: Vec2, settings: &ImpulseSettings) {
+    for object in objects.iter_mut() {
+        let offset = object.get_render_shape_reference().get_centre() - centre;
+        let distance = offset.length();
+        if distance >= settings.radius || !matches!(object.get_physics_type(), PhysicsType::Dynamic) {
+            continue;
+        }
+        //An object right at the centre is pushed straight up, as it has no direction away from it
+        let direction = if distance > 0.0 { offset / distance } else { Vec2::new(0., -1.) };
+        apply_impulse(object.as_mut(), direction * settings.strength * settings.falloff.get_factor(distance / settings.radius));
+    }
+}
+
+//Use the impulse tool with the mouse. Explosions go off where the user clicks, showing their radius around the mouse.
+//Single objects are hit in the direction the user drags from them when they let go, harder the further they drag
+#[allow(dead_code)]
+pub fn impulse_process(first_mouse_pos: &mut Option<Vec2>, camera: &Camera2D, objects: &mut [Box<dyn PhysicsObject>], settings: &ImpulseSettings) {
+    let mouse = camera.screen_to_world(Vec2::from(mouse_position()));
+    //Ignore clicks on the hotbar and the settings panel
+    let on_ui = mouse_position().1 <= 40. || root_ui().is_mouse_over(Vec2::from(mouse_position()));
+    let colour = Color::new(ORANGE.r, ORANGE.g, ORANGE.b, 0.8);
+    match settings.mode {
+        ImpulseMode::Radial => {
+            draw_circle_lines(mouse.x, mouse.y, meter(settings.radius), 2., colour);
+            if is_mouse_button_pressed(MouseButton::Left) && !on_ui {
+                apply_radial_impulse(objects, vec2_meter(mouse), settings);
+            }
+        }
+        ImpulseMode::Directional => {
+            if is_mouse_button_down(MouseButton::Left) {
+                match *first_mouse_pos {
+                    //Show the direction the object will be hit in
+                    Some(start) => draw_arrow(vec2_meter(start), vec2_meter(mouse), colour),
+                    None if !on_ui => *first_mouse_pos = Some(mouse),
+                    None => {}
+                }
+            } else if let Some(start) = first_mouse_pos.take() {
+                let drag = vec2_meter(mouse - start);
+                if let Some(index) = objects.iter_mut().position(|object| object.get_render_shape_reference().mouse_in_area(start)) {
+                    apply_impulse(objects[index].as_mut(), drag * settings.strength);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::math::Vec2;
+    use crate::objects::scene::Scene;
+    use super::{apply_radial_impulse, Falloff, ImpulseSettings};
+
+    #[test]
+    fn explosions_push_away_from_the_centre() {
+        let mut scene = Scene::load_test("explosion", "circle x=0 y=0 radius=0.1 mass=1 type=dynamic\n\
+            circle x=1 y=0 radius=0.1 mass=2 type=dynamic\n\
+            circle x=0 y=-3 radius=0.1 mass=1 type=dynamic\n\
+            circle x=-1 y=0 radius=0.1 mass=1 type=static\n");
+        let settings = ImpulseSettings::new();
+        apply_radial_impulse(&mut scene.objects, Vec2::ZERO, &settings);
+        let velocities: Vec<Vec2> = scene.objects.iter().map(|object| object.get_velocity()).collect();
+        //An object at the centre goes straight up with the whole impulse
+        assert!((velocities[0] - Vec2::new(0.0, -5.0)).length() < 1e-4);
+        //Halfway to the edge, linear falloff gives half of 5 Ns, so v = J / m = 1.25 m/s
+        assert!((velocities[1] - Vec2::new(1.25, 0.0)).length() < 1e-4);
+        //Objects outside the radius and static objects aren't pushed
+        assert_eq!(velocities[2], Vec2::ZERO);
+        assert_eq!(velocities[3], Vec2::ZERO);
+    }
+
+    #[test]
+    fn falloff_weakens_with_distance() {
+        for (falloff, factor) in [(Falloff::Constant, 1.0), (Falloff::Linear, 0.5), (Falloff::Quadratic, 0.25)] {
+            assert_eq!(falloff.get_factor(0.5), factor);
+            assert_eq!(falloff.get_factor(0.0), 1.0);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use macroquad::color::Color;
+use macroquad::math::Vec2;
+use macroquad::shapes::{draw_line, draw_rectangle, draw_rectangle_lines, draw_triangle};
+use crate::measurements::{dt, meter};
+use crate::objects::forces::{drag_force, Body, ForceGenerator};
+use crate::objects::soft_bodies::get_polygon_area;
+
+//The number of strips a circle is cut into when working out how much of it is under the fluid
+const CIRCLE_STRIPS: usize = 32;
+
+//A rectangle or polygon of fluid (e.g. water). Objects in it are pushed up by the fluid they move out of (Archimedes),
+//and slowed down by drag and viscosity
+pub(crate) struct FluidRegion {
+    //The top left corner and size of the region, the same as a rectangle. For a polygon this is the box around it
+    pub(crate) pos: Vec2,
+    pub(crate) size: Vec2,
+    //The corners of the region in order if it is a polygon, or empty if it is a rectangle
+    pub(crate) corners: Vec<Vec2>,
+    pub(crate) density: f32,
+    pub(crate) viscosity: f32,
+}
+
+impl FluidRegion {
+    //Create a fluid region, turning a negative width or height (from drawing it backwards) into a positive one
+    pub(crate) fn new(pos: Vec2, size: Vec2, density: f32, viscosity: f32) -> FluidRegion {
+        FluidRegion { pos: pos.min(pos + size), size: size.abs(), corners: Vec::new(), density, viscosity }
+    }
+
+    //Create a fluid region with any shape, from its corners in order
+    pub(crate) fn polygon(corners: Vec<Vec2>, density: f32, viscosity: f32) -> FluidRegion {
+        let top_left = corners.iter().fold(Vec2::splat(f32::MAX), |corner, point| corner.min(*point));
+        let bottom_right = corners.iter().fold(Vec2::splat(f32::MIN), |corner, point| corner.max(*point));
+        FluidRegion { pos: top_left, size: bottom_right - top_left, corners, density, viscosity }
+    }
+
+    //The parts of a vertical line at x that are inside the fluid, as (top, bottom) pairs
+    fn get_inside_spans(&self, x: f32) -> Vec<(f32, f32)> {
+        if x < self.pos.x || x > self.pos.x + self.size.x {
+            return Vec::new();
+        }
+        if self.corners.is_empty() {
+            return vec![(self.pos.y, self.pos.y + self.size.y)];
+        }
+        //Find everywhere the line crosses an edge. Going down the line, each crossing swaps between outside and inside
+        let mut crossings = Vec::new();
+        for (index, start) in self.corners.iter().enumerate() {
+            let end = self.corners[(index + 1) % self.corners.len()];
+            if (start.x <= x) != (end.x <= x) {
+                crossings.push(start.y + (x - start.x) / (end.x - start.x) * (end.y - start.y));
+            }
+        }
+        crossings.sort_by(f32::total_cmp);
+        crossings.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect()
+    }
+
+    //Cut the polygon down to the part inside a box (Sutherland-Hodgman), one side of the box at a time
+    fn clip_to_box(&self, top_left: Vec2, bottom_right: Vec2) -> Vec<Vec2> {
+        let mut points = self.corners.clone();
+        //Each side is how far a point is inside it, which is negative for points outside
+        let sides: [&dyn Fn(Vec2) -> f32; 4] = [
+            &|point| point.x - top_left.x,
+            &|point| bottom_right.x - point.x,
+            &|point| point.y - top_left.y,
+            &|point| bottom_right.y - point.y,
+        ];
+        for inside in sides {
+            let mut clipped = Vec::new();
+            for (index, start) in points.iter().enumerate() {
+                let end = points[(index + 1) % points.len()];
+                let (start_inside, end_inside) = (inside(*start), inside(end));
+                if start_inside >= 0.0 {
+                    clipped.push(*start);
+                }
+                //Add the point where the edge crosses the side
+                if (start_inside >= 0.0) != (end_inside >= 0.0) {
+                    clipped.push(start.lerp(end, start_inside / (start_inside - end_inside)));
+                }
+            }
+            points = clipped;
+            if points.is_empty() {
+                break;
+            }
+        }
+        points
+    }
+
+    //Find how much of an object's area is inside the fluid
+    pub(crate) fn get_submerged_area(&self, body: &Body) -> f32 {
+        let top = self.pos.y;
+        let bottom = self.pos.y + self.size.y;
+        let left = self.pos.x;
+        let right = self.pos.x + self.size.x;
+
+        if !body.circle && !self.corners.is_empty() {
+            if body.centre.x + body.half_size.x < left || body.centre.x - body.half_size.x > right
+                || body.centre.y + body.half_size.y < top || body.centre.y - body.half_size.y > bottom {
+                return 0.0;
+            }
+            //The part of the polygon inside the object's rectangle
+            let inside = self.clip_to_box(body.centre - body.half_size, body.centre + body.half_size);
+            return if inside.len() < 3 { 0.0 } else { get_polygon_area(&inside) };
+        }
+        if !body.circle {
+            //Two rectangles overlap in a rectangle
+            let width = (right.min(body.centre.x + body.half_size.x) - left.max(body.centre.x - body.half_size.x)).max(0.0);
+            let height = (bottom.min(body.centre.y + body.half_size.y) - top.max(body.centre.y - body.half_size.y)).max(0.0);
+            return width * height;
+        }
+
+        //Cut the circle into thin strips, and add up how much of each strip is inside the fluid
+        let radius = body.half_size.x;
+        let strip_width = 2. * radius / CIRCLE_STRIPS as f32;
+        let mut area = 0.0;
+        for strip in 0..CIRCLE_STRIPS {
+            let x = body.centre.x - radius + (strip as f32 + 0.5) * strip_width;
+            let half_height = (radius * radius - (x - body.centre.x).powi(2)).max(0.0).sqrt();
+            for (top, bottom) in self.get_inside_spans(x) {
+                let height = (bottom.min(body.centre.y + half_height) - top.max(body.centre.y - half_height)).max(0.0);
+                area += height * strip_width;
+            }
+        }
+        area
+    }
+}
+
+impl ForceGenerator for FluidRegion {
+    fn get_force(&self, body: &Body) -> Vec2 {
+        let submerged = self.get_submerged_area(body);
+        if submerged <= 0.0 {
+            return Vec2::ZERO;
+        }
+        //Buoyancy is the weight of the fluid moved out of the way (F = p A g), upwards against gravity
+        let buoyancy = Vec2::new(0.0, -self.density * submerged * body.gravity);
+
+        //Drag from the fluid (F = 1/2 p v^2 A C_d) and viscosity (F = -n A v), only on the part that is under,
+        //limited so they can only slow the object to a stop in one step
+        let fraction = (submerged / body.area).min(1.0);
+        let resistance = drag_force(body, body.velocity, self.density) * fraction - body.velocity * self.viscosity * submerged;
+        let limit = body.mass * body.velocity.length() / dt();
+        buoyancy + resistance.clamp_length_max(limit)
+    }
+
+    fn draw(&self, _bodies: &[Body]) {
+        //Polygons are filled with triangles from their middle, the same as soft bodies
+        if !self.corners.is_empty() {
+            let middle = self.corners.iter().sum::<Vec2>() / self.corners.len() as f32;
+            for (index, start) in self.corners.iter().enumerate() {
+                let end = self.corners[(index + 1) % self.corners.len()];
+                draw_triangle(middle * meter(1.), *start * meter(1.), end * meter(1.), Color::from_rgba(40, 120, 220, 90));
+                draw_line(meter(start.x), meter(start.y), meter(end.x), meter(end.y), 2., Color::from_rgba(90, 160, 255, 200));
+            }
+            return;
+        }
+        draw_rectangle(meter(self.pos.x), meter(self.pos.y), meter(self.size.x), meter(self.size.y), Color::from_rgba(40, 120, 220, 90));
+        draw_rectangle_lines(meter(self.pos.x), meter(self.pos.y), meter(self.size.x), meter(self.size.y), 2., Color::from_rgba(90, 160, 255, 200));
+    }
+
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        //Polygons are written as their corners, x:y,x:y...
+        if !self.corners.is_empty() {
+            let corners: Vec<String> = self.corners.iter().map(|corner| format!("{}:{}", corner.x, corner.y)).collect();
+            return Some(format!("fluid corners={} density={} viscosity={}", corners.join(","), self.density, self.viscosity));
+        }
+        Some(format!("fluid x={} y={} width={} height={} density={} viscosity={}", self.pos.x, self.pos.y, self.size.x,
+                     self.size.y, self.density, self.viscosity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use macroquad::math::Vec2;
+    use crate::objects::events::EventLog;
+    use crate::objects::forces::Body;
+    use crate::objects::scene::Scene;
+    use super::FluidRegion;
+
+    fn body(centre: Vec2, half_size: Vec2, circle: bool) -> Body {
+        Body { handle: 0, centre, velocity: Vec2::ZERO, mass: 1.0, area: 1.0, gravity: 9.81, drag_coefficient: 1.0,
+               do_air_resistance: false, half_size, circle, charge: 0.0, dynamic: true }
+    }
+
+    #[test]
+    fn submerged_area_matches_the_shapes() {
+        //A circle with its centre on the surface is half under
+        let water = FluidRegion::new(Vec2::new(-2.0, 0.0), Vec2::new(4.0, 2.0), 1.0, 0.0);
+        let area = water.get_submerged_area(&body(Vec2::ZERO, Vec2::splat(0.5), true));
+        assert!((area - PI * 0.25 / 2.).abs() < 0.01, "half a circle had {} m^2 under", area);
+
+        //A square over the whole triangle has the triangle's area under, and a small one inside it is all under
+        let triangle = FluidRegion::polygon(vec![Vec2::new(0.0, 0.0), Vec2::new(2.0, 0.0), Vec2::new(0.0, 2.0)], 1.0, 0.0);
+        assert!((triangle.get_submerged_area(&body(Vec2::splat(1.0), Vec2::splat(1.0), false)) - 2.0).abs() < 1e-4);
+        assert!((triangle.get_submerged_area(&body(Vec2::splat(0.5), Vec2::splat(0.25), false)) - 0.25).abs() < 1e-4);
+        assert!((triangle.get_submerged_area(&body(Vec2::splat(0.5), Vec2::splat(0.25), true)) - PI * 0.0625).abs() < 0.01);
+    }
+
+    #[test]
+    fn light_objects_float() {
+        //A box half as dense as the water floats with half of it under
+        let mut scene = Scene::load_test("floating", "fluid x=-2 y=0 width=4 height=2 density=1000 viscosity=1000\n\
+            rectangle x=-0.5 y=-1 width=1 height=0.5 mass=250 type=dynamic air=false\n");
+        scene.run(&mut EventLog::new(), 900);
+        let bottom = scene.objects[0].get_render_shape_reference().get_centre().y + 0.25;
+        assert!((bottom - 0.25).abs() < 0.02, "the box floated with its bottom at {} m", bottom);
+        assert!(scene.objects[0].get_velocity().length() < 0.05);
+    }
+}
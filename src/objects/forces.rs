@@ -0,0 +1,298 @@
+use std::any::Any;
+use macroquad::color::{Color, ORANGE, SKYBLUE};
+use macroquad::math::Vec2;
+use macroquad::shapes::{draw_circle, draw_circle_lines, draw_line};
+use crate::measurements::{dt, meter};
+use crate::objects::electrostatics::Coulomb;
+use crate::objects::physics::{PhysicsObject, PhysicsType};
+use crate::objects::Render;
+
+//The density of air (kg/m^3), used for drag and wind
+pub(crate) const AIR_DENSITY: f32 = 1.29;
+
+//Everything a force generator needs to know about the object it is pushing
+#[derive(Clone, Copy)]
+pub(crate) struct Body {
+    pub(crate) handle: usize,
+    pub(crate) centre: Vec2,
+    pub(crate) velocity: Vec2,
+    pub(crate) mass: f32,
+    pub(crate) area: f32,
+    pub(crate) gravity: f32,
+    pub(crate) drag_coefficient: f32,
+    pub(crate) do_air_resistance: bool,
+    //Half the width and height of the object (the radius for circles), for working out how much of it is in a region
+    pub(crate) half_size: Vec2,
+    pub(crate) circle: bool,
+    pub(crate) charge: f32,
+    //Only dynamic objects are moved by forces, so static and kinematic ones can't be pushed back
+    pub(crate) dynamic: bool,
+}
+
+//Anything that pushes or pulls on objects. The physics step asks every generator for its force on each dynamic object,
+//adds them together, and accelerates the object by F / m
+pub(crate) trait ForceGenerator: Any {
+    //Get ready for a step, given every object in the scene (e.g. for forces between objects)
+    fn prepare(&mut self, _bodies: &[Body]) {}
+    //Move any objects the generator drives itself, e.g. actuators moving kinematic objects along a path
+    fn drive(&mut self, _objects: &mut [Box<dyn PhysicsObject>]) {}
+    //The force (N) on an object
+    fn get_force(&self, body: &Body) -> Vec2;
+    //The energy (J) stored between the generator and an object, e.g. in a stretched spring
+    fn get_stored_energy(&self, _body: &Body) -> f32 { 0.0 }
+    //The potential energy (J) of every object in a field made by the generator, if it isn't uniform gravity
+    fn get_field_energy(&self, _bodies: &[Body]) -> f32 { 0.0 }
+    //Check if an object must be kept awake, e.g. because it is joined to other objects that are still moving
+    fn keep_awake(&self, _body: &Body) -> bool { false }
+    //Move anything the generator looks after itself (e.g. particles), pushed by the total force from every other generator
+    fn update(&mut self, _others: &dyn Fn(&Body) -> Vec2, _bodies: &[Body]) {}
+    //Draw the generator on top of the scene
+    #[allow(dead_code)]
+    fn draw(&self, _bodies: &[Body]) {}
+    //Add everything the generator moves itself (e.g. its particles) to a checksum, as the bits of each value
+    fn hash_state(&self, _add: &mut dyn FnMut(u32)) {}
+    //The line written to a scene file for this generator, or None if every scene has it anyway.
+    //Objects are written as their index in the scene file, rather than their handle
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> { None }
+}
+
+//Pulls every object down with its own gravity (F = m g)
+pub(crate) struct UniformGravity;
+
+//Air resistance against the direction of movement (F = 1/2 p v^2 A C_d)
+pub(crate) struct Drag;
+
+//A spring between a fixed anchor and an object (F = -k x), with damping to stop it bouncing forever
+pub(crate) struct Spring {
+    pub(crate) handle: usize,
+    pub(crate) anchor: Vec2,
+    pub(crate) stiffness: f32,
+    pub(crate) rest_length: f32,
+    pub(crate) damping: f32,
+}
+
+//Air moving at a constant velocity, pushing objects as drag on their velocity relative to the air
+pub(crate) struct Wind {
+    pub(crate) velocity: Vec2,
+    pub(crate) density: f32,
+}
+
+//Pulls every object towards a point, weaker with distance squared (F = m s / r^2). A negative strength pushes them away.
+//The softening stops the force becoming infinite when an object reaches the point
+pub(crate) struct RadialAttractor {
+    pub(crate) centre: Vec2,
+    pub(crate) strength: f32,
+    pub(crate) softening: f32,
+}
+
+impl Body {
+    pub(crate) fn new(object: &mut dyn PhysicsObject) -> Body {
+        let (centre, area, drag_coefficient, (half_size, circle)) = {
+            let shape = object.get_render_shape_reference();
+            (shape.get_centre(), shape.get_area(), shape.get_drag_coefficient(), get_extents(*shape))
+        };
+        Body {
+            handle: object.get_handle(),
+            centre,
+            velocity: object.get_velocity(),
+            mass: object.get_material().mass,
+            area,
+            gravity: *object.get_gravity(),
+            drag_coefficient,
+            do_air_resistance: *object.set_do_air_resistance(),
+            half_size,
+            circle,
+            charge: *object.get_charge(),
+            dynamic: matches!(object.get_physics_type(), PhysicsType::Dynamic),
+        }
+    }
+}
+
+//Get half the size of a shape, and whether it is a circle
+pub(crate) fn get_extents(shape: &dyn Render) -> (Vec2, bool) {
+    let (measurement1, measurement2) = shape.get_measurements();
+    match shape.get_id() {
+        "Circle" => (Vec2::new(measurement1, measurement1), true),
+        "Square" => (Vec2::splat(measurement1.abs() / 2.), false),
+        _ => (Vec2::new(measurement1, measurement2).abs() / 2., false),
+    }
+}
+
+//Get the bodies of every object, e.g. for drawing the generators or measuring their energy
+pub(crate) fn get_bodies(objects: &mut [Box<dyn PhysicsObject>]) -> Vec<Body> {
+    objects.iter_mut().map(|object| Body::new(object.as_mut())).collect()
+}
+
+//The forces every scene starts with
+pub(crate) fn default_forces() -> Vec<Box<dyn ForceGenerator>> {
+    vec![Box::new(UniformGravity), Box::new(Drag), Box::new(Coulomb::new())]
+}
+
+//Check if there is a generator of a type, e.g. has_force::<UniformGravity>(forces)
+pub(crate) fn has_force<T: ForceGenerator>(forces: &[Box<dyn ForceGenerator>]) -> bool {
+    forces.iter().any(|generator| (generator.as_ref() as &dyn Any).is::<T>())
+}
+
+//Find the first generator of a type, so its settings can be changed
+pub(crate) fn find_force<T: ForceGenerator>(forces: &mut [Box<dyn ForceGenerator>]) -> Option<&mut T> {
+    forces.iter_mut().find_map(|generator| (generator.as_mut() as &mut dyn Any).downcast_mut::<T>())
+}
+
+//Find every generator of a type, e.g. every magnetic field region
+#[allow(dead_code)]
+pub(crate) fn find_forces<T: ForceGenerator>(forces: &mut [Box<dyn ForceGenerator>]) -> Vec<&mut T> {
+    forces.iter_mut().filter_map(|generator| (generator.as_mut() as &mut dyn Any).downcast_mut::<T>()).collect()
+}
+
+//Remove every generator of a type
+pub(crate) fn remove_force<T: ForceGenerator>(forces: &mut Vec<Box<dyn ForceGenerator>>) {
+    forces.retain(|generator| !(generator.as_ref() as &dyn Any).is::<T>());
+}
+
+//Remove one generator of a type, counted among the generators of that type only, e.g. the second magnetic field
+#[allow(dead_code)]
+pub(crate) fn remove_nth_force<T: ForceGenerator>(forces: &mut Vec<Box<dyn ForceGenerator>>, index: usize) {
+    let position = forces.iter().enumerate().filter(|(_, generator)| (generator.as_ref() as &dyn Any).is::<T>()).nth(index);
+    if let Some((position, _)) = position {
+        forces.remove(position);
+    }
+}
+
+//Add up the force from every generator on an object
+pub(crate) fn total_force(forces: &[Box<dyn ForceGenerator>], body: &Body) -> Vec2 {
+    forces.iter().fold(Vec2::ZERO, |total, generator| total + generator.get_force(body))
+}
+
+//Calculate drag against a velocity relative to the air, limited so it can only slow the object to a stop in one step
+pub(crate) fn drag_force(body: &Body, relative_velocity: Vec2, density: f32) -> Vec2 {
+    let speed = relative_velocity.length();
+    let drag = 0.5 * density * speed * speed * body.area * body.drag_coefficient;
+    let limit = body.mass * speed / dt();
+    -relative_velocity.normalize_or_zero() * drag.min(limit)
+}
+
+impl ForceGenerator for UniformGravity {
+    fn get_force(&self, body: &Body) -> Vec2 {
+        Vec2::new(0.0, body.mass * body.gravity)
+    }
+}
+
+impl ForceGenerator for Drag {
+    fn get_force(&self, body: &Body) -> Vec2 {
+        if !body.do_air_resistance {
+            return Vec2::ZERO;
+        }
+        drag_force(body, body.velocity, AIR_DENSITY)
+    }
+}
+
+impl Spring {
+    //How far the spring is stretched past its rest length, along the direction from the anchor to the object
+    fn get_extension(&self, body: &Body) -> (f32, Vec2) {
+        let offset = body.centre - self.anchor;
+        (offset.length() - self.rest_length, offset.normalize_or_zero())
+    }
+}
+
+impl ForceGenerator for Spring {
+    fn get_force(&self, body: &Body) -> Vec2 {
+        if body.handle != self.handle {
+            return Vec2::ZERO;
+        }
+        //Damping only resists movement along the spring
+        let (extension, direction) = self.get_extension(body);
+        let stretch_speed = body.velocity.dot(direction);
+        -direction * (self.stiffness * extension + self.damping * stretch_speed)
+    }
+    //E = 1/2 k x^2
+    fn get_stored_energy(&self, body: &Body) -> f32 {
+        if body.handle != self.handle {
+            return 0.0;
+        }
+        let (extension, _) = self.get_extension(body);
+        0.5 * self.stiffness * extension * extension
+    }
+    fn draw(&self, bodies: &[Body]) {
+        let Some(body) = bodies.iter().find(|body| body.handle == self.handle) else { return; };
+        draw_line(meter(self.anchor.x), meter(self.anchor.y), meter(body.centre.x), meter(body.centre.y), 2., ORANGE);
+        draw_circle(meter(self.anchor.x), meter(self.anchor.y), 4., ORANGE);
+    }
+    fn to_line(&self, index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        let index = index_of(self.handle)?;
+        Some(format!("spring object={} x={} y={} stiffness={} length={} damping={}", index, self.anchor.x, self.anchor.y,
+                     self.stiffness, self.rest_length, self.damping))
+    }
+}
+
+impl ForceGenerator for Wind {
+    fn get_force(&self, body: &Body) -> Vec2 {
+        drag_force(body, body.velocity - self.velocity, self.density)
+    }
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        Some(format!("wind vx={} vy={} density={}", self.velocity.x, self.velocity.y, self.density))
+    }
+}
+
+impl ForceGenerator for RadialAttractor {
+    fn get_force(&self, body: &Body) -> Vec2 {
+        let offset = self.centre - body.centre;
+        let distance_sqr = offset.length_squared() + self.softening * self.softening;
+        if distance_sqr == 0.0 {
+            return Vec2::ZERO;
+        }
+        offset.normalize_or_zero() * body.mass * self.strength / distance_sqr
+    }
+    fn draw(&self, _bodies: &[Body]) {
+        let colour = if self.strength >= 0.0 { SKYBLUE } else { Color::from_rgba(255, 120, 120, 255) };
+        draw_circle_lines(meter(self.centre.x), meter(self.centre.y), 8., 2., colour);
+        draw_circle(meter(self.centre.x), meter(self.centre.y), 3., colour);
+    }
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        Some(format!("attractor x={} y={} strength={} softening={}", self.centre.x, self.centre.y, self.strength, self.softening))
+    }
+}
+
+//Draw every force generator that can be seen
+#[allow(dead_code)]
+pub(crate) fn draw_forces(forces: &[Box<dyn ForceGenerator>], objects: &mut [Box<dyn PhysicsObject>]) {
+    let bodies = get_bodies(objects);
+    for generator in forces {
+        generator.draw(&bodies);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::math::Vec2;
+    use crate::objects::events::EventLog;
+    use crate::objects::scene::Scene;
+    use super::{Body, ForceGenerator, AIR_DENSITY};
+
+    //A generator that pushes every object with the same force, like one a user might add
+    struct Push(Vec2);
+
+    impl ForceGenerator for Push {
+        fn get_force(&self, _body: &Body) -> Vec2 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn added_generator_accelerates_objects() {
+        let mut scene = Scene::load_test("push", "circle x=0 y=0 radius=0.2 mass=2 type=dynamic gravity=0 air=false\n");
+        scene.forces.push(Box::new(Push(Vec2::new(4.0, 0.0))));
+        //a = F / m = 2 m/s^2 for 1s
+        scene.run(&mut EventLog::new(), 60);
+        assert!((scene.objects[0].get_velocity() - Vec2::new(2.0, 0.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn drag_gives_terminal_velocity() {
+        let mut scene = Scene::load_test("terminal", "circle x=0 y=0 radius=0.2 mass=0.1 type=dynamic\n");
+        scene.run(&mut EventLog::new(), 600);
+        //v = sqrt(2 m g / p A Cd)
+        let body = Body::new(scene.objects[0].as_mut());
+        let terminal = (2. * body.mass * body.gravity / (AIR_DENSITY * body.area * body.drag_coefficient)).sqrt();
+        assert!((body.velocity.y - terminal).abs() < terminal * 0.01, "fell at {} m/s instead of {} m/s", body.velocity.y, terminal);
+    }
+}
@@ -0,0 +1,95 @@
+use macroquad::math::Vec2;
+use crate::objects::physics::{Material, PhysicsObject};
+use crate::objects::shapes::{Rectangle, Square};
+use crate::objects::{Object, Render};
+
+//The smallest width or height (m) a piece can have. Thinner pieces can sink through the floor before they are pushed out
+const MIN_PIECE_SIZE: f32 = 0.2;
+//The gap (m) left between neighbouring pieces, so they don't start off touching and push each other apart
+const PIECE_GAP: f32 = 0.01;
+
+//How many columns and rows a rectangle or square breaks into. Each side is halved if the halves aren't too small,
+//and circles never break
+fn get_grid(object: &mut dyn PhysicsObject) -> (usize, usize) {
+    let shape = object.get_render_shape_reference();
+    let (width, height) = match shape.get_id() {
+        "Square" => (shape.get_measurements().0, shape.get_measurements().0),
+        "Rectangle" => shape.get_measurements(),
+        _ => return (1, 1),
+    };
+    let split = |length: f32| if length.abs() / 2. >= MIN_PIECE_SIZE { 2 } else { 1 };
+    (split(width), split(height))
+}
+
+//Check if an object can be broken into more than one piece
+pub(crate) fn can_fracture(object: &mut dyn PhysicsObject) -> bool {
+    let (columns, rows) = get_grid(object);
+    columns * rows > 1
+}
+
+//Give a new piece everything from the object it broke off. Its mass and charge are shared out by area,
+//and it moves at the same velocity, so momentum is kept
+fn make_piece<T: Render + Clone + 'static>(shape: T, object: &mut Box<dyn PhysicsObject>, share: f32) -> Box<dyn PhysicsObject> {
+    let area = shape.get_area();
+    let mut piece = Object::new(shape, Material::new(object.get_material().mass * share, area), *object.get_physics_type());
+    let velocity = object.get_velocity();
+    piece.dx = velocity.x;
+    piece.dy = velocity.y;
+    piece.gravity = *object.get_gravity();
+    piece.do_air_resistance = *object.set_do_air_resistance();
+    piece.collision_category = *object.get_collision_category();
+    piece.collision_mask = *object.get_collision_mask();
+    piece.sensor = *object.get_sensor();
+    piece.charge = *object.get_charge() * share;
+    piece.break_impulse = *object.get_break_impulse();
+    piece.friction = *object.get_friction();
+    piece.restitution = *object.get_restitution();
+    piece.surface_velocity = *object.get_surface_velocity();
+    Box::new(piece)
+}
+
+//Split every object that was broken this step into a grid of pieces. The first piece takes the place of the object,
+//keeping its handle (so joints and the event log still find it), and the rest are added to the end of the list
+pub(crate) fn fracture_objects(objects: &mut Vec<Box<dyn PhysicsObject>>) {
+    let mut pieces: Vec<Box<dyn PhysicsObject>> = Vec::new();
+    for object in objects.iter_mut() {
+        if !*object.get_broken() {
+            continue;
+        }
+        *object.get_broken() = false;
+        let (columns, rows) = get_grid(object.as_mut());
+        let share = 1. / (columns * rows) as f32;
+        let (id, pos, measurements, colour, outline_colour) = {
+            let shape = object.get_render_shape_reference();
+            (shape.get_id().to_string(), *shape.get_pos(), shape.get_measurements(), shape.get_colour(), *shape.get_outline_colour())
+        };
+        //Work from the top left corner, as rectangles can be drawn with a negative width or height
+        let size = if id == "Square" { Vec2::splat(measurements.0) } else { Vec2::new(measurements.0, measurements.1) };
+        let corner = pos.min(pos + size);
+        //Each piece is shrunk to leave half the gap around it, inside its share of the object
+        let cell_size = size.abs() / Vec2::new(columns as f32, rows as f32);
+        let piece_size = cell_size - Vec2::splat(PIECE_GAP);
+
+        for column in 0..columns {
+            for row in 0..rows {
+                let piece_pos = corner + cell_size * Vec2::new(column as f32, row as f32) + Vec2::splat(PIECE_GAP / 2.);
+                if column == 0 && row == 0 {
+                    continue;
+                }
+                if id == "Square" {
+                    pieces.push(make_piece(Square::new(piece_pos, piece_size.x, colour, outline_colour), object, share));
+                } else {
+                    pieces.push(make_piece(Rectangle::new(piece_pos, piece_size.x, piece_size.y, colour, outline_colour), object, share));
+                }
+            }
+        }
+
+        //Shrink the object into the first piece, once every other piece has copied it
+        *object.get_render_shape_reference().get_pos() = corner + Vec2::splat(PIECE_GAP / 2.);
+        object.get_render_shape_reference().set_measurements((piece_size.x, piece_size.y));
+        object.get_material().mass *= share;
+        object.update_material();
+        *object.get_charge() *= share;
+    }
+    objects.append(&mut pieces);
+}
@@ -0,0 +1,344 @@
+use std::f32::consts::TAU;
+use macroquad::color::{Color, WHITE};
+use macroquad::math::Vec2;
+use macroquad::shapes::{draw_circle, draw_line, draw_rectangle_lines};
+use crate::measurements::{dt, meter, random_range, Point, QuadTree, Rect};
+use crate::objects::forces::{Body, ForceGenerator};
+
+//The Boltzmann constant (J/K)
+pub(crate) const BOLTZMANN: f32 = 1.380649e-23;
+//Molecules are made much heavier than real ones (kg), so at room temperature they move a few meters per second and can be watched
+pub(crate) const MOLECULE_MASS: f32 = 2e-21;
+const MOLECULE_RADIUS: f32 = 0.02;
+//How long (s) the pressure is averaged over, as single hits on the walls are very noisy
+const PRESSURE_TIME: f32 = 1.0;
+//How long (s) the thermostat takes to bring the gas to its temperature
+const THERMOSTAT_TIME: f32 = 0.5;
+//The mass of the free piston compared to the gas, and the friction on it so it settles instead of bouncing forever
+const PISTON_MASS_RATIO: f32 = 0.5;
+const PISTON_FRICTION: f32 = 2.0;
+//The narrowest the container can be squashed to
+pub(crate) const MIN_WIDTH: f32 = 0.2;
+
+//A single molecule of the gas, a small circle that collides elastically
+struct Molecule {
+    pos: Vec2,
+    velocity: Vec2,
+}
+
+//A container of ideal gas. The molecules bounce elastically off each other and the walls, and only the walls push them.
+//The right wall is a piston, which can be moved by hand or left free to be pushed by the gas against a pressure outside
+pub(crate) struct GasBox {
+    //The top left corner and size of the inside of the container. The width is set by the piston
+    pub(crate) pos: Vec2,
+    pub(crate) size: Vec2,
+    //The furthest the piston can be pulled out
+    pub(crate) max_width: f32,
+    //Keep the gas at the target temperature (K), like a heat bath around the container
+    pub(crate) thermostat: bool,
+    pub(crate) target_temperature: f32,
+    //Let the piston be pushed by the gas, against the pressure (N/m) outside the container
+    pub(crate) piston_free: bool,
+    pub(crate) outside_pressure: f32,
+    molecules: Vec<Molecule>,
+    piston_velocity: f32,
+    //The width at the end of the last step, so a piston moved by hand pushes the molecules it hits
+    last_width: f32,
+    //The pressure on the walls, averaged over time
+    pressure: f32,
+}
+
+//Pick a random velocity from the Maxwell-Boltzmann distribution at a temperature.
+//In 2D the speeds follow a Rayleigh distribution, and every direction is as likely as every other
+fn random_velocity(temperature: f32) -> Vec2 {
+    let spread = (BOLTZMANN * temperature.max(0.0) / MOLECULE_MASS).sqrt();
+    let speed = spread * (-2.0 * (1.0 - random_range(0.0, 1.0)).ln()).sqrt();
+    Vec2::from_angle(random_range(0.0, TAU)) * speed
+}
+
+//The fraction of molecules per unit of speed (s/m) expected at a speed, from the 2D Maxwell-Boltzmann distribution:
+//f(v) = m v / kT e^(-m v^2 / 2kT)
+#[allow(dead_code)]
+pub(crate) fn maxwell_boltzmann(speed: f32, temperature: f32) -> f32 {
+    if temperature <= 0.0 {
+        return 0.0;
+    }
+    let kt = BOLTZMANN * temperature / MOLECULE_MASS;
+    speed / kt * (-speed * speed / (2.0 * kt)).exp()
+}
+
+impl GasBox {
+    //Create a container filled with molecules at a temperature, turning a negative width or height into a positive one
+    pub(crate) fn new(pos: Vec2, size: Vec2, count: usize, temperature: f32) -> GasBox {
+        let pos = pos.min(pos + size);
+        let size = size.abs().max(Vec2::splat(MIN_WIDTH));
+        let mut gas = GasBox {
+            pos,
+            size,
+            max_width: size.x,
+            thermostat: false,
+            target_temperature: temperature,
+            piston_free: false,
+            outside_pressure: 0.0,
+            molecules: Vec::new(),
+            piston_velocity: 0.0,
+            last_width: size.x,
+            pressure: 0.0,
+        };
+        gas.set_count(count);
+        //Start the pressure at the ideal gas value, rather than waiting for it to build up
+        gas.pressure = gas.get_count() as f32 * BOLTZMANN * gas.get_temperature() / gas.get_volume();
+        gas
+    }
+
+    pub(crate) fn get_count(&self) -> usize {
+        self.molecules.len()
+    }
+
+    //Add or remove molecules, with new ones at the target temperature in random places
+    pub(crate) fn set_count(&mut self, count: usize) {
+        self.molecules.truncate(count);
+        while self.molecules.len() < count {
+            let pos = self.pos + Vec2::new(random_range(MOLECULE_RADIUS, self.size.x - MOLECULE_RADIUS),
+                                           random_range(MOLECULE_RADIUS, self.size.y - MOLECULE_RADIUS));
+            self.molecules.push(Molecule { pos, velocity: random_velocity(self.target_temperature) });
+        }
+    }
+
+    //The temperature (K) from the mean kinetic energy. Molecules in 2D have two degrees of freedom, so KE = kT
+    pub(crate) fn get_temperature(&self) -> f32 {
+        if self.molecules.is_empty() {
+            return 0.0;
+        }
+        let kinetic: f32 = self.molecules.iter().map(|molecule| 0.5 * MOLECULE_MASS * molecule.velocity.length_squared()).sum();
+        kinetic / (self.molecules.len() as f32 * BOLTZMANN)
+    }
+
+    //The pressure (N/m) on the walls, in 2D a force per length
+    #[allow(dead_code)]
+    pub(crate) fn get_pressure(&self) -> f32 {
+        self.pressure
+    }
+
+    //The volume of the container, in 2D an area (m^2)
+    pub(crate) fn get_volume(&self) -> f32 {
+        self.size.x * self.size.y
+    }
+
+    //PV / NkT, which is 1 for an ideal gas
+    #[allow(dead_code)]
+    pub(crate) fn get_ideal_ratio(&self) -> f32 {
+        let nkt = self.get_count() as f32 * BOLTZMANN * self.get_temperature();
+        if nkt == 0.0 { 0.0 } else { self.get_pressure() * self.get_volume() / nkt }
+    }
+
+    //The fraction of molecules per unit of speed (s/m) in each range of speeds from 0 to the max speed
+    #[allow(dead_code)]
+    pub(crate) fn get_speed_histogram(&self, bins: usize, max_speed: f32) -> Vec<f32> {
+        let mut histogram = vec![0.0; bins];
+        let bin_width = max_speed / bins as f32;
+        for molecule in &self.molecules {
+            let bin = (molecule.velocity.length() / bin_width) as usize;
+            if bin < bins {
+                histogram[bin] += 1.0;
+            }
+        }
+        let total = self.molecules.len().max(1) as f32 * bin_width;
+        histogram.iter().map(|count| count / total).collect()
+    }
+
+    //Bounce every molecule off the walls, returning the total impulse (Ns) on the walls and the impulse on the piston
+    fn collide_walls(&mut self, piston_speed: f32) -> (f32, f32) {
+        let (top_left, bottom_right) = (self.pos, self.pos + self.size);
+        let mut wall_impulse = 0.0;
+        let mut piston_impulse = 0.0;
+        for molecule in &mut self.molecules {
+            if molecule.pos.x < top_left.x + MOLECULE_RADIUS {
+                molecule.pos.x = top_left.x + MOLECULE_RADIUS;
+                if molecule.velocity.x < 0.0 {
+                    wall_impulse -= 2.0 * MOLECULE_MASS * molecule.velocity.x;
+                    molecule.velocity.x = -molecule.velocity.x;
+                }
+            }
+            //The piston can be moving, so the molecule bounces off it relative to the piston
+            if molecule.pos.x > bottom_right.x - MOLECULE_RADIUS {
+                molecule.pos.x = bottom_right.x - MOLECULE_RADIUS;
+                let closing_speed = molecule.velocity.x - piston_speed;
+                if closing_speed > 0.0 {
+                    wall_impulse += 2.0 * MOLECULE_MASS * closing_speed;
+                    piston_impulse += 2.0 * MOLECULE_MASS * closing_speed;
+                    molecule.velocity.x = 2.0 * piston_speed - molecule.velocity.x;
+                }
+            }
+            for (wall, direction) in [(top_left.y + MOLECULE_RADIUS, 1.0), (bottom_right.y - MOLECULE_RADIUS, -1.0)] {
+                if (molecule.pos.y - wall) * direction < 0.0 {
+                    molecule.pos.y = wall;
+                    if molecule.velocity.y * direction < 0.0 {
+                        wall_impulse += 2.0 * MOLECULE_MASS * molecule.velocity.y.abs();
+                        molecule.velocity.y = -molecule.velocity.y;
+                    }
+                }
+            }
+        }
+        (wall_impulse, piston_impulse)
+    }
+
+    //Bounce molecules that are touching off each other elastically, finding the neighbours of each one with a QuadTree
+    fn collide_molecules(&mut self) {
+        let centre = self.pos + self.size / 2.;
+        let mut qtree = QuadTree::new_unique(Rect::new(centre.x, centre.y, self.size.x / 2., self.size.y / 2.), 4);
+        for (index, molecule) in self.molecules.iter().enumerate() {
+            qtree.insert(Point::new(molecule.pos.x, molecule.pos.y, index));
+        }
+        let diameter = 2.0 * MOLECULE_RADIUS;
+        for index in 0..self.molecules.len() {
+            let pos = self.molecules[index].pos;
+            for point in qtree.query(&Rect::new(pos.x, pos.y, diameter, diameter)) {
+                if point.index <= index {
+                    continue;
+                }
+                let offset = self.molecules[point.index].pos - pos;
+                let distance = offset.length();
+                if distance >= diameter || distance == 0.0 {
+                    continue;
+                }
+                //Equal masses swap the parts of their velocities along the line between them
+                let normal = offset / distance;
+                let closing_speed = (self.molecules[index].velocity - self.molecules[point.index].velocity).dot(normal);
+                if closing_speed > 0.0 {
+                    self.molecules[index].velocity -= normal * closing_speed;
+                    self.molecules[point.index].velocity += normal * closing_speed;
+                }
+                //Move them apart so they aren't overlapping any more
+                let push = normal * (diameter - distance) / 2.0;
+                self.molecules[index].pos -= push;
+                self.molecules[point.index].pos += push;
+            }
+        }
+    }
+}
+
+impl ForceGenerator for GasBox {
+    //The gas is only pushed by its container, and doesn't push any objects
+    fn get_force(&self, _body: &Body) -> Vec2 {
+        Vec2::ZERO
+    }
+
+    fn update(&mut self, _others: &dyn Fn(&Body) -> Vec2, _bodies: &[Body]) {
+        for molecule in &mut self.molecules {
+            molecule.pos += molecule.velocity * dt();
+        }
+        //A piston moved by hand since the last step is moving at the speed it was moved
+        let piston_speed = if self.piston_free { self.piston_velocity } else { (self.size.x - self.last_width) / dt() };
+        let (wall_impulse, piston_impulse) = self.collide_walls(piston_speed);
+        self.collide_molecules();
+
+        //Average the pressure over time, P = F / L with F = impulse / time
+        let perimeter = 2.0 * (self.size.x + self.size.y);
+        let pressure = wall_impulse / (dt() * perimeter);
+        self.pressure += (pressure - self.pressure) * (dt() / PRESSURE_TIME).min(1.0);
+
+        //Push the free piston with the gas inside and the pressure outside, a = F / m
+        if self.piston_free {
+            let piston_mass = PISTON_MASS_RATIO * MOLECULE_MASS * self.get_count().max(1) as f32;
+            let force = piston_impulse / dt() - self.outside_pressure * self.size.y - PISTON_FRICTION * piston_mass * self.piston_velocity;
+            self.piston_velocity += force / piston_mass * dt();
+            self.size.x += self.piston_velocity * dt();
+            if self.size.x < MIN_WIDTH || self.size.x > self.max_width {
+                self.size.x = self.size.x.clamp(MIN_WIDTH, self.max_width);
+                self.piston_velocity = 0.0;
+            }
+        } else {
+            self.piston_velocity = 0.0;
+        }
+        self.size.x = self.size.x.clamp(MIN_WIDTH, self.max_width);
+        self.last_width = self.size.x;
+
+        //Bring the gas towards the target temperature by scaling every speed (a Berendsen thermostat)
+        let temperature = self.get_temperature();
+        if self.thermostat && temperature > 0.0 {
+            let scale = (1.0 + dt() / THERMOSTAT_TIME * (self.target_temperature / temperature - 1.0)).max(0.0).sqrt();
+            for molecule in &mut self.molecules {
+                molecule.velocity *= scale;
+            }
+        }
+    }
+
+    //Draw the container with its piston, and every molecule coloured from blue (slow) to red (fast)
+    fn draw(&self, _bodies: &[Body]) {
+        let (x, y) = (meter(self.pos.x), meter(self.pos.y));
+        draw_rectangle_lines(x, y, meter(self.max_width), meter(self.size.y), 1., Color::from_rgba(255, 255, 255, 60));
+        draw_line(x, y, x + meter(self.size.x), y, 3., WHITE);
+        draw_line(x, y + meter(self.size.y), x + meter(self.size.x), y + meter(self.size.y), 3., WHITE);
+        draw_line(x, y, x, y + meter(self.size.y), 3., WHITE);
+        draw_line(x + meter(self.size.x), y, x + meter(self.size.x), y + meter(self.size.y), 6., Color::from_rgba(200, 200, 120, 255));
+
+        let typical_speed = (2.0 * BOLTZMANN * self.target_temperature.max(1.0) / MOLECULE_MASS).sqrt();
+        for molecule in &self.molecules {
+            let heat = (molecule.velocity.length() / (2.0 * typical_speed)).min(1.0);
+            let colour = Color::new(heat, 0.3, 1.0 - heat, 1.0);
+            draw_circle(meter(molecule.pos.x), meter(molecule.pos.y), meter(MOLECULE_RADIUS), colour);
+        }
+    }
+
+    fn hash_state(&self, add: &mut dyn FnMut(u32)) {
+        add(self.size.x.to_bits());
+        add(self.piston_velocity.to_bits());
+        for molecule in &self.molecules {
+            for value in [molecule.pos.x, molecule.pos.y, molecule.velocity.x, molecule.velocity.y] {
+                add(value.to_bits());
+            }
+        }
+    }
+
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        Some(format!("gas x={} y={} width={} height={} max_width={} count={} temperature={} thermostat={} piston_free={} outside_pressure={}",
+                     self.pos.x, self.pos.y, self.size.x, self.size.y, self.max_width, self.get_count(), self.target_temperature,
+                     self.thermostat, self.piston_free, self.outside_pressure))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::math::Vec2;
+    use crate::measurements::{seed_random, set_fixed_dt};
+    use crate::objects::forces::ForceGenerator;
+    use super::GasBox;
+
+    fn run(gas: &mut GasBox, steps: usize) {
+        for _ in 0..steps {
+            gas.update(&|_| Vec2::ZERO, &[]);
+        }
+    }
+
+    #[test]
+    fn closed_gas_follows_the_ideal_gas_law() {
+        set_fixed_dt(Some(1. / 60.));
+        seed_random(7);
+        let mut gas = GasBox::new(Vec2::ZERO, Vec2::new(2.0, 2.0), 200, 300.0);
+        let temperature = gas.get_temperature();
+        run(&mut gas, 300);
+        //Every bounce is elastic, so the temperature stays the same, and PV = NkT once the pressure has been measured
+        assert!((gas.get_temperature() - temperature).abs() < temperature * 1e-3);
+        assert!((gas.get_ideal_ratio() - 1.0).abs() < 0.15, "PV / NkT was {}", gas.get_ideal_ratio());
+    }
+
+    #[test]
+    fn thermostat_and_piston_change_the_gas() {
+        set_fixed_dt(Some(1. / 60.));
+        seed_random(8);
+        let mut gas = GasBox::new(Vec2::ZERO, Vec2::new(1.0, 2.0), 100, 300.0);
+        gas.thermostat = true;
+        gas.target_temperature = 600.0;
+        run(&mut gas, 300);
+        assert!((gas.get_temperature() - 600.0).abs() < 6.0, "the gas only got to {} K", gas.get_temperature());
+
+        //With nothing outside, the gas pushes a free piston all the way out and cools as it does work on it
+        gas.thermostat = false;
+        gas.piston_free = true;
+        gas.max_width = 3.0;
+        run(&mut gas, 600);
+        assert_eq!(gas.size.x, 3.0);
+        assert!(gas.get_temperature() < 600.0);
+    }
+}
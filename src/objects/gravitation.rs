@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use macroquad::math::Vec2;
+use crate::measurements::{Point, QuadTree, Rect};
+use crate::objects::forces::{remove_force, Body, Drag, ForceGenerator, UniformGravity};
+
+//Newton's law of gravitation between every object and every other object (F = G m1 m2 / r^2).
+//The field is worked out with Barnes-Hut, using a QuadTree of every object's centre, so it doesn't take n^2 time
+pub(crate) struct NBodyGravity {
+    pub(crate) gravitational_constant: f32,
+    //Stops the force becoming infinite when two objects get very close (m)
+    pub(crate) softening: f32,
+    //How far away a group of objects must be before it is treated as one mass. 0 works out every pair exactly
+    pub(crate) theta: f32,
+    qtree: Option<QuadTree>,
+    masses: Vec<f32>,
+    points: HashMap<usize, Point>,
+}
+
+impl NBodyGravity {
+    pub(crate) fn new(gravitational_constant: f32, softening: f32, theta: f32) -> NBodyGravity {
+        NBodyGravity { gravitational_constant, softening, theta, qtree: None, masses: Vec::new(), points: HashMap::new() }
+    }
+}
+
+impl ForceGenerator for NBodyGravity {
+    //Build the QuadTree from where every object is at the start of the step
+    fn prepare(&mut self, bodies: &[Body]) {
+        self.masses = bodies.iter().map(|body| body.mass).collect();
+        self.points.clear();
+        if bodies.is_empty() {
+            self.qtree = None;
+            return;
+        }
+
+        //Use a square around every object, so the quads stay square as they are split
+        let mut corner1 = bodies[0].centre;
+        let mut corner2 = bodies[0].centre;
+        for body in bodies {
+            corner1 = corner1.min(body.centre);
+            corner2 = corner2.max(body.centre);
+        }
+        let centre = (corner1 + corner2) / 2.;
+        let half_size = ((corner2 - corner1).max_element() / 2.).max(0.5) * 1.01;
+
+        let mut qtree = QuadTree::new_unique(Rect::new(centre.x, centre.y, half_size, half_size), 1);
+        for (index, body) in bodies.iter().enumerate() {
+            let point = Point::new(body.centre.x, body.centre.y, index);
+            qtree.insert(point);
+            self.points.insert(body.handle, point);
+        }
+        qtree.compute_mass(&self.masses);
+        self.qtree = Some(qtree);
+    }
+
+    fn get_force(&self, body: &Body) -> Vec2 {
+        let (Some(qtree), Some(point)) = (&self.qtree, self.points.get(&body.handle)) else { return Vec2::ZERO; };
+        qtree.gravity_at(*point, &self.masses, self.theta, self.softening) * self.gravitational_constant * body.mass
+    }
+
+    //U = -G m1 m2 / r for every pair of objects
+    fn get_field_energy(&self, bodies: &[Body]) -> f32 {
+        let mut energy = 0.0;
+        for (index, body) in bodies.iter().enumerate() {
+            for other in &bodies[index + 1..] {
+                let distance = (body.centre.distance_squared(other.centre) + self.softening * self.softening).sqrt();
+                if distance > 0.0 {
+                    energy -= self.gravitational_constant * body.mass * other.mass / distance;
+                }
+            }
+        }
+        energy
+    }
+
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        Some(format!("nbody g={} softening={} theta={}", self.gravitational_constant, self.softening, self.theta))
+    }
+}
+
+//Turn N-body gravitation on, replacing gravity downwards and air resistance (space has neither),
+//or turn it off and put them back
+pub(crate) fn set_nbody(forces: &mut Vec<Box<dyn ForceGenerator>>, nbody: Option<NBodyGravity>) {
+    remove_force::<NBodyGravity>(forces);
+    remove_force::<UniformGravity>(forces);
+    remove_force::<Drag>(forces);
+    match nbody {
+        Some(nbody) => forces.insert(0, Box::new(nbody)),
+        None => {
+            forces.insert(0, Box::new(Drag));
+            forces.insert(0, Box::new(UniformGravity));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::math::Vec2;
+    use crate::objects::forces::{Body, ForceGenerator};
+    use super::NBodyGravity;
+
+    //A spread out cluster of objects with different masses, placed without using the random number generator
+    fn cluster() -> Vec<Body> {
+        (0..60).map(|index| {
+            let angle = index as f32 * 2.4;
+            let radius = 0.2 + (index % 7) as f32 * 0.5;
+            Body {
+                handle: index,
+                centre: Vec2::from_angle(angle) * radius + Vec2::new((index % 3) as f32 * 4., 0.),
+                velocity: Vec2::ZERO,
+                mass: 1. + (index % 5) as f32,
+                area: 1.,
+                gravity: 0.,
+                drag_coefficient: 0.,
+                do_air_resistance: false,
+                half_size: Vec2::splat(0.1),
+                circle: true,
+                charge: 0.,
+                dynamic: true,
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn barnes_hut_is_close_to_the_direct_sum() {
+        let bodies = cluster();
+        let mut exact = NBodyGravity::new(1.0, 0.05, 0.0);
+        let mut approximate = NBodyGravity::new(1.0, 0.05, 0.5);
+        exact.prepare(&bodies);
+        approximate.prepare(&bodies);
+
+        for body in &bodies {
+            //Theta 0 should be the same as adding up every pair by hand
+            let mut direct = Vec2::ZERO;
+            for other in bodies.iter().filter(|other| other.handle != body.handle) {
+                let offset = other.centre - body.centre;
+                let distance_sqr = offset.length_squared() + 0.05 * 0.05;
+                direct += offset * body.mass * other.mass / (distance_sqr * distance_sqr.sqrt());
+            }
+            let force = exact.get_force(body);
+            assert!((force - direct).length() <= direct.length() * 1e-4, "exact force {} but the direct sum is {}", force, direct);
+            let force = approximate.get_force(body);
+            assert!((force - direct).length() <= direct.length() * 0.05, "Barnes-Hut force {} but the direct sum is {}", force, direct);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use macroquad::color::{Color, RED, WHITE};
+use macroquad::math::Vec2;
+use macroquad::shapes::{draw_circle, draw_line};
+use macroquad::text::draw_text;
+use crate::measurements::{dt, meter};
+use crate::objects::forces::{find_force, Body, ForceGenerator};
+use crate::objects::physics::SLEEP_VELOCITY;
+
+//How many times each step the joints are pulled back to their length. Fewer and long structures sag like they are elastic
+const ITERATIONS: usize = 30;
+
+//A rigid rod holding the centres of two objects the same distance apart, so objects can be built into structures like
+//bridges. It snaps for good if it is pulled or pushed harder than its break force
+pub(crate) struct Joint {
+    pub(crate) a: usize,
+    pub(crate) b: usize,
+    pub(crate) rest_length: f32,
+    //The force (N) the joint snaps at, 0 for joints that never break
+    pub(crate) break_force: f32,
+    pub(crate) broken: bool,
+    //The force (N) in the joint this step, positive when it is stretched
+    pub(crate) tension: f32,
+}
+
+//Every joint in the scene. They are all solved together, as each joint pulls on the others through the objects they share
+pub(crate) struct JointSystem {
+    pub(crate) joints: Vec<Joint>,
+    //The force on every jointed object this step, and whether any of them are moving
+    body_forces: HashMap<usize, Vec2>,
+    moving: bool,
+    //The objects, force and place of every joint that snapped this step, to be put in the event log
+    snapped: Vec<(usize, usize, f32, Vec2)>,
+}
+
+impl Joint {
+    //Join two objects at the distance they are apart now
+    pub(crate) fn new(a: &Body, b: &Body) -> Joint {
+        Joint { a: a.handle, b: b.handle, rest_length: a.centre.distance(b.centre), break_force: 0.0, broken: false, tension: 0.0 }
+    }
+}
+
+impl JointSystem {
+    pub(crate) fn new() -> JointSystem {
+        JointSystem { joints: Vec::new(), body_forces: HashMap::new(), moving: false, snapped: Vec::new() }
+    }
+
+    //Every joint that snapped this step. They are only given once
+    pub(crate) fn take_snaps(&mut self) -> Vec<(usize, usize, f32, Vec2)> {
+        std::mem::take(&mut self.snapped)
+    }
+}
+
+//Add a joint to the scene, creating the joint system if this is the first one
+pub(crate) fn add_joint(forces: &mut Vec<Box<dyn ForceGenerator>>, joint: Joint) {
+    match find_force::<JointSystem>(forces) {
+        Some(system) => system.joints.push(joint),
+        None => {
+            let mut system = JointSystem::new();
+            system.joints.push(joint);
+            forces.push(Box::new(system));
+        }
+    }
+}
+
+impl ForceGenerator for JointSystem {
+    //The pull of every joint on an object
+    fn get_force(&self, body: &Body) -> Vec2 {
+        self.body_forces.get(&body.handle).copied().unwrap_or(Vec2::ZERO)
+    }
+
+    //Keep every jointed object awake while any of them are moving, so one isn't left hanging in the air
+    fn keep_awake(&self, body: &Body) -> bool {
+        self.moving && self.body_forces.contains_key(&body.handle)
+    }
+
+    //Find where every jointed object would be at the end of the step without the joints, then pull the joints back to their
+    //length by moving those positions (position based dynamics), sharing the move by mass. The force on each object is what
+    //moves it there instead, F = m x / dt^2, and the tension is found the same way from how far each joint moved its objects
+    fn update(&mut self, others: &dyn Fn(&Body) -> Vec2, bodies: &[Body]) {
+        self.body_forces.clear();
+        self.moving = false;
+        let mut predicted: HashMap<usize, (Vec2, f32)> = HashMap::new();
+        for joint in self.joints.iter_mut().filter(|joint| !joint.broken) {
+            joint.tension = 0.0;
+            for handle in [joint.a, joint.b] {
+                let Some(body) = bodies.iter().find(|body| body.handle == handle) else { continue; };
+                self.moving |= body.velocity.length() > SLEEP_VELOCITY;
+                predicted.entry(handle).or_insert_with(|| {
+                    //Objects that can't be pushed are treated as having infinite mass
+                    let (acceleration, inverse_mass) = if body.dynamic { (others(body) / body.mass, 1. / body.mass) } else { (Vec2::ZERO, 0.0) };
+                    (body.centre + (body.velocity + acceleration * dt()) * dt(), inverse_mass)
+                });
+            }
+        }
+        let start = predicted.clone();
+
+        for _ in 0..ITERATIONS {
+            for joint in self.joints.iter_mut().filter(|joint| !joint.broken) {
+                let (Some(&(pos_a, inverse_a)), Some(&(pos_b, inverse_b))) = (predicted.get(&joint.a), predicted.get(&joint.b)) else { continue; };
+                let offset = pos_b - pos_a;
+                let length = offset.length();
+                if inverse_a + inverse_b == 0.0 || length == 0.0 {
+                    continue;
+                }
+                let correction = (length - joint.rest_length) / (inverse_a + inverse_b);
+                let direction = offset / length;
+                predicted.insert(joint.a, (pos_a + direction * correction * inverse_a, inverse_a));
+                predicted.insert(joint.b, (pos_b - direction * correction * inverse_b, inverse_b));
+                joint.tension += correction / (dt() * dt());
+            }
+        }
+
+        for (handle, (pos, inverse_mass)) in &predicted {
+            let force = if *inverse_mass > 0.0 { (*pos - start[handle].0) / *inverse_mass / (dt() * dt()) } else { Vec2::ZERO };
+            self.body_forces.insert(*handle, force);
+        }
+        //Snap every joint that had to pull or push harder than it can take
+        for joint in self.joints.iter_mut().filter(|joint| !joint.broken) {
+            if joint.break_force > 0.0 && joint.tension.abs() > joint.break_force {
+                joint.broken = true;
+                let point = bodies.iter().filter(|body| body.handle == joint.a || body.handle == joint.b)
+                    .fold(Vec2::ZERO, |total, body| total + body.centre / 2.);
+                self.snapped.push((joint.a, joint.b, joint.tension, point));
+            }
+        }
+    }
+
+    //Draw every joint between the centres of its objects, getting redder the closer it is to snapping, with the force in it
+    fn draw(&self, bodies: &[Body]) {
+        let centres: HashMap<usize, Vec2> = bodies.iter().map(|body| (body.handle, body.centre)).collect();
+        for joint in self.joints.iter().filter(|joint| !joint.broken) {
+            let (Some(a), Some(b)) = (centres.get(&joint.a), centres.get(&joint.b)) else { continue; };
+            let strain = if joint.break_force > 0.0 { (joint.tension.abs() / joint.break_force).min(1.0) } else { 0.0 };
+            let colour = Color::from_vec(WHITE.to_vec().lerp(RED.to_vec(), strain));
+            draw_line(meter(a.x), meter(a.y), meter(b.x), meter(b.y), 3., colour);
+            draw_circle(meter(a.x), meter(a.y), 4., colour);
+            draw_circle(meter(b.x), meter(b.y), 4., colour);
+            let middle = (*a + *b) / 2.;
+            draw_text(&format!("{:.1} N", joint.tension), meter(middle.x) + 6., meter(middle.y) - 6., 16., colour);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use macroquad::color::Color;
+use macroquad::math::Vec2;
+use macroquad::shapes::{draw_circle, draw_circle_lines, draw_line, draw_rectangle, draw_rectangle_lines};
+use crate::measurements::{dt, meter};
+use crate::objects::forces::{Body, ForceGenerator};
+
+//A region with the same magnetic field everywhere inside it, pointing straight out of or into the screen.
+//Charges moving through it are pushed sideways (F = q v x B), so they move in circles
+pub(crate) struct MagneticField {
+    //The top left corner and size of the region, the same as a rectangle
+    pub(crate) pos: Vec2,
+    pub(crate) size: Vec2,
+    //The magnetic flux density (T). Positive is out of the screen, negative is into it
+    pub(crate) strength: f32,
+}
+
+impl MagneticField {
+    //Create a field region, turning a negative width or height (from drawing it backwards) into a positive one
+    pub(crate) fn new(pos: Vec2, size: Vec2, strength: f32) -> MagneticField {
+        MagneticField { pos: pos.min(pos + size), size: size.abs(), strength }
+    }
+
+    //Check if a point is inside the region
+    fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.pos).all() && point.cmple(self.pos + self.size).all()
+    }
+}
+
+impl ForceGenerator for MagneticField {
+    fn get_force(&self, body: &Body) -> Vec2 {
+        if body.charge == 0.0 || !self.contains(body.centre) {
+            return Vec2::ZERO;
+        }
+        //The force is always at right angles to the velocity, so it turns the object at w = q B / m without speeding it up.
+        //Turning the velocity by exactly the angle for one step keeps the speed the same, where adding F = q v x B would slowly speed it up
+        let angle = body.charge * self.strength / body.mass * dt();
+        let turned = Vec2::from_angle(angle).rotate(body.velocity);
+        (turned - body.velocity) * body.mass / dt()
+    }
+
+    //Draw the region with dots for a field out of the screen, or crosses for a field into it
+    fn draw(&self, _bodies: &[Body]) {
+        let colour = Color::from_rgba(120, 220, 255, 200);
+        draw_rectangle(meter(self.pos.x), meter(self.pos.y), meter(self.size.x), meter(self.size.y), Color::from_rgba(120, 220, 255, 30));
+        draw_rectangle_lines(meter(self.pos.x), meter(self.pos.y), meter(self.size.x), meter(self.size.y), 2., colour);
+        if self.strength == 0.0 {
+            return;
+        }
+        //One symbol for every half meter, at most 10 in each direction
+        let columns = (self.size.x * 2.).clamp(1., 10.) as usize;
+        let rows = (self.size.y * 2.).clamp(1., 10.) as usize;
+        for column in 0..columns {
+            for row in 0..rows {
+                let centre = self.pos + self.size * Vec2::new((column as f32 + 0.5) / columns as f32, (row as f32 + 0.5) / rows as f32);
+                let (x, y) = (meter(centre.x), meter(centre.y));
+                if self.strength > 0.0 {
+                    draw_circle_lines(x, y, 8., 2., colour);
+                    draw_circle(x, y, 2.5, colour);
+                } else {
+                    draw_line(x - 6., y - 6., x + 6., y + 6., 2., colour);
+                    draw_line(x - 6., y + 6., x + 6., y - 6., 2., colour);
+                }
+            }
+        }
+    }
+
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        Some(format!("bfield x={} y={} width={} height={} strength={}", self.pos.x, self.pos.y, self.size.x, self.size.y, self.strength))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use crate::objects::events::EventLog;
+    use crate::objects::scene::Scene;
+
+    #[test]
+    fn charges_circle_in_the_field() {
+        //w = q B / m = 2 pi rad/s, so the charge goes round once a second on a circle of radius v / w, keeping its speed
+        let mut scene = Scene::load_test("bfield_circle", &format!("bfield x=-2 y=-2 width=4 height=4 strength={}\n\
+            circle x=0 y=0 radius=0.05 mass=1 type=dynamic gravity=0 air=false charge=1 vx=1\n", 2. * PI));
+        let start = scene.objects[0].get_render_shape_reference().get_centre();
+        let mut events = EventLog::new();
+        scene.run(&mut events, 30);
+        let halfway = scene.objects[0].get_render_shape_reference().get_centre();
+        assert!((halfway.distance(start) - 1. / PI).abs() < 0.01, "moved {} m in half a turn", halfway.distance(start));
+        assert!((scene.objects[0].get_velocity().x + 1.0).abs() < 1e-3);
+        scene.run(&mut events, 30);
+        assert!(scene.objects[0].get_render_shape_reference().get_centre().distance(start) < 0.01);
+        assert!((scene.objects[0].get_velocity().length() - 1.0).abs() < 1e-4);
+    }
+}
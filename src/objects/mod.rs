@@ -1,12 +1,43 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
 use macroquad::{self};
 use macroquad::math::Vec2;
-use macroquad::color::Color;
+use macroquad::color::{Color, SKYBLUE, YELLOW};
+use macroquad::shapes::{draw_circle, draw_circle_lines};
+use macroquad::text::draw_text;
 
 pub(crate) mod shapes;
 pub(crate) mod physics;
+pub(crate) mod events;
+pub(crate) mod forces;
+pub(crate) mod gravitation;
+pub(crate) mod fluids;
+pub(crate) mod electrostatics;
+pub(crate) mod magnetism;
+pub(crate) mod zones;
+pub(crate) mod soft_bodies;
+pub(crate) mod particles;
+pub(crate) mod gas;
+pub(crate) mod sph;
+pub(crate) mod cloth;
+pub(crate) mod joints;
+pub(crate) mod fracture;
+pub(crate) mod actuators;
+pub(crate) mod world;
+pub(crate) mod scene;
 pub(crate) mod create_objects;
-use physics::{Material, PhysicsType};
-use crate::measurements::{dt, Point, QuadTree};
+pub(crate) mod explosions;
+pub(crate) mod slingshot;
+pub(crate) mod orbits;
+pub(crate) mod experiment;
+pub(crate) mod script;
+use physics::{Material, PhysicsType, SLEEP_VELOCITY};
+use crate::measurements::{dt, meter, Point, QuadTree};
+use physics::PhysicsObject;
+use electrostatics::draw_arrow;
+use forces::get_extents;
+
+//How far apart (m) the arrows on a conveyor belt are drawn
+const BELT_ARROW_GAP: f32 = 0.5;
 
 //Give all the functions for shapes that can be rendered (this will be used for everything related to shapes)
 #[allow(dead_code)]
@@ -25,6 +56,14 @@ pub(crate) trait Render {
     fn get_outline_colour(&self) -> &Color;
     fn set_outline_colour(&mut self, colour: Color);
     fn detect_near_object(&mut self, qtree: &mut QuadTree) -> Vec<Point>;
+    fn get_centre(&self) -> Vec2;
+}
+
+//Every object is given its own handle, so it can be told apart from the others in the event log
+static NEXT_HANDLE: AtomicUsize = AtomicUsize::new(0);
+
+fn next_handle() -> usize {
+    NEXT_HANDLE.fetch_add(1, Ordering::Relaxed)
 }
 
 //Create the object struct
@@ -38,13 +77,34 @@ pub (crate) struct Object<T> where T: Render {
     do_air_resistance: bool,
     phys_type: PhysicsType,
     to_be_deleted: bool,
+    sleeping: bool,
+    sleep_timer: f32,
+    last_pos: Vec2,
+    collision_category: u32,
+    collision_mask: u32,
+    sensor: bool,
+    //Electric charge (C), 0 for objects that aren't charged
+    charge: f32,
+    //The impulse (Ns) of a hit that breaks the object into pieces, 0 for objects that never break
+    break_impulse: f32,
+    //Set when the object is hit hard enough to break, so it is split at the end of the step
+    broken: bool,
+    //The coefficient of friction with anything the object touches. Objects use the higher of the two, so 0 is frictionless
+    friction: f32,
+    //The coefficient of restitution, how much of the closing speed is kept as a bounce. Objects use the higher of the two,
+    //so 0 stops at a surface and 1 bounces back perfectly elastically
+    restitution: f32,
+    //How fast (m/s) the object's surface moves around it clockwise, like a conveyor belt, e.g. its top moves right
+    surface_velocity: f32,
+    handle: usize,
 }
 
 //Implement functions for the object trait
 impl<T: Render> Object<T>{
     //New function for the Object type. dx, dy and gravity do not have to be constant, however to_be_deleted should always start false
-    pub(crate) fn new(shape: T, material: Material, phys_type: PhysicsType) -> Object<T> {
+    pub(crate) fn new(mut shape: T, material: Material, phys_type: PhysicsType) -> Object<T> {
         Object {
+            last_pos: *shape.get_pos(),
             shape,
             material,
             dx: 0.0,
@@ -53,13 +113,26 @@ impl<T: Render> Object<T>{
             do_air_resistance: true,
             phys_type,
             to_be_deleted: false,
+            sleeping: false,
+            sleep_timer: 0.0,
+            collision_category: 1,
+            collision_mask: u32::MAX,
+            sensor: false,
+            charge: 0.0,
+            break_impulse: 0.0,
+            broken: false,
+            friction: 0.0,
+            restitution: 0.0,
+            surface_velocity: 0.0,
+            handle: next_handle(),
         }
     }
     #[allow(dead_code)]
     //The same as the previous new function, however creates the material for the shape and mass, saving time.
-    pub(crate) fn create(shape: T, mass: f32, phys_type: PhysicsType) -> Object<T> {
+    pub(crate) fn create(mut shape: T, mass: f32, phys_type: PhysicsType) -> Object<T> {
         Object {
             material: Material::new(mass, shape.get_area()),
+            last_pos: *shape.get_pos(),
             shape,
             dx: 0.0,
             dy: 0.0,
@@ -67,6 +140,18 @@ impl<T: Render> Object<T>{
             do_air_resistance: true,
             phys_type,
             to_be_deleted: false,
+            sleeping: false,
+            sleep_timer: 0.0,
+            collision_category: 1,
+            collision_mask: u32::MAX,
+            sensor: false,
+            charge: 0.0,
+            break_impulse: 0.0,
+            broken: false,
+            friction: 0.0,
+            restitution: 0.0,
+            surface_velocity: 0.0,
+            handle: next_handle(),
         }
     }
     //Process the movement of any objects
@@ -74,12 +159,63 @@ impl<T: Render> Object<T>{
         self.shape.get_pos().y += self.dy * dt();
         self.shape.get_pos().x += self.dx * dt();
     }
+    //Time how long the object has barely moved for. It is put to sleep with everything it is touching once they have all
+    //been still for long enough (see world::sleep_groups)
+    fn sleep_process(&mut self) {
+        let pos = *self.shape.get_pos();
+        let moved = pos.distance(self.last_pos);
+        self.last_pos = pos;
+
+        if moved < SLEEP_VELOCITY * dt() {
+            self.sleep_timer += dt();
+        } else {
+            self.sleep_timer = 0.0;
+        }
+    }
 }
 
 
 //Loop through all objects needed to be rendered, and render them
+#[allow(dead_code)]
 pub fn render_objects(objects: &Vec<Box<dyn Render>>) {
     for object in objects {
         object.render();
     }
-}
\ No newline at end of file
+}
+
+//Mark every sleeping object with a small dot at its centre, so the user can see it is resting
+#[allow(dead_code)]
+pub fn draw_sleep_indicators(objects: &mut Vec<Box<dyn PhysicsObject>>) {
+    for object in objects {
+        if object.get_sleeping() {
+            let centre = object.get_render_shape_reference().get_centre();
+            draw_circle(meter(centre.x), meter(centre.y), 4., SKYBLUE);
+            draw_circle_lines(meter(centre.x), meter(centre.y), 7., 1., SKYBLUE);
+        }
+    }
+}
+
+//Draw arrows along the top and bottom of every conveyor belt, showing which way its surface moves and how fast
+#[allow(dead_code)]
+pub fn draw_belt_arrows(objects: &mut Vec<Box<dyn PhysicsObject>>) {
+    for object in objects {
+        let speed = *object.get_surface_velocity();
+        if speed == 0.0 {
+            continue;
+        }
+        let shape = object.get_render_shape_reference();
+        let (half_size, _) = get_extents(*shape);
+        let centre = shape.get_centre();
+        //Clockwise, so the top moves right and the bottom moves left for a positive speed
+        let direction = Vec2::new(speed.signum() * 0.1, 0.0);
+        let count = ((half_size.x * 2. / BELT_ARROW_GAP) as usize).max(1);
+        for index in 0..count {
+            let x = centre.x - half_size.x + (index as f32 + 0.5) * half_size.x * 2. / count as f32;
+            let top = Vec2::new(x, centre.y - half_size.y - 0.05);
+            let bottom = Vec2::new(x, centre.y + half_size.y + 0.05);
+            draw_arrow(top - direction, top + direction, YELLOW);
+            draw_arrow(bottom + direction, bottom - direction, YELLOW);
+        }
+        draw_text(&format!("{:.2} m/s", speed), meter(centre.x) - 20., meter(centre.y) + 5., 16., YELLOW);
+    }
+}
@@ -0,0 +1,111 @@
+use std::f32::consts::PI;
+use macroquad::math::Vec2;
+use crate::objects::physics::{PhysicsObject, PhysicsType};
+
+//The shape and size of an orbit, worked out from where an object is and how fast it is moving relative to the body it orbits
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub(crate) struct OrbitalElements {
+    pub(crate) distance: f32,
+    pub(crate) semi_major_axis: f32,
+    pub(crate) eccentricity: f32,
+    //None when the object is moving fast enough to escape, so it never comes back round
+    pub(crate) period: Option<f32>,
+}
+
+//The standard gravitational parameter of two bodies (mu = G (M + m)). A body that can't move is pulled on by nothing,
+//so only its own mass counts
+#[allow(dead_code)]
+pub(crate) fn get_mu(gravitational_constant: f32, object: &mut Box<dyn PhysicsObject>, centre: &mut Box<dyn PhysicsObject>) -> f32 {
+    let centre_mass = centre.get_material().mass;
+    match centre.get_physics_type() {
+        PhysicsType::Dynamic => gravitational_constant * (centre_mass + object.get_material().mass),
+        _ => gravitational_constant * centre_mass,
+    }
+}
+
+//Find the object pulling hardest on another (the largest m / r^2), as the body it is most likely orbiting
+#[allow(dead_code)]
+pub(crate) fn find_primary(objects: &mut [Box<dyn PhysicsObject>], index: usize) -> Option<usize> {
+    let pos = objects[index].get_render_shape_reference().get_centre();
+    let mut best: Option<(usize, f32)> = None;
+    for (other, object) in objects.iter_mut().enumerate() {
+        let distance_sqr = object.get_render_shape_reference().get_centre().distance_squared(pos);
+        if other == index || distance_sqr == 0.0 {
+            continue;
+        }
+        let pull = object.get_material().mass / distance_sqr;
+        if best.is_none_or(|(_, best_pull)| pull > best_pull) {
+            best = Some((other, pull));
+        }
+    }
+    best.map(|(other, _)| other)
+}
+
+//Work out the orbit from the position and velocity relative to the centre
+#[allow(dead_code)]
+pub(crate) fn get_orbital_elements(relative_pos: Vec2, relative_velocity: Vec2, mu: f32) -> OrbitalElements {
+    let distance = relative_pos.length();
+    //Specific orbital energy: e = v^2 / 2 - mu / r, and specific angular momentum: h = r x v
+    let energy = relative_velocity.length_squared() / 2. - mu / distance;
+    let angular_momentum = relative_pos.perp_dot(relative_velocity);
+
+    let semi_major_axis = -mu / (2. * energy);
+    let eccentricity = (1. + 2. * energy * angular_momentum * angular_momentum / (mu * mu)).max(0.0).sqrt();
+    //Kepler's third law: T = 2 pi sqrt(a^3 / mu)
+    let period = if energy < 0.0 { Some(2. * PI * (semi_major_axis.powi(3) / mu).sqrt()) } else { None };
+
+    OrbitalElements { distance, semi_major_axis, eccentricity, period }
+}
+
+//Find the velocity (relative to the centre) for an orbit with an eccentricity, starting at its closest point (periapsis).
+//An eccentricity of 0 gives a circular orbit. The orbit goes the same way round as the object already is, or anticlockwise on screen if it isn't moving
+#[allow(dead_code)]
+pub(crate) fn get_orbit_velocity(relative_pos: Vec2, relative_velocity: Vec2, mu: f32, eccentricity: f32) -> Vec2 {
+    let distance = relative_pos.length();
+    if distance == 0.0 {
+        return Vec2::ZERO;
+    }
+    //Vis-viva at periapsis: v = sqrt(mu (1 + e) / r)
+    let speed = (mu * (1. + eccentricity) / distance).sqrt();
+    //The y axis points down, so this perpendicular goes anticlockwise on screen
+    let mut direction = relative_pos.perp().normalize() * -1.;
+    if relative_pos.perp_dot(relative_velocity) > 0.0 {
+        direction = -direction;
+    }
+    direction * speed
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use macroquad::math::Vec2;
+    use super::{get_orbit_velocity, get_orbital_elements};
+
+    #[test]
+    fn preset_velocities_give_the_chosen_orbit() {
+        let (pos, mu) = (Vec2::new(2.0, 0.0), 8.0);
+        //A circular orbit stays at the same distance, and goes round in T = 2 pi sqrt(r^3 / mu)
+        let circular = get_orbital_elements(pos, get_orbit_velocity(pos, Vec2::ZERO, mu, 0.0), mu);
+        assert!(circular.eccentricity < 1e-3);
+        assert!((circular.semi_major_axis - 2.0).abs() < 1e-3);
+        assert!((circular.period.unwrap() - 2. * PI).abs() < 1e-3);
+
+        //Starting at periapsis, r = a (1 - e)
+        let elliptical = get_orbital_elements(pos, get_orbit_velocity(pos, Vec2::ZERO, mu, 0.5), mu);
+        assert!((elliptical.eccentricity - 0.5).abs() < 1e-3);
+        assert!((elliptical.semi_major_axis - 4.0).abs() < 1e-3);
+
+        //Anything at or past escape velocity never comes back
+        assert!(get_orbital_elements(pos, get_orbit_velocity(pos, Vec2::ZERO, mu, 1.5), mu).period.is_none());
+    }
+
+    #[test]
+    fn orbits_keep_going_the_same_way_round() {
+        let pos = Vec2::new(0.0, -3.0);
+        for velocity in [Vec2::new(1.0, 0.0), Vec2::new(-1.0, 0.0)] {
+            let new_velocity = get_orbit_velocity(pos, velocity, 1.0, 0.0);
+            assert!(pos.perp_dot(new_velocity).signum() == pos.perp_dot(velocity).signum());
+        }
+    }
+}
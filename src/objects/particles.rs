@@ -0,0 +1,322 @@
+use std::f32::consts::PI;
+use macroquad::color::Color;
+use macroquad::math::Vec2;
+use macroquad::shapes::{draw_circle, draw_circle_lines};
+use crate::measurements::{dt, meter, random_range, Point, QuadTree, Rect};
+use crate::objects::electrostatics::draw_arrow;
+use crate::objects::forces::{Body, ForceGenerator};
+
+//The most particles one emitter can have at once, so a high rate can't slow the program down forever
+const MAX_PARTICLES: usize = 2000;
+//Particles are treated as small spheres when working out drag
+const PARTICLE_DRAG_COEFFICIENT: f32 = 0.47;
+//Particles aren't objects, so they are given a handle no object can have
+const PARTICLE_HANDLE: usize = usize::MAX;
+
+//The effects an emitter can be set up for from the emitter panel
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub(crate) enum EmitterPreset {
+    Smoke,
+    Sparks,
+    Gas,
+}
+
+//A single particle, which only lives for the emitter's lifetime
+struct Particle {
+    pos: Vec2,
+    velocity: Vec2,
+    age: f32,
+}
+
+//A point that keeps making particles. The particles are pushed by every other force generator (gravity, drag, wind,
+//fluids...) and can bounce off objects, but they are too light to push the objects back
+pub(crate) struct ParticleEmitter {
+    pub(crate) pos: Vec2,
+    //Particles made every second
+    pub(crate) rate: f32,
+    //The speed particles are made with (m/s), and how much faster or slower than it each one can be
+    pub(crate) speed: f32,
+    pub(crate) speed_spread: f32,
+    //The direction particles are fired in (degrees, clockwise on screen from the right), and the width of the cone around it
+    pub(crate) angle: f32,
+    pub(crate) angle_spread: f32,
+    //How long each particle lasts (s)
+    pub(crate) lifetime: f32,
+    //The radius (m) and mass (kg) of each particle
+    pub(crate) size: f32,
+    pub(crate) mass: f32,
+    pub(crate) gravity: f32,
+    pub(crate) do_air_resistance: bool,
+    //The colour of a particle when it is made, fading to the end colour when it runs out of time
+    pub(crate) start_colour: Color,
+    pub(crate) end_colour: Color,
+    pub(crate) collide: bool,
+    //How much of its speed into a surface a particle keeps when it bounces off (1 is perfectly elastic)
+    pub(crate) bounce: f32,
+    particles: Vec<Particle>,
+    //The part of a particle waiting to be made, so rates that aren't a whole number per step still work
+    spawn_timer: f32,
+}
+
+impl EmitterPreset {
+    #[allow(dead_code)]
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            EmitterPreset::Smoke => "smoke",
+            EmitterPreset::Sparks => "sparks",
+            EmitterPreset::Gas => "gas",
+        }
+    }
+}
+
+impl ParticleEmitter {
+    pub(crate) fn new(pos: Vec2, preset: EmitterPreset) -> ParticleEmitter {
+        let mut emitter = ParticleEmitter {
+            pos,
+            rate: 0.0,
+            speed: 0.0,
+            speed_spread: 0.0,
+            angle: 0.0,
+            angle_spread: 0.0,
+            lifetime: 0.0,
+            size: 0.0,
+            mass: 0.0,
+            gravity: 0.0,
+            do_air_resistance: true,
+            start_colour: Color::new(1.0, 1.0, 1.0, 1.0),
+            end_colour: Color::new(1.0, 1.0, 1.0, 0.0),
+            collide: true,
+            bounce: 0.0,
+            particles: Vec::new(),
+            spawn_timer: 0.0,
+        };
+        emitter.set_preset(preset);
+        emitter
+    }
+
+    //Change every setting to the ones for an effect, keeping the particles that already exist
+    pub(crate) fn set_preset(&mut self, preset: EmitterPreset) {
+        match preset {
+            //Hot smoke is lighter than the air around it, so it floats upwards and is slowed down a lot by drag
+            EmitterPreset::Smoke => {
+                (self.rate, self.speed, self.speed_spread, self.angle, self.angle_spread) = (30.0, 1.0, 0.3, 270.0, 40.0);
+                (self.lifetime, self.size, self.mass, self.gravity, self.bounce) = (4.0, 0.06, 0.001, -2.0, 0.1);
+                self.do_air_resistance = true;
+                self.start_colour = Color::new(0.6, 0.6, 0.6, 0.6);
+                self.end_colour = Color::new(0.3, 0.3, 0.3, 0.0);
+            }
+            //Small heavy sparks fly out quickly, fall and bounce off the floor
+            EmitterPreset::Sparks => {
+                (self.rate, self.speed, self.speed_spread, self.angle, self.angle_spread) = (60.0, 5.0, 2.0, 270.0, 90.0);
+                (self.lifetime, self.size, self.mass, self.gravity, self.bounce) = (1.5, 0.02, 0.01, 9.81, 0.5);
+                self.do_air_resistance = true;
+                self.start_colour = Color::new(1.0, 0.9, 0.3, 1.0);
+                self.end_colour = Color::new(1.0, 0.2, 0.0, 0.0);
+            }
+            //Gas molecules move in every direction without gravity or drag, and bounce elastically off the walls
+            EmitterPreset::Gas => {
+                (self.rate, self.speed, self.speed_spread, self.angle, self.angle_spread) = (20.0, 3.0, 1.0, 0.0, 360.0);
+                (self.lifetime, self.size, self.mass, self.gravity, self.bounce) = (60.0, 0.03, 0.001, 0.0, 1.0);
+                self.do_air_resistance = false;
+                self.start_colour = Color::new(0.4, 0.8, 1.0, 1.0);
+                self.end_colour = Color::new(0.4, 0.8, 1.0, 0.5);
+            }
+        }
+        self.collide = true;
+    }
+
+    //How many particles there are at the moment
+    #[allow(dead_code)]
+    pub(crate) fn count(&self) -> usize {
+        self.particles.len()
+    }
+
+    //Remove every particle, e.g. when the settings have changed a lot
+    #[allow(dead_code)]
+    pub(crate) fn clear(&mut self) {
+        self.particles.clear();
+        self.spawn_timer = 0.0;
+    }
+
+    //Fire a new particle from the emitter, in a random direction inside the cone
+    fn spawn(&mut self) {
+        let angle = self.angle + random_range(-0.5, 0.5) * self.angle_spread;
+        let speed = (self.speed + random_range(-1.0, 1.0) * self.speed_spread).max(0.0);
+        self.particles.push(Particle { pos: self.pos, velocity: Vec2::from_angle(angle.to_radians()) * speed, age: 0.0 });
+    }
+
+    //Describe a particle the same way as an object, so the other generators can push it
+    fn get_body(&self, particle: &Particle) -> Body {
+        Body {
+            handle: PARTICLE_HANDLE,
+            centre: particle.pos,
+            velocity: particle.velocity,
+            mass: self.mass,
+            area: PI * self.size * self.size,
+            gravity: self.gravity,
+            drag_coefficient: PARTICLE_DRAG_COEFFICIENT,
+            do_air_resistance: self.do_air_resistance,
+            half_size: Vec2::splat(self.size),
+            circle: true,
+            charge: 0.0,
+            dynamic: true,
+        }
+    }
+}
+
+//Find how far a particle has gone into an object, and the direction out of it
+pub(crate) fn get_contact(body: &Body, point: Vec2, radius: f32) -> Option<(Vec2, f32)> {
+    if body.circle {
+        let offset = point - body.centre;
+        let depth = body.half_size.x + radius - offset.length();
+        let normal = if offset == Vec2::ZERO { Vec2::new(0.0, -1.0) } else { offset.normalize() };
+        return (depth > 0.0).then_some((normal, depth));
+    }
+    let corner1 = body.centre - body.half_size;
+    let corner2 = body.centre + body.half_size;
+    let closest = point.clamp(corner1, corner2);
+    if closest != point {
+        let offset = point - closest;
+        return (offset.length() < radius).then(|| (offset.normalize(), radius - offset.length()));
+    }
+    //The particle's centre is inside the rectangle, so push it out of the nearest side
+    let sides = [
+        (point.x - corner1.x, Vec2::new(-1.0, 0.0)),
+        (corner2.x - point.x, Vec2::new(1.0, 0.0)),
+        (point.y - corner1.y, Vec2::new(0.0, -1.0)),
+        (corner2.y - point.y, Vec2::new(0.0, 1.0)),
+    ];
+    let (distance, normal) = sides.into_iter().fold((f32::MAX, Vec2::ZERO), |nearest, side| if side.0 < nearest.0 { side } else { nearest });
+    Some((normal, distance + radius))
+}
+
+impl ParticleEmitter {
+    //Push every particle out of any object it has gone into, and bounce it off the object's surface.
+    //The particles are put in a QuadTree, so each object only checks the particles near it instead of every particle
+    fn collide_particles(&mut self, bodies: &[Body]) {
+        let corner1 = self.particles.iter().fold(Vec2::splat(f32::MAX), |corner, particle| corner.min(particle.pos));
+        let corner2 = self.particles.iter().fold(Vec2::splat(f32::MIN), |corner, particle| corner.max(particle.pos));
+        let centre = (corner1 + corner2) / 2.;
+        let half_size = (corner2 - corner1) / 2. + Vec2::splat(self.size);
+        let mut qtree = QuadTree::new_unique(Rect::new(centre.x, centre.y, half_size.x, half_size.y), 8);
+        for (index, particle) in self.particles.iter().enumerate() {
+            qtree.insert(Point::new(particle.pos.x, particle.pos.y, index));
+        }
+        for body in bodies {
+            //Any particle touching the object is within its size plus the particle's own size. They are sorted so the
+            //particles are always pushed in the same order
+            let reach = body.half_size + Vec2::splat(self.size);
+            let mut nearby: Vec<usize> = qtree.query(&Rect::new(body.centre.x, body.centre.y, reach.x, reach.y))
+                .iter().map(|point| point.index).collect();
+            nearby.sort_unstable();
+            for index in nearby {
+                let particle = &mut self.particles[index];
+                if let Some((normal, depth)) = get_contact(body, particle.pos, self.size) {
+                    particle.pos += normal * depth;
+                    let closing_speed = (particle.velocity - body.velocity).dot(normal);
+                    if closing_speed < 0.0 {
+                        particle.velocity -= normal * closing_speed * (1.0 + self.bounce);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ForceGenerator for ParticleEmitter {
+    //Emitters don't push objects, only their own particles
+    fn get_force(&self, _body: &Body) -> Vec2 {
+        Vec2::ZERO
+    }
+
+    fn update(&mut self, others: &dyn Fn(&Body) -> Vec2, bodies: &[Body]) {
+        //Remove particles that have run out of time, then make the new ones for this step
+        let lifetime = self.lifetime;
+        self.particles.retain(|particle| particle.age < lifetime);
+        self.spawn_timer += self.rate.max(0.0) * dt();
+        while self.spawn_timer >= 1.0 {
+            self.spawn_timer -= 1.0;
+            if self.particles.len() < MAX_PARTICLES {
+                self.spawn();
+            }
+        }
+
+        //Accelerate and move every particle the same way as an object, a = F / m
+        let forces: Vec<Vec2> = self.particles.iter().map(|particle| others(&self.get_body(particle))).collect();
+        for (particle, force) in self.particles.iter_mut().zip(forces) {
+            if self.mass > 0.0 {
+                particle.velocity += force / self.mass * dt();
+            }
+            particle.pos += particle.velocity * dt();
+            particle.age += dt();
+        }
+        if self.collide && !self.particles.is_empty() {
+            self.collide_particles(bodies);
+        }
+    }
+
+    //Draw every particle fading from the start colour to the end colour, and the emitter with its direction
+    fn draw(&self, _bodies: &[Body]) {
+        for particle in &self.particles {
+            let life = (particle.age / self.lifetime).clamp(0.0, 1.0);
+            let start = self.start_colour.to_vec();
+            let colour = Color::from_vec(start + (self.end_colour.to_vec() - start) * life);
+            draw_circle(meter(particle.pos.x), meter(particle.pos.y), meter(self.size).max(1.0), colour);
+        }
+        let colour = Color::from_rgba(255, 200, 120, 255);
+        draw_circle_lines(meter(self.pos.x), meter(self.pos.y), 6., 2., colour);
+        if self.angle_spread < 360.0 {
+            draw_arrow(self.pos, self.pos + Vec2::from_angle(self.angle.to_radians()) * 0.3, colour);
+        }
+    }
+
+    fn hash_state(&self, add: &mut dyn FnMut(u32)) {
+        add(self.spawn_timer.to_bits());
+        for particle in &self.particles {
+            for value in [particle.pos.x, particle.pos.y, particle.velocity.x, particle.velocity.y, particle.age] {
+                add(value.to_bits());
+            }
+        }
+    }
+
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        let colour = |colour: Color| format!("{},{},{},{}", colour.r, colour.g, colour.b, colour.a);
+        Some(format!("emitter x={} y={} rate={} speed={} speed_spread={} angle={} angle_spread={} lifetime={} size={} mass={} \
+                      gravity={} air={} collide={} bounce={} start_colour={} end_colour={}",
+                     self.pos.x, self.pos.y, self.rate, self.speed, self.speed_spread, self.angle, self.angle_spread, self.lifetime,
+                     self.size, self.mass, self.gravity, self.do_air_resistance, self.collide, self.bounce,
+                     colour(self.start_colour), colour(self.end_colour)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::events::EventLog;
+    use crate::objects::forces::find_forces;
+    use crate::objects::scene::Scene;
+    use super::ParticleEmitter;
+
+    #[test]
+    fn particles_live_for_the_lifetime() {
+        //60 particles a second that last half a second gives 30 at a time
+        let mut scene = Scene::load_test("emitter_count", "emitter x=0 y=0 rate=60 speed=1 lifetime=0.5 gravity=0 air=false collide=false\n");
+        scene.run(&mut EventLog::new(), 120);
+        let count = find_forces::<ParticleEmitter>(&mut scene.forces)[0].count();
+        assert!((29..=31).contains(&count), "there were {} particles", count);
+    }
+
+    #[test]
+    fn particles_bounce_off_objects() {
+        //Fire particles straight down at a floor, which they can't go through
+        let mut scene = Scene::load_test("emitter_floor", "emitter x=0 y=0 rate=30 speed=5 speed_spread=0 angle=90 angle_spread=0 \
+            lifetime=2 size=0.05 mass=0.01 gravity=9.81 air=false collide=true bounce=0.5\n\
+            rectangle x=-2 y=1 width=4 height=1 type=static\n");
+        scene.run(&mut EventLog::new(), 120);
+        let emitter = &find_forces::<ParticleEmitter>(&mut scene.forces)[0];
+        assert!(emitter.count() > 0);
+        for particle in &emitter.particles {
+            assert!(particle.pos.y <= 1.0 - emitter.size + 1e-3, "a particle went into the floor at {}", particle.pos.y);
+        }
+    }
+}
@@ -1,10 +1,17 @@
 use std::ops::Add;
-use macroquad::camera::Camera2D;
-use macroquad::color::{GREEN, RED};
-use crate::measurements::{dt, meter, QuadTree};
+use macroquad::color::GREEN;
+use crate::measurements::dt;
 use crate::objects::{Object, Render};
+use crate::objects::events::EventLog;
+use crate::objects::forces::{total_force, Body, ForceGenerator};
+use crate::objects::fracture::can_fracture;
 use macroquad::math::{Vec2, Rect};
-use macroquad::shapes::draw_circle_lines;
+
+//Speed (m/s) an object must stay under, and for how long (s), before it is put to sleep
+pub(crate) const SLEEP_VELOCITY: f32 = 0.05;
+pub(crate) const SLEEP_TIME: f32 = 1.0;
+//The coefficient of friction given to a conveyor belt that has none, as a frictionless belt can't drag anything along
+pub(crate) const BELT_FRICTION: f32 = 0.5;
 
 //Create the PhysicsType enum
 #[derive(Clone, Copy)]
@@ -25,9 +32,7 @@ pub struct Material {
 //Create the trait PhysicObject to be given to all physics objects (Objects)
 #[allow(unused)]
 pub(crate) trait PhysicsObject {
-    fn physics_process(&mut self, camera: &Camera2D);
-    fn get_drag(&self) -> Vec2;
-    fn get_terminal_velocity(&self) -> f32;
+    fn physics_process(&mut self, forces: &[Box<dyn ForceGenerator>]);
     fn get_physics_type(&mut self) -> &mut PhysicsType;
     fn set_physics_type(&mut self, new_type: PhysicsType);
     fn get_render_shape(&mut self) -> Box<dyn Render>;
@@ -39,8 +44,21 @@ pub(crate) trait PhysicsObject {
     fn get_velocity(&self) -> Vec2;
     fn set_velocity(&mut self, velocity: Vec2);
     fn set_do_air_resistance(&mut self) -> &mut bool;
-    fn detect_near_object(&mut self, qtree: &mut QuadTree, objects: Vec<&mut Box<dyn PhysicsObject>>);
-    fn check_collisions (&mut self, object: &mut Box<dyn PhysicsObject>);
+    fn check_collisions (&mut self, object: &mut Box<dyn PhysicsObject>, events: &mut EventLog);
+    fn get_sleeping(&self) -> bool;
+    fn get_sleep_state(&self) -> (bool, f32, Vec2);
+    fn wake(&mut self);
+    fn sleep(&mut self);
+    fn get_collision_category(&mut self) -> &mut u32;
+    fn get_collision_mask(&mut self) -> &mut u32;
+    fn get_sensor(&mut self) -> &mut bool;
+    fn get_charge(&mut self) -> &mut f32;
+    fn get_break_impulse(&mut self) -> &mut f32;
+    fn get_broken(&mut self) -> &mut bool;
+    fn get_friction(&mut self) -> &mut f32;
+    fn get_restitution(&mut self) -> &mut f32;
+    fn get_surface_velocity(&mut self) -> &mut f32;
+    fn get_handle(&self) -> usize;
 }
 
 //Give default functions to material
@@ -57,55 +75,39 @@ impl Material {
 //Implement PhysicsObject to the object struct
 impl<T: Render + Clone + 'static> PhysicsObject for Object<T> {
     //Perform the physics process for different objects
-    fn physics_process(&mut self, _camera: &Camera2D) {
+    fn physics_process(&mut self, forces: &[Box<dyn ForceGenerator>]) {
         match self.get_physics_type() {
-            PhysicsType::Static => {}
+            PhysicsType::Static => {
+                //Remember where the object is, so anything resting on it can be woken up if it is moved
+                self.last_pos = *self.shape.get_pos();
+            }
             PhysicsType::Dynamic => {
-                //Process the drag and gravity for all directions of movement
-                self.dy += self.gravity * dt();
-                if self.dx > 0.0 {
-                    self.dx -= self.get_drag().x * dt()
-                } else if self.dx < 0.0 {
-                    self.dx += self.get_drag().x * dt()
-                }
-                if self.dy > 0.0 {
-                    self.dy -= self.get_drag().y * dt()
-                } else if self.dy < 0.0 {
-                    self.dy += self.get_drag().x * dt()
-                }
+                //Sleeping objects do not move until something wakes them up
+                if self.sleeping { return; }
+                self.sleep_process();
+                //Add up every force on the object (gravity, drag, springs...), and accelerate it by a = F / m
+                let body = Body::new(self);
+                let acceleration = total_force(forces, &body) / self.material.mass;
+                self.dx += acceleration.x * dt();
+                self.dy += acceleration.y * dt();
                 //Move the object in the correct direction
                 self.movement_process();
             }
             PhysicsType::Kinematic => {
                 //Move the object in the correct direction
+                self.last_pos = *self.shape.get_pos();
                 self.movement_process();
             }
         };
     }
 
-    //Calculate the drag in the x and y direction for any object
-    fn get_drag(&self) -> Vec2 {
-        //Solve for drag using F = 1/2 p v^2 A C_d
-        let drag_x = 0.5 * 1.29 * (self.dx * self.dx) * self.shape.get_area() * 1.05;
-        let drag_y = 0.5 * 1.29 * (self.dy * self.dy) * self.shape.get_area() * 1.05;
-
-        //Resolve for the force applied
-        let dc_x = drag_x / self.material.mass;
-        let dc_y = drag_y / self.material.mass;
-        //Return the value of the drag
-        Vec2::new(dc_x, dc_y)
-    }
-    //Find the terminal velocity of the object (TO BE USED)
-    #[allow(dead_code)]
-    fn get_terminal_velocity(&self) -> f32 {
-        f32::sqrt((2.0 * self.material.mass * 1.0) * (1.29 * self.shape.get_area() * 1.05))
-    }
     //Setters and getters for the physics type
     fn get_physics_type(&mut self) -> &mut PhysicsType {
         &mut self.phys_type
     }
     fn set_physics_type(&mut self, new_type: PhysicsType) {
         self.phys_type = new_type;
+        self.wake();
     }
     //Getters for the render shape and material
     fn get_render_shape(&mut self) -> Box<dyn Render> {
@@ -127,25 +129,46 @@ impl<T: Render + Clone + 'static> PhysicsObject for Object<T> {
     fn set_velocity(&mut self, velocity: Vec2) {
         self.dx = velocity.x;
         self.dy = velocity.y;
+        self.wake();
     }
     fn set_do_air_resistance(&mut self) -> &mut bool { &mut self.do_air_resistance }
 
-    fn detect_near_object(&mut self, mut qtree: &mut QuadTree, mut objects: Vec<&mut Box<dyn PhysicsObject>>) {
-        let points = self.get_render_shape_reference().detect_near_object(&mut qtree);
-        for i in points {
-
-            if objects.get_mut(i.index).is_some() {
-                self.check_collisions(objects.get_mut(i.index).unwrap());
-            };
+    fn check_collisions(&mut self, object: &mut Box<dyn PhysicsObject>, events: &mut EventLog) {
+        //Skip the pair if neither object can move (sleeping or static)
+        let self_resting = self.sleeping || matches!(self.phys_type, PhysicsType::Static);
+        let object_resting = object.get_sleeping() || matches!(object.get_physics_type(), PhysicsType::Static);
+        //Conveyor belts keep dragging anything resting on them, so they are always checked
+        let moving_surface = self.surface_velocity != 0.0 || *object.get_surface_velocity() != 0.0;
+        if self_resting && object_resting && !moving_surface {
+            events.keep_contact(self.handle, object.get_handle());
+            return;
         }
-    }
+        //Skip the pair if either object's mask does not include the other object's category
+        if self.collision_category & *object.get_collision_mask() == 0 || *object.get_collision_category() & self.collision_mask == 0 {
+            return;
+        }
+        //Sensors detect overlaps, but never push objects apart
+        let sensor = self.sensor || *object.get_sensor();
 
-    fn check_collisions(&mut self, object: &mut Box<dyn PhysicsObject>) {
         if object.get_render_shape_reference().get_id() == "Circle" && object.get_render_shape_reference().get_id() == self.get_render_shape_reference().get_id() {
             if self.get_render_shape_reference().get_pos().distance(*object.get_render_shape_reference().get_pos()) <=
                 self.get_render_shape_reference().get_measurements().0 + object.get_render_shape_reference().get_measurements().0 {
 
-                //Objects are colliding
+                self.get_render_shape_reference().set_colour(GREEN);
+                object.get_render_shape_reference().set_colour(GREEN);
+                let normal = *object.get_render_shape_reference().get_pos() - *self.shape.get_pos();
+                let point = *self.shape.get_pos() + normal.normalize_or_zero() * self.shape.get_measurements().0;
+                if sensor {
+                    record_contact(self, object, events, true, 0.0, point);
+                    return;
+                }
+
+                //Objects are colliding, so wake them both up
+                self.wake();
+                object.wake();
+                let impulse = stop_closing_velocity(self, object, normal);
+                record_contact(self, object, events, false, impulse, point);
+                apply_friction(self, object, normal, impulse);
                 resolve_overlap_circles(self, object);
             }
             return;
@@ -167,25 +190,51 @@ impl<T: Render + Clone + 'static> PhysicsObject for Object<T> {
             if overlap {
                 self.get_render_shape_reference().set_colour(GREEN);
                 object.get_render_shape_reference().set_colour(GREEN);
+                //The contact is in the middle of the overlapping area, and pushes along the shortest side of that area
+                let area = r1.intersect(r2).or(r2.intersect(r1)).unwrap();
+                let normal = if area.w < area.h { Vec2::new(1., 0.) } else { Vec2::new(0., 1.) };
+                //Point the normal from this object towards the other one
+                let normal = if (r2.center() - r1.center()).dot(normal) < 0.0 { -normal } else { normal };
+                if sensor {
+                    record_contact(self, object, events, true, 0.0, area.center());
+                    return;
+                }
+                self.wake();
+                object.wake();
 
+                let impulse = stop_closing_velocity(self, object, normal);
+                record_contact(self, object, events, false, impulse, area.center());
+                apply_friction(self, object, normal, impulse);
                 resolve_overlap_rect(self, object);
             }
         }
 
 
         if self.get_render_shape_reference().get_id() == "Circle" && object.get_render_shape_reference().get_id() == "Rectangle" {
-            let closest_point = get_closest_point(self.get_render_shape().get_pos().clone(),
+            let (closest_point, inside) = get_surface_point(self.get_render_shape().get_pos().clone(),
                                                   object.get_render_shape().get_pos().clone(),
                                                   Vec2::new(object.get_render_shape().get_pos().x + object.get_render_shape().get_measurements().0,
                                                             object.get_render_shape().get_pos().y + object.get_render_shape().get_measurements().1));
 
             let target = self.get_render_shape().get_pos().clone();
-            let overlap = target.distance(closest_point) < self.get_render_shape_reference().get_measurements().0;
+            let overlap = inside || target.distance(closest_point) < self.get_render_shape_reference().get_measurements().0;
 
             if overlap {
                 self.get_render_shape_reference().set_colour(GREEN);
                 object.get_render_shape_reference().set_colour(GREEN);
+                //The normal points out of the rectangle towards the circle
+                let normal = if inside { closest_point - target } else { target - closest_point };
+                if sensor {
+                    record_contact(self, object, events, true, 0.0, closest_point);
+                    return;
+                }
+                self.wake();
+                object.wake();
 
+                //The circle is object 1 here, so the normal from it to the rectangle is the other way
+                let impulse = stop_closing_velocity(self, object, -normal);
+                record_contact(self, object, events, false, impulse, closest_point);
+                apply_friction(self, object, -normal, impulse);
                 let mut circle_type = self.get_physics_type().clone();
                 let mut rect_type = object.get_physics_type().clone();
 
@@ -196,17 +245,28 @@ impl<T: Render + Clone + 'static> PhysicsObject for Object<T> {
         if self.get_render_shape_reference().get_id() == "Rectangle" && object.get_render_shape_reference().get_id() == "Circle" {
 
             //Check Vertices
-            let closest_point = get_closest_point(object.get_render_shape().get_pos().clone(),
+            let (closest_point, inside) = get_surface_point(object.get_render_shape().get_pos().clone(),
                                                   self.get_render_shape().get_pos().clone(),
                                                   Vec2::new(self.get_render_shape().get_pos().x + self.get_render_shape().get_measurements().0,
                                                             self.get_render_shape().get_pos().y + self.get_render_shape().get_measurements().1));
 
             let target = object.get_render_shape().get_pos().clone();
-            let overlap = target.distance(closest_point) < object.get_render_shape_reference().get_measurements().0;
+            let overlap = inside || target.distance(closest_point) < object.get_render_shape_reference().get_measurements().0;
             if overlap {
                 self.get_render_shape_reference().set_colour(GREEN);
                 object.get_render_shape_reference().set_colour(GREEN);
+                //The normal points out of the rectangle towards the circle
+                let normal = if inside { closest_point - target } else { target - closest_point };
+                if sensor {
+                    record_contact(self, object, events, true, 0.0, closest_point);
+                    return;
+                }
+                self.wake();
+                object.wake();
 
+                let impulse = stop_closing_velocity(self, object, normal);
+                record_contact(self, object, events, false, impulse, closest_point);
+                apply_friction(self, object, normal, impulse);
                 let mut circle_type = object.get_physics_type().clone();
                 let mut rect_type = self.get_physics_type().clone();
 
@@ -214,11 +274,143 @@ impl<T: Render + Clone + 'static> PhysicsObject for Object<T> {
             }
         }
     }
+
+    //Getter for whether the object is asleep, and wake the object (resetting its sleep timer)
+    fn get_sleeping(&self) -> bool { self.sleeping }
+    fn wake(&mut self) {
+        if self.sleeping {
+            self.sleeping = false;
+            self.sleep_timer = 0.0;
+            self.last_pos = *self.shape.get_pos();
+        }
+    }
+    //Put the object to sleep, stopping it where it is
+    fn sleep(&mut self) {
+        self.sleeping = true;
+        self.dx = 0.0;
+        self.dy = 0.0;
+    }
+    //Whether the object is asleep, how long (s) it has been still and where it was at the end of the last step, for saving
+    fn get_sleep_state(&self) -> (bool, f32, Vec2) { (self.sleeping, self.sleep_timer, self.last_pos) }
+    //Getters for the collision category, mask and whether the object is a sensor
+    fn get_collision_category(&mut self) -> &mut u32 { &mut self.collision_category }
+    fn get_collision_mask(&mut self) -> &mut u32 { &mut self.collision_mask }
+    fn get_sensor(&mut self) -> &mut bool { &mut self.sensor }
+    //Getter for the electric charge of the object
+    fn get_charge(&mut self) -> &mut f32 { &mut self.charge }
+    //Getters for how hard the object must be hit to break, and whether it has been
+    fn get_break_impulse(&mut self) -> &mut f32 { &mut self.break_impulse }
+    fn get_broken(&mut self) -> &mut bool { &mut self.broken }
+    //Getters for the coefficients of friction and restitution, and how fast the surface moves like a conveyor belt
+    fn get_friction(&mut self) -> &mut f32 { &mut self.friction }
+    fn get_restitution(&mut self) -> &mut f32 { &mut self.restitution }
+    fn get_surface_velocity(&mut self) -> &mut f32 { &mut self.surface_velocity }
+    //Getter for the handle used to tell objects apart in the event log
+    fn get_handle(&self) -> usize { self.handle }
 }
 
-fn get_closest_point(target: Vec2, corner1: Vec2, corner2: Vec2) -> Vec2 {
-    let mut closest_point = corner1;
+//Change a dynamic object's velocity by an impulse (Ns), using v = u + J / m. Static and kinematic objects can't be pushed
+pub(crate) fn apply_impulse(object: &mut dyn PhysicsObject, impulse: Vec2) {
+    if !matches!(object.get_physics_type(), PhysicsType::Dynamic) {
+        return;
+    }
+    let mass = object.get_material().mass;
+    let velocity = object.get_velocity() + impulse / mass;
+    object.set_velocity(velocity);
+}
 
+//Make an object's surface move like a conveyor belt, giving it the default belt friction if it has none
+pub(crate) fn set_belt_speed(object: &mut dyn PhysicsObject, speed: f32) {
+    *object.get_surface_velocity() = speed;
+    if speed != 0.0 && *object.get_friction() <= 0.0 {
+        *object.get_friction() = BELT_FRICTION;
+    }
+}
+
+//Tell the event log that two objects are touching, with the impulse (Ns) the collision gave them
+fn record_contact(object_1: &mut dyn PhysicsObject, object_2: &mut Box<dyn PhysicsObject>, events: &mut EventLog, sensor: bool, impulse: f32, point: Vec2) {
+    events.contact(object_1.get_handle(), object_2.get_handle(), sensor, impulse, point);
+    if sensor {
+        return;
+    }
+    //Break either object if it was hit harder than it can take
+    let (handle_1, handle_2) = (object_1.get_handle(), object_2.get_handle());
+    if check_break(object_1, impulse) {
+        events.record_break(handle_1, handle_2, impulse, point);
+    }
+    if check_break(object_2.as_mut(), impulse) {
+        events.record_break(handle_2, handle_1, impulse, point);
+    }
+}
+
+//Mark an object to be broken if the impulse is more than it can take, and it is big enough to split into pieces
+fn check_break(object: &mut dyn PhysicsObject, impulse: f32) -> bool {
+    let threshold = *object.get_break_impulse();
+    if threshold <= 0.0 || impulse <= threshold || *object.get_broken() || !can_fracture(object) {
+        return false;
+    }
+    *object.get_broken() = true;
+    true
+}
+
+//Check if an object can't be pushed by collisions
+fn is_fixed(phys_type: &PhysicsType) -> bool {
+    matches!(phys_type, PhysicsType::Static | PhysicsType::Kinematic)
+}
+
+//Take away the speed two touching objects are moving towards each other along the normal (from object 1 to object 2),
+//so small or fast objects stop at a surface instead of sinking through it. The higher coefficient of restitution (e) of the
+//two sends back e times the closing speed as a bounce, so the default of 0 stops objects dead along the normal.
+//Momentum is kept, and objects that can't move are treated as having infinite mass. Returns the impulse (Ns) given
+fn stop_closing_velocity(object_1: &mut dyn PhysicsObject, object_2: &mut Box<dyn PhysicsObject>, normal: Vec2) -> f32 {
+    let normal = normal.normalize_or_zero();
+    let (velocity_1, velocity_2) = (object_1.get_velocity(), object_2.get_velocity());
+    let closing_speed = (velocity_1 - velocity_2).dot(normal);
+    if closing_speed <= 0.0 {
+        return 0.0;
+    }
+    let inverse_mass = |object: &mut dyn PhysicsObject| {
+        if matches!(object.get_physics_type(), PhysicsType::Dynamic) { 1. / object.get_material().mass } else { 0.0 }
+    };
+    let (inverse_1, inverse_2) = (inverse_mass(object_1), inverse_mass(object_2.as_mut()));
+    if inverse_1 + inverse_2 == 0.0 {
+        return 0.0;
+    }
+    let restitution = object_1.get_restitution().max(*object_2.get_restitution());
+    let impulse = (1. + restitution) * closing_speed / (inverse_1 + inverse_2);
+    object_1.set_velocity(velocity_1 - normal * impulse * inverse_1);
+    object_2.set_velocity(velocity_2 + normal * impulse * inverse_2);
+    impulse
+}
+
+//Slow down two touching objects sliding past each other, by at most the coefficient of friction times the impulse pushing
+//them apart (F <= u R). A conveyor belt's surface is moving, so objects on it are dragged towards the belt's speed instead
+fn apply_friction(object_1: &mut dyn PhysicsObject, object_2: &mut Box<dyn PhysicsObject>, normal: Vec2, normal_impulse: f32) {
+    let friction = object_1.get_friction().max(*object_2.get_friction());
+    if friction <= 0.0 || normal_impulse <= 0.0 {
+        return;
+    }
+    let normal = normal.normalize_or_zero();
+    //Going clockwise around object 1 at the contact. Object 2's surface faces the other way, so its clockwise is backwards
+    let tangent = Vec2::new(-normal.y, normal.x);
+    let (velocity_1, velocity_2) = (object_1.get_velocity(), object_2.get_velocity());
+    let surface_1 = velocity_1 + tangent * *object_1.get_surface_velocity();
+    let surface_2 = velocity_2 - tangent * *object_2.get_surface_velocity();
+    let sliding_speed = (surface_1 - surface_2).dot(tangent);
+    let inverse_mass = |object: &mut dyn PhysicsObject| {
+        if matches!(object.get_physics_type(), PhysicsType::Dynamic) { 1. / object.get_material().mass } else { 0.0 }
+    };
+    let (inverse_1, inverse_2) = (inverse_mass(object_1), inverse_mass(object_2.as_mut()));
+    if inverse_1 + inverse_2 == 0.0 || sliding_speed == 0.0 {
+        return;
+    }
+    //Stop the sliding completely if friction is strong enough, otherwise take away as much as it can
+    let impulse = (sliding_speed / (inverse_1 + inverse_2)).clamp(-friction * normal_impulse, friction * normal_impulse);
+    object_1.set_velocity(velocity_1 - tangent * impulse * inverse_1);
+    object_2.set_velocity(velocity_2 + tangent * impulse * inverse_2);
+}
+
+fn get_closest_point(target: Vec2, corner1: Vec2, corner2: Vec2) -> Vec2 {
     let min_x = corner1.x.min(corner2.x);
     let max_x = corner1.x.max(corner2.x);
     let min_y = corner1.y.min(corner2.y);
@@ -227,11 +419,27 @@ fn get_closest_point(target: Vec2, corner1: Vec2, corner2: Vec2) -> Vec2 {
     let true_x = target.x.clamp(min_x, max_x);
     let true_y = target.y.clamp(min_y, max_y);
 
-    draw_circle_lines(meter(true_x), meter(true_y), 1.,1., RED);
-
     Vec2::new(true_x, true_y)
 }
 
+//Find the closest point on the outside of a rectangle, and whether the target is inside it.
+//A target inside the rectangle is closest to its nearest side, so it can be pushed back out that way
+fn get_surface_point(target: Vec2, corner1: Vec2, corner2: Vec2) -> (Vec2, bool) {
+    let closest_point = get_closest_point(target, corner1, corner2);
+    if closest_point != target {
+        return (closest_point, false);
+    }
+    let (min, max) = (corner1.min(corner2), corner1.max(corner2));
+    let sides = [
+        (target.x - min.x, Vec2::new(min.x, target.y)),
+        (max.x - target.x, Vec2::new(max.x, target.y)),
+        (target.y - min.y, Vec2::new(target.x, min.y)),
+        (max.y - target.y, Vec2::new(target.x, max.y)),
+    ];
+    let (_, side_point) = sides.into_iter().fold((f32::MAX, target), |nearest, side| if side.0 < nearest.0 { side } else { nearest });
+    (side_point, true)
+}
+
 fn resolve_overlap_circles(object_1: &mut dyn PhysicsObject, object_2: &mut Box<dyn PhysicsObject>) {
     let distance = object_1.get_render_shape().get_pos().distance(object_2.get_render_shape().get_pos().clone());
     let dx = object_2.get_render_shape().get_pos().x - object_1.get_render_shape().get_pos().x;
@@ -245,14 +453,15 @@ fn resolve_overlap_circles(object_1: &mut dyn PhysicsObject, object_2: &mut Box<
     let mut move_it_1 = Vec2::new(nx * (overlap/2.), ny * (overlap/2.));
     let mut move_it_2 = move_it_1.clone();
 
-    if matches!(object_1.get_physics_type(), PhysicsType::Static) {
+    //Static and kinematic objects are never pushed back, so a moving platform carries on along its path
+    if is_fixed(object_1.get_physics_type()) {
         move_it_1.x = 0.;
         move_it_1.y = 0.;
 
         move_it_2.x = move_it_2.x * 2.;
         move_it_2.y = move_it_2.y * 2.;
     }
-    if matches!(object_2.get_physics_type(), PhysicsType::Static) {
+    if is_fixed(object_2.get_physics_type()) {
         move_it_2.x = 0.;
         move_it_2.y = 0.;
 
@@ -280,11 +489,11 @@ fn resolve_overlap_rect(object_1: &mut dyn PhysicsObject, object_2: &mut Box<dyn
     let mut object1_overlap = Vec2::new(overlap_x/2., overlap_y/2.);
     let mut object2_overlap = Vec2::new(overlap_x/2., overlap_y/2.);
 
-    if matches!(object_1.get_physics_type(), PhysicsType::Static) {
+    if is_fixed(object_1.get_physics_type()) {
         object1_overlap = Vec2::new(0., 0.);
         object2_overlap = Vec2::new(object2_overlap.x *2. , object2_overlap.y *2.);
     }
-    if matches!(object_2.get_physics_type(), PhysicsType::Static) {
+    if is_fixed(object_2.get_physics_type()) {
         object2_overlap = Vec2::new(0., 0.);
         object1_overlap = Vec2::new(object1_overlap.x *2. , object1_overlap.y *2.);
     }
@@ -319,29 +528,35 @@ fn resolve_overlap_cirect(mut cirlce: Box<&mut dyn Render>, mut rect: Box<&mut d
     let target = cirlce.get_pos().clone();
     let l1 = rect.get_pos().clone();
     let l2 = Vec2::new(rect.get_pos().x + rect.get_measurements().0, rect.get_pos().y + rect.get_measurements().1);
-    let closest_point = get_closest_point(target, l1, l2);
+    let (closest_point, inside) = get_surface_point(target, l1, l2);
 
     let dist_x = cirlce.get_pos().x - closest_point.x;
     let dist_y = cirlce.get_pos().y - closest_point.y;
 
     let radius = cirlce.get_measurements().0.clone();
     let dist = (dist_x*dist_x + dist_y*dist_y).sqrt();
-    let overlap = radius - dist;
+    let mut overlap = radius - dist;
 
     if dist == 0.0 { return; }
-    if dist.powf(2.) >= radius * radius { return; }
-
-    let nx = dist_x/dist.sqrt();
-    let ny = dist_y/dist;
+    if !inside && dist.powf(2.) >= radius * radius { return; }
+
+    let mut nx = dist_x/dist;
+    let mut ny = dist_y/dist;
+    //A circle whose centre has gone inside the rectangle is pushed back out of the nearest side
+    if inside {
+        nx = -dist_x / dist;
+        ny = -dist_y / dist;
+        overlap = radius + dist;
+    }
 
     let mut move_circle = Vec2::new(nx * overlap/2., ny * overlap/2.);
     let mut move_rect = Vec2::new(nx * overlap/2., ny * overlap/2.);
 
-    if matches!(circle_type, PhysicsType::Static) {
+    if is_fixed(circle_type) {
         move_circle = Vec2::new(0., 0.);
         move_rect = Vec2::new(move_rect.x * 2., move_rect.y * 2.);
     }
-    if matches!(rect_type, PhysicsType::Static) {
+    if is_fixed(rect_type) {
         move_rect = Vec2::new(0., 0.);
         move_circle = Vec2::new(move_circle.x * 2., move_circle.y * 2.);
     }
@@ -0,0 +1,560 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs;
+use std::str::FromStr;
+use macroquad::color::{Color, BLACK, WHITE};
+use macroquad::math::Vec2;
+use crate::measurements::get_random_state;
+use crate::objects::actuators::{Actuator, ActuatorMode, Repeat};
+use crate::objects::forces::{default_forces, Body, ForceGenerator, RadialAttractor, Spring, Wind, AIR_DENSITY};
+use crate::objects::magnetism::MagneticField;
+use crate::objects::gravitation::{set_nbody, NBodyGravity};
+use crate::objects::electrostatics::ElectricField;
+use crate::objects::cloth::{Cloth, ClothKind};
+use crate::objects::fluids::FluidRegion;
+use crate::objects::gas::GasBox;
+use crate::objects::joints::{add_joint, Joint, JointSystem};
+use crate::objects::particles::{EmitterPreset, ParticleEmitter};
+use crate::objects::physics::{Material, PhysicsObject, PhysicsType, BELT_FRICTION};
+use crate::objects::shapes::{Circle, Rectangle};
+use crate::objects::soft_bodies::{SoftBody, SoftSpring};
+use crate::objects::sph::SphFluid;
+use crate::objects::world::SimulationSettings;
+use crate::objects::zones::{ForceZone, ZoneKind};
+use crate::objects::{Object, Render};
+
+//A scene loaded from a file: the settings for the simulation, every object in it, and the forces acting on them
+pub(crate) struct Scene {
+    pub(crate) settings: SimulationSettings,
+    pub(crate) objects: Vec<Box<dyn PhysicsObject>>,
+    pub(crate) forces: Vec<Box<dyn ForceGenerator>>,
+}
+
+impl Scene {
+    //An empty scene, with only the forces every scene has
+    pub(crate) fn new() -> Scene {
+        Scene { settings: SimulationSettings::new(), objects: Vec::new(), forces: default_forces() }
+    }
+
+    //Write a scene to a temporary file and load it, using its timestep, for tests
+    #[cfg(test)]
+    pub(crate) fn load_test(name: &str, text: &str) -> Scene {
+        let path = std::env::temp_dir().join(format!("rv_test_{}_{}.txt", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let scene = load_scene(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        crate::measurements::set_fixed_dt(Some(scene.settings.timestep));
+        scene
+    }
+
+    //Step the scene forward a number of times, for tests
+    #[cfg(test)]
+    pub(crate) fn run(&mut self, events: &mut crate::objects::events::EventLog, steps: usize) {
+        for _ in 0..steps {
+            crate::objects::world::step(&mut self.objects, &mut self.forces, events);
+        }
+    }
+}
+
+//Get the name used for a physics type in a scene file
+fn physics_type_name(phys_type: &PhysicsType) -> &str {
+    match phys_type {
+        PhysicsType::Static => "static",
+        PhysicsType::Dynamic => "dynamic",
+        PhysicsType::Kinematic => "kinematic",
+    }
+}
+
+//Turn an object into a single line of a scene file.
+//Numbers are written with Rust's shortest exact formatting, so loading them back gives exactly the same value
+fn object_to_line(object: &mut Box<dyn PhysicsObject>) -> String {
+    let (id, pos, measurements, colour) = {
+        let shape = object.get_render_shape_reference();
+        (shape.get_id().to_lowercase(), *shape.get_pos(), shape.get_measurements(), shape.get_colour())
+    };
+    let size = match id.as_str() {
+        "circle" => format!("radius={}", measurements.0),
+        "square" => format!("size={}", measurements.0),
+        _ => format!("width={} height={}", measurements.0, measurements.1),
+    };
+    let velocity = object.get_velocity();
+    let mass = object.get_material().mass;
+    let phys_type = physics_type_name(object.get_physics_type()).to_string();
+    let gravity = *object.get_gravity();
+    let air = *object.set_do_air_resistance();
+    let layers = *object.get_collision_category();
+    let mask = *object.get_collision_mask();
+    let sensor = *object.get_sensor();
+    let charge = *object.get_charge();
+    let break_impulse = *object.get_break_impulse();
+    let friction = *object.get_friction();
+    let restitution = *object.get_restitution();
+    let belt = *object.get_surface_velocity();
+    let (asleep, still, last) = object.get_sleep_state();
+
+    format!("{} x={} y={} {} mass={} type={} vx={} vy={} gravity={} air={} layers={} mask={} sensor={} charge={} break={} friction={} bounce={} belt={} asleep={} still={} lastx={} lasty={} colour={},{},{},{}",
+            id, pos.x, pos.y, size, mass, phys_type, velocity.x, velocity.y, gravity, air, layers, mask, sensor, charge, break_impulse, friction, restitution, belt,
+            asleep, still, last.x, last.y,
+            colour.r, colour.g, colour.b, colour.a)
+}
+
+//Turn a joint into a single line of a scene file. Snapped joints are not saved, as they no longer hold anything together
+fn joint_to_line(joint: &Joint, index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+    if joint.broken {
+        return None;
+    }
+    Some(format!("joint a={} b={} length={} break={}", index_of(joint.a)?, index_of(joint.b)?, joint.rest_length, joint.break_force))
+}
+
+//Save the settings, every object and every extra force to a scene file
+pub(crate) fn save_scene(path: &str, settings: &SimulationSettings, objects: &mut Vec<Box<dyn PhysicsObject>>,
+                         forces: &[Box<dyn ForceGenerator>]) -> Result<(), String> {
+    let mut text = String::from("# RV physics scene\n");
+    text.push_str(&format!("deterministic {}\nseed {}\ntimestep {}\nrandom {}\n", settings.deterministic, settings.seed, settings.timestep, get_random_state()));
+    for object in objects.iter_mut() {
+        text.push_str(&object_to_line(object));
+        text.push('\n');
+    }
+    //Forces are written after the objects, so any objects they are attached to already exist when loading
+    let handles: Vec<usize> = objects.iter().map(|object| object.get_handle()).collect();
+    let index_of = |handle: usize| handles.iter().position(|other| *other == handle);
+    for generator in forces {
+        if let Some(line) = generator.to_line(&index_of) {
+            text.push_str(&line);
+            text.push('\n');
+        }
+    }
+    //Every joint is written on its own line, like the objects
+    let systems = forces.iter().filter_map(|generator| (generator.as_ref() as &dyn Any).downcast_ref::<JointSystem>());
+    for joint in systems.flat_map(|system| system.joints.iter()) {
+        if let Some(line) = joint_to_line(joint, &index_of) {
+            text.push_str(&line);
+            text.push('\n');
+        }
+    }
+    fs::write(path, text).map_err(|error| format!("Could not save {}: {}", path, error))
+}
+
+//Read a value from a line of the scene file, using the default if it isn't given
+fn get_value<T: FromStr>(values: &HashMap<&str, &str>, key: &str, default: T, line_number: usize) -> Result<T, String> {
+    match values.get(key) {
+        Some(text) => text.parse::<T>().map_err(|_| format!("Line {}: '{}' is not a valid value for {}", line_number, text, key)),
+        None => Ok(default),
+    }
+}
+
+//Read a colour written as r,g,b,a from a line of the scene file
+fn get_colour(values: &HashMap<&str, &str>, key: &str, default: Color, line_number: usize) -> Result<Color, String> {
+    let Some(text) = values.get(key) else { return Ok(default); };
+    let parts: Vec<f32> = text.split(',').filter_map(|part| part.trim().parse::<f32>().ok()).collect();
+    if parts.len() != 4 {
+        return Err(format!("Line {}: {} must be written as r,g,b,a", line_number, key));
+    }
+    Ok(Color::new(parts[0], parts[1], parts[2], parts[3]))
+}
+
+//Give a newly created object all the properties written on its line of the scene file
+fn apply_values<T: Render>(mut object: Object<T>, values: &HashMap<&str, &str>, line_number: usize) -> Result<Object<T>, String> {
+    object.phys_type = match get_value(values, "type", String::from("static"), line_number)?.as_str() {
+        "static" => PhysicsType::Static,
+        "dynamic" => PhysicsType::Dynamic,
+        "kinematic" => PhysicsType::Kinematic,
+        other => return Err(format!("Line {}: unknown physics type '{}'", line_number, other)),
+    };
+    object.dx = get_value(values, "vx", 0.0, line_number)?;
+    object.dy = get_value(values, "vy", 0.0, line_number)?;
+    object.gravity = get_value(values, "gravity", 9.81, line_number)?;
+    object.do_air_resistance = get_value(values, "air", true, line_number)?;
+    object.collision_category = get_value(values, "layers", 1, line_number)?;
+    object.collision_mask = get_value(values, "mask", u32::MAX, line_number)?;
+    object.sensor = get_value(values, "sensor", false, line_number)?;
+    object.charge = get_value(values, "charge", 0.0, line_number)?;
+    object.break_impulse = get_value(values, "break", 0.0, line_number)?;
+    //Belts without a friction given get the default belt friction, so they still drag objects along
+    object.surface_velocity = get_value(values, "belt", 0.0, line_number)?;
+    let friction = if object.surface_velocity != 0.0 { BELT_FRICTION } else { 0.0 };
+    object.friction = get_value(values, "friction", friction, line_number)?;
+    object.restitution = get_value(values, "bounce", 0.0, line_number)?;
+    //Where the object was at the end of the last step and how long it has been still, so it falls asleep at the same time
+    object.sleeping = get_value(values, "asleep", false, line_number)?;
+    object.sleep_timer = get_value(values, "still", 0.0, line_number)?;
+    object.last_pos = Vec2::new(get_value(values, "lastx", object.last_pos.x, line_number)?, get_value(values, "lasty", object.last_pos.y, line_number)?);
+    Ok(object)
+}
+
+//Create an object from one line of a scene file
+fn line_to_object(kind: &str, values: &HashMap<&str, &str>, line_number: usize) -> Result<Box<dyn PhysicsObject>, String> {
+    let pos = Vec2::new(get_value(values, "x", 0.0, line_number)?, get_value(values, "y", 0.0, line_number)?);
+    let colour = get_colour(values, "colour", WHITE, line_number)?;
+
+    //Create the shape, and then the material from the mass and the shape's area
+    match kind {
+        "circle" => {
+            let shape = Circle::new(pos, get_value(values, "radius", 0.5, line_number)?, colour, BLACK);
+            let material = Material::new(get_value(values, "mass", shape.get_area(), line_number)?, shape.get_area());
+            Ok(Box::new(apply_values(Object::new(shape, material, PhysicsType::Static), values, line_number)?))
+        }
+        "rectangle" => {
+            let shape = Rectangle::new(pos, get_value(values, "width", 1.0, line_number)?, get_value(values, "height", 1.0, line_number)?, colour, BLACK);
+            let material = Material::new(get_value(values, "mass", shape.get_area(), line_number)?, shape.get_area());
+            Ok(Box::new(apply_values(Object::new(shape, material, PhysicsType::Static), values, line_number)?))
+        }
+        //Squares are made as rectangles with equal sides, the same as when they are drawn with the mouse, so they can collide
+        "square" => {
+            let size = get_value(values, "size", 1.0, line_number)?;
+            let shape = Rectangle::new(pos, size, size, colour, BLACK);
+            let material = Material::new(get_value(values, "mass", shape.get_area(), line_number)?, shape.get_area());
+            Ok(Box::new(apply_values(Object::new(shape, material, PhysicsType::Static), values, line_number)?))
+        }
+        other => Err(format!("Line {}: unknown object '{}'", line_number, other)),
+    }
+}
+
+//Create a force generator from one line of a scene file. Springs are attached to an object already in the scene,
+//given by its index in the file
+fn line_to_force(kind: &str, values: &HashMap<&str, &str>, objects: &[Box<dyn PhysicsObject>], line_number: usize) -> Result<Box<dyn ForceGenerator>, String> {
+    let pos = Vec2::new(get_value(values, "x", 0.0, line_number)?, get_value(values, "y", 0.0, line_number)?);
+    match kind {
+        "spring" => {
+            let index: usize = get_value(values, "object", 0, line_number)?;
+            let Some(object) = objects.get(index) else {
+                return Err(format!("Line {}: spring is attached to object {}, which has not been created yet", line_number, index));
+            };
+            Ok(Box::new(Spring {
+                handle: object.get_handle(),
+                anchor: pos,
+                stiffness: get_value(values, "stiffness", 10.0, line_number)?,
+                rest_length: get_value(values, "length", 1.0, line_number)?,
+                damping: get_value(values, "damping", 0.0, line_number)?,
+            }))
+        }
+        "wind" => Ok(Box::new(Wind {
+            velocity: Vec2::new(get_value(values, "vx", 0.0, line_number)?, get_value(values, "vy", 0.0, line_number)?),
+            density: get_value(values, "density", AIR_DENSITY, line_number)?,
+        })),
+        "fluid" => {
+            let density = get_value(values, "density", 1.0, line_number)?;
+            let viscosity = get_value(values, "viscosity", 0.5, line_number)?;
+            //Polygons are written as their corners, x:y,x:y..., instead of a rectangle
+            let Some(text) = values.get("corners") else {
+                let size = Vec2::new(get_value(values, "width", 1.0, line_number)?, get_value(values, "height", 1.0, line_number)?);
+                return Ok(Box::new(FluidRegion::new(pos, size, density, viscosity)));
+            };
+            let mut corners = Vec::new();
+            for corner in text.split(',').filter(|part| !part.is_empty()) {
+                let parts: Result<Vec<f32>, _> = corner.split(':').map(str::parse).collect();
+                let Ok([x, y]) = parts.as_deref() else {
+                    return Err(format!("Line {}: '{}' is not a valid corner, which must be written as x:y", line_number, corner));
+                };
+                corners.push(Vec2::new(*x, *y));
+            }
+            if corners.len() < 3 {
+                return Err(format!("Line {}: a fluid polygon needs at least 3 corners", line_number));
+            }
+            Ok(Box::new(FluidRegion::polygon(corners, density, viscosity)))
+        }
+        "efield" => Ok(Box::new(ElectricField::new(
+            pos,
+            Vec2::new(get_value(values, "width", 1.0, line_number)?, get_value(values, "height", 1.0, line_number)?),
+            Vec2::new(get_value(values, "ex", 0.0, line_number)?, get_value(values, "ey", 0.0, line_number)?),
+        ))),
+        "bfield" => Ok(Box::new(MagneticField::new(
+            pos,
+            Vec2::new(get_value(values, "width", 1.0, line_number)?, get_value(values, "height", 1.0, line_number)?),
+            get_value(values, "strength", 1.0, line_number)?,
+        ))),
+        "zone" => {
+            let name: String = get_value(values, "kind", String::from("wind"), line_number)?;
+            let Some(kind) = ZoneKind::from_name(&name) else {
+                return Err(format!("Line {}: unknown zone kind '{}'", line_number, name));
+            };
+            Ok(Box::new(ForceZone::new(
+                pos,
+                Vec2::new(get_value(values, "width", 1.0, line_number)?, get_value(values, "height", 1.0, line_number)?),
+                kind,
+                get_value(values, "strength", 10.0, line_number)?,
+                get_value(values, "angle", 0.0, line_number)?,
+            )))
+        }
+        "softbody" => {
+            //Points and springs are written as the indexes of objects earlier in the file
+            let handle_of = |text: &str| -> Result<usize, String> {
+                let index: usize = text.trim().parse().map_err(|_| format!("Line {}: '{}' is not an object index", line_number, text))?;
+                objects.get(index).map(|object| object.get_handle())
+                    .ok_or(format!("Line {}: soft body uses object {}, which has not been created yet", line_number, index))
+            };
+            let get_handles = |key: &str| -> Result<Vec<usize>, String> {
+                values.get(key).map_or(Ok(Vec::new()), |text| text.split(',').filter(|part| !part.is_empty()).map(handle_of).collect())
+            };
+            let mut springs = Vec::new();
+            for spring in values.get("springs").copied().unwrap_or("").split(',').filter(|part| !part.is_empty()) {
+                let parts: Vec<&str> = spring.split(':').collect();
+                let [a, b, rest_length] = parts[..] else {
+                    return Err(format!("Line {}: springs must be written as a:b:length", line_number));
+                };
+                let rest_length = rest_length.parse().map_err(|_| format!("Line {}: '{}' is not a valid spring length", line_number, rest_length))?;
+                springs.push(SoftSpring { a: handle_of(a)?, b: handle_of(b)?, rest_length });
+            }
+            Ok(Box::new(SoftBody::new(
+                get_handles("points")?,
+                get_handles("hull")?,
+                springs,
+                get_value(values, "stiffness", 10.0, line_number)?,
+                get_value(values, "damping", 0.0, line_number)?,
+                (get_value(values, "pressure", 0.0, line_number)?, get_value(values, "area", 0.0, line_number)?),
+                get_colour(values, "colour", WHITE, line_number)?,
+            )))
+        }
+        "rope" | "cloth" => {
+            let end = Vec2::new(get_value(values, "x2", pos.x + 1.0, line_number)?, get_value(values, "y2", pos.y, line_number)?);
+            let cloth_kind = if kind == "rope" { ClothKind::Rope } else { ClothKind::Cloth };
+            let mut cloth = Cloth::new(cloth_kind, pos, end, get_value(values, "length", pos.distance(end), line_number)?);
+            cloth.spacing = get_value(values, "spacing", cloth.spacing, line_number)?;
+            cloth.point_mass = get_value(values, "mass", cloth.point_mass, line_number)?;
+            cloth.tear_tension = get_value(values, "tear", 0.0, line_number)?;
+            cloth.gravity = get_value(values, "gravity", cloth.gravity, line_number)?;
+            cloth.colour = get_colour(values, "colour", WHITE, line_number)?;
+            //The points are made again now the spacing is known
+            cloth.reset();
+            Ok(Box::new(cloth))
+        }
+        "gas" => {
+            let mut gas = GasBox::new(
+                pos,
+                Vec2::new(get_value(values, "width", 2.0, line_number)?, get_value(values, "height", 2.0, line_number)?),
+                get_value(values, "count", 100, line_number)?,
+                get_value(values, "temperature", 300.0, line_number)?,
+            );
+            gas.max_width = get_value(values, "max_width", gas.max_width, line_number)?.max(gas.size.x);
+            gas.thermostat = get_value(values, "thermostat", false, line_number)?;
+            gas.piston_free = get_value(values, "piston_free", false, line_number)?;
+            gas.outside_pressure = get_value(values, "outside_pressure", 0.0, line_number)?;
+            Ok(Box::new(gas))
+        }
+        "sph" => {
+            let mut fluid = SphFluid::new(
+                pos,
+                Vec2::new(get_value(values, "width", 1.0, line_number)?, get_value(values, "height", 1.0, line_number)?),
+                get_value(values, "spacing", 0.1, line_number)?,
+                get_value(values, "density", 1.0, line_number)?,
+                get_value(values, "sound_speed", 15.0, line_number)?,
+                get_value(values, "viscosity", 0.1, line_number)?,
+            );
+            fluid.gravity = get_value(values, "gravity", fluid.gravity, line_number)?;
+            Ok(Box::new(fluid))
+        }
+        "emitter" => {
+            //Anything not written uses the smoke settings
+            let mut emitter = ParticleEmitter::new(pos, EmitterPreset::Smoke);
+            emitter.rate = get_value(values, "rate", emitter.rate, line_number)?;
+            emitter.speed = get_value(values, "speed", emitter.speed, line_number)?;
+            emitter.speed_spread = get_value(values, "speed_spread", emitter.speed_spread, line_number)?;
+            emitter.angle = get_value(values, "angle", emitter.angle, line_number)?;
+            emitter.angle_spread = get_value(values, "angle_spread", emitter.angle_spread, line_number)?;
+            emitter.lifetime = get_value(values, "lifetime", emitter.lifetime, line_number)?;
+            emitter.size = get_value(values, "size", emitter.size, line_number)?;
+            emitter.mass = get_value(values, "mass", emitter.mass, line_number)?;
+            emitter.gravity = get_value(values, "gravity", emitter.gravity, line_number)?;
+            emitter.do_air_resistance = get_value(values, "air", emitter.do_air_resistance, line_number)?;
+            emitter.collide = get_value(values, "collide", emitter.collide, line_number)?;
+            emitter.bounce = get_value(values, "bounce", emitter.bounce, line_number)?;
+            emitter.start_colour = get_colour(values, "start_colour", emitter.start_colour, line_number)?;
+            emitter.end_colour = get_colour(values, "end_colour", emitter.end_colour, line_number)?;
+            Ok(Box::new(emitter))
+        }
+        "actuator" => {
+            let index: usize = get_value(values, "object", 0, line_number)?;
+            let Some(object) = objects.get(index) else {
+                return Err(format!("Line {}: actuator drives object {}, which has not been created yet", line_number, index));
+            };
+            let name: String = get_value(values, "mode", String::from("path"), line_number)?;
+            let Some(mode) = ActuatorMode::from_name(&name) else {
+                return Err(format!("Line {}: unknown actuator mode '{}'", line_number, name));
+            };
+            let name: String = get_value(values, "repeat", String::from("loop"), line_number)?;
+            let Some(repeat) = Repeat::from_name(&name) else {
+                return Err(format!("Line {}: unknown actuator repeat '{}'", line_number, name));
+            };
+            //Keyframes are written as time:x:y, or time:angular velocity for orbiting actuators
+            let mut keyframes = Vec::new();
+            for key in values.get("keys").copied().unwrap_or("").split(',').filter(|part| !part.is_empty()) {
+                let parts: Result<Vec<f32>, _> = key.split(':').map(str::parse).collect();
+                let value = match parts.as_deref() {
+                    Ok([time, x, y]) if mode != ActuatorMode::Orbit => (*time, Vec2::new(*x, *y)),
+                    Ok([time, speed]) if mode == ActuatorMode::Orbit => (*time, Vec2::new(*speed, 0.0)),
+                    _ => return Err(format!("Line {}: '{}' is not a valid keyframe", line_number, key)),
+                };
+                keyframes.push(value);
+            }
+            let mut actuator = Actuator::new(object.get_handle(), mode, keyframes);
+            actuator.repeat = repeat;
+            actuator.pivot = Vec2::new(get_value(values, "px", 0.0, line_number)?, get_value(values, "py", 0.0, line_number)?);
+            actuator.running = get_value(values, "running", true, line_number)?;
+            Ok(Box::new(actuator))
+        }
+        "attractor" => Ok(Box::new(RadialAttractor {
+            centre: pos,
+            strength: get_value(values, "strength", 10.0, line_number)?,
+            softening: get_value(values, "softening", 0.1, line_number)?,
+        })),
+        _ => Err(format!("Line {}: unknown force '{}'", line_number, kind)),
+    }
+}
+
+//Create a joint from one line of a scene file, between two objects given by their index in the file
+fn line_to_joint(values: &HashMap<&str, &str>, objects: &mut [Box<dyn PhysicsObject>], line_number: usize) -> Result<Joint, String> {
+    let mut get_body = |key: &str| -> Result<Body, String> {
+        let index: usize = get_value(values, key, 0, line_number)?;
+        objects.get_mut(index).map(|object| Body::new(object.as_mut()))
+            .ok_or(format!("Line {}: joint uses object {}, which has not been created yet", line_number, index))
+    };
+    let (a, b) = (get_body("a")?, get_body("b")?);
+    let mut joint = Joint::new(&a, &b);
+    joint.rest_length = get_value(values, "length", joint.rest_length, line_number)?;
+    joint.break_force = get_value(values, "break", 0.0, line_number)?;
+    Ok(joint)
+}
+
+//Load the settings and every object from a scene file.
+//Each line is either a setting ("seed 42"), an object ("circle x=0 y=0 radius=0.5 ..."), blank, or a comment starting with #
+pub(crate) fn load_scene(path: &str) -> Result<Scene, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+    let mut scene = Scene::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let kind = words.next().unwrap();
+        let rest: Vec<&str> = words.collect();
+
+        //Read the settings for the simulation
+        let parse_setting = |name: &str| -> Result<&str, String> {
+            rest.first().copied().ok_or(format!("Line {}: {} needs a value", line_number, name))
+        };
+        match kind {
+            "deterministic" => {
+                scene.settings.deterministic = parse_setting(kind)?.parse().map_err(|_| format!("Line {}: deterministic must be true or false", line_number))?;
+            }
+            "seed" => {
+                scene.settings.seed = parse_setting(kind)?.parse().map_err(|_| format!("Line {}: seed must be a whole number", line_number))?;
+            }
+            "random" => {
+                let state = parse_setting(kind)?.parse().map_err(|_| format!("Line {}: random must be a whole number", line_number))?;
+                scene.settings.random_state = Some(state);
+            }
+            "timestep" => {
+                let timestep: f32 = parse_setting(kind)?.parse().map_err(|_| format!("Line {}: timestep must be a number", line_number))?;
+                if timestep <= 0.0 {
+                    return Err(format!("Line {}: timestep must be greater than 0", line_number));
+                }
+                scene.settings.timestep = timestep;
+            }
+            //Anything else is a force or an object, with its properties written as key=value
+            _ => {
+                let mut values: HashMap<&str, &str> = HashMap::new();
+                for word in rest {
+                    let Some((key, value)) = word.split_once('=') else {
+                        return Err(format!("Line {}: expected key=value but found '{}'", line_number, word));
+                    };
+                    values.insert(key, value);
+                }
+                if kind == "nbody" {
+                    //N-body gravitation replaces the normal gravity and drag
+                    let nbody = NBodyGravity::new(get_value(&values, "g", 1.0, line_number)?,
+                                                  get_value(&values, "softening", 0.05, line_number)?,
+                                                  get_value(&values, "theta", 0.5, line_number)?);
+                    set_nbody(&mut scene.forces, Some(nbody));
+                } else if matches!(kind, "spring" | "wind" | "attractor" | "fluid" | "efield" | "bfield" | "zone" | "softbody" | "emitter" | "gas"
+                                         | "sph" | "rope" | "cloth" | "actuator") {
+                    scene.forces.push(line_to_force(kind, &values, &scene.objects, line_number)?);
+                } else if kind == "joint" {
+                    add_joint(&mut scene.forces, line_to_joint(&values, &mut scene.objects, line_number)?);
+                } else {
+                    scene.objects.push(line_to_object(kind, &values, line_number)?);
+                }
+            }
+        }
+    }
+    Ok(scene)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::measurements::{random_range, seed_random, set_fixed_dt};
+    use crate::objects::events::EventLog;
+    use crate::objects::world::checksum;
+    use super::{load_scene, save_scene, SimulationSettings};
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("rv_scene_test_{}_{}.txt", name, std::process::id())).to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn saved_scene_carries_on_the_same() {
+        let path = temp_path("original");
+        fs::write(&path, "deterministic true\nseed 3\n\
+            rectangle x=-1 y=3 width=6 height=0.5 type=static friction=0.3\n\
+            rectangle x=0 y=1 width=0.5 height=0.4 type=dynamic bounce=0.2 vx=1\n\
+            square x=1 y=0 size=0.3 type=dynamic\n\
+            circle x=2 y=1 radius=0.2 type=dynamic charge=0.001\n\
+            circle x=2 y=0 radius=0.1 type=static\n\
+            joint a=3 b=4\n\
+            spring object=1 x=0 y=0 stiffness=5 damping=0.5\n").unwrap();
+        let mut scene = load_scene(&path).unwrap();
+        set_fixed_dt(Some(scene.settings.timestep));
+        scene.run(&mut EventLog::new(), 30);
+
+        //Saving and loading halfway through should give exactly the same simulation from then on
+        let saved = temp_path("saved");
+        save_scene(&saved, &scene.settings, &mut scene.objects, &scene.forces).unwrap();
+        let mut loaded = load_scene(&saved).unwrap();
+        assert_eq!(checksum(&mut scene.objects, &scene.forces), checksum(&mut loaded.objects, &loaded.forces));
+        scene.run(&mut EventLog::new(), 60);
+        loaded.run(&mut EventLog::new(), 60);
+        assert_eq!(checksum(&mut scene.objects, &scene.forces), checksum(&mut loaded.objects, &loaded.forces));
+
+        //Saving the loaded scene again should write the same file
+        let resaved = temp_path("resaved");
+        let mut reloaded = load_scene(&saved).unwrap();
+        save_scene(&resaved, &reloaded.settings, &mut reloaded.objects, &reloaded.forces).unwrap();
+        assert_eq!(fs::read_to_string(&saved).unwrap(), fs::read_to_string(&resaved).unwrap());
+        for file in [path, saved, resaved] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn saved_scene_keeps_the_random_numbers() {
+        seed_random(3);
+        random_range(0.0, 1.0);
+        let path = temp_path("random");
+        save_scene(&path, &SimulationSettings::new(), &mut Vec::new(), &[]).unwrap();
+        let next = random_range(0.0, 1.0);
+
+        //Loading the scene goes back to where the random numbers were when it was saved, not to the seed
+        seed_random(3);
+        let loaded = load_scene(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        loaded.settings.start_random();
+        assert_eq!(random_range(0.0, 1.0), next);
+    }
+
+    #[test]
+    fn bad_lines_give_errors() {
+        let path = temp_path("bad");
+        for (text, error) in [("timestep 0\n", "Line 1: timestep must be greater than 0"),
+                              ("circle x=1 y\n", "Line 1: expected key=value but found 'y'"),
+                              ("triangle x=0\n", "Line 1: unknown object 'triangle'"),
+                              ("circle\nspring object=2\n", "Line 2: spring is attached to object 2, which has not been created yet")] {
+            fs::write(&path, text).unwrap();
+            assert_eq!(load_scene(&path).err().as_deref(), Some(error));
+        }
+        fs::remove_file(path).unwrap();
+    }
+}
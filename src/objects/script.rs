@@ -0,0 +1,406 @@
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::rc::Rc;
+use macroquad::color::{BLACK, WHITE};
+use macroquad::math::Vec2;
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, AST, INT};
+use crate::measurements::dt;
+use crate::objects::events::{EventLog, EventType};
+use crate::objects::forces::{get_bodies, total_force, ForceGenerator, RadialAttractor, Spring, Wind, AIR_DENSITY};
+use crate::objects::physics::{apply_impulse, set_belt_speed, Material, PhysicsObject, PhysicsType};
+use crate::objects::shapes::{Circle, Rectangle};
+use crate::objects::world::step;
+use crate::objects::{Object, Render};
+
+//The most operations a script can run in one call, so a script stuck in a loop can't freeze the program
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+//The objects and forces are moved in here while the script runs, so the functions given to the script can change them
+type SharedObjects = Rc<RefCell<Vec<Box<dyn PhysicsObject>>>>;
+type SharedForces = Rc<RefCell<Vec<Box<dyn ForceGenerator>>>>;
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+//A script that sets up a scene, and can change it every step or when objects touch.
+//
+//Functions given to the script:
+//  circle(x, y, radius), rectangle(x, y, width, height), square(x, y, size) -> handle
+//  set_type(handle, "static" | "dynamic" | "kinematic"), set_mass(handle, mass), set_gravity(handle, gravity),
+//  set_velocity(handle, vx, vy), set_air_resistance(handle, true | false), set_sensor(handle, true | false),
+//  set_charge(handle, charge), set_break(handle, impulse), set_friction(handle, coefficient),
+//  set_restitution(handle, coefficient), set_belt(handle, speed)
+//  apply_force(handle, fx, fy), apply_impulse(handle, jx, jy) (impulses only move dynamic objects)
+//  spring(handle, x, y, stiffness, length, damping), wind(vx, vy), attractor(x, y, strength) add force generators
+//  get_x(handle), get_y(handle), get_vx(handle), get_vy(handle), get_mass(handle), get_density(handle), time()
+//  get_fx(handle), get_fy(handle) (the total force on the object from every force generator)
+//  on_step(|time, dt| { ... }), on_contact(|handle_1, handle_2, sensor| { ... })
+pub(crate) struct Script {
+    engine: Engine,
+    ast: AST,
+    path: String,
+    objects: SharedObjects,
+    forces: SharedForces,
+    time: Rc<Cell<f32>>,
+    step_callbacks: Rc<RefCell<Vec<FnPtr>>>,
+    contact_callbacks: Rc<RefCell<Vec<FnPtr>>>,
+}
+
+//Turn a number from the script into an f32, so students can write 1 as well as 1.0
+fn number(value: Dynamic) -> ScriptResult<f32> {
+    if let Ok(float) = value.as_float() {
+        return Ok(float as f32);
+    }
+    if let Ok(int) = value.as_int() {
+        return Ok(int as f32);
+    }
+    Err(format!("expected a number but found {}", value.type_name()).into())
+}
+
+//Find the object with a handle, and run a function on it
+fn with_object<R>(objects: &SharedObjects, handle: INT, function: impl FnOnce(&mut Box<dyn PhysicsObject>) -> R) -> ScriptResult<R> {
+    let mut objects = objects.borrow_mut();
+    match objects.iter_mut().find(|object| object.get_handle() as INT == handle) {
+        Some(object) => Ok(function(object)),
+        None => Err(format!("there is no object #{}", handle).into()),
+    }
+}
+
+//Add a new static object to the scene, with a mass worked out from its area, and give its handle to the script
+fn add_object<T: Render + Clone + 'static>(objects: &SharedObjects, shape: T) -> INT {
+    let material = Material::new(shape.get_area(), shape.get_area());
+    let object = Object::new(shape, material, PhysicsType::Static);
+    let handle = object.handle as INT;
+    objects.borrow_mut().push(Box::new(object));
+    handle
+}
+
+//Work out the force every generator puts on an object right now, getting them ready first so forces
+//between objects (e.g. charges) use where the objects are now, not where they were at the last step
+fn get_total_force(objects: &SharedObjects, forces: &SharedForces, handle: INT) -> ScriptResult<Vec2> {
+    let bodies = get_bodies(&mut objects.borrow_mut());
+    let Some(body) = bodies.iter().find(|body| body.handle as INT == handle) else {
+        return Err(format!("there is no object #{}", handle).into());
+    };
+    let mut forces = forces.borrow_mut();
+    for generator in forces.iter_mut() {
+        generator.prepare(&bodies);
+    }
+    Ok(total_force(&forces, body))
+}
+
+//Give the script every function it can use to build and change the scene
+fn register_functions(engine: &mut Engine, objects: &SharedObjects, forces: &SharedForces, time: &Rc<Cell<f32>>,
+                      step_callbacks: &Rc<RefCell<Vec<FnPtr>>>, contact_callbacks: &Rc<RefCell<Vec<FnPtr>>>) {
+    //Creating objects
+    let shared = objects.clone();
+    engine.register_fn("circle", move |x: Dynamic, y: Dynamic, radius: Dynamic| -> ScriptResult<INT> {
+        let shape = Circle::new(Vec2::new(number(x)?, number(y)?), number(radius)?, WHITE, BLACK);
+        Ok(add_object(&shared, shape))
+    });
+    let shared = objects.clone();
+    engine.register_fn("rectangle", move |x: Dynamic, y: Dynamic, width: Dynamic, height: Dynamic| -> ScriptResult<INT> {
+        let shape = Rectangle::new(Vec2::new(number(x)?, number(y)?), number(width)?, number(height)?, WHITE, BLACK);
+        Ok(add_object(&shared, shape))
+    });
+    let shared = objects.clone();
+    //Squares are made as rectangles, the same as when they are drawn with the mouse, so they can collide
+    engine.register_fn("square", move |x: Dynamic, y: Dynamic, size: Dynamic| -> ScriptResult<INT> {
+        let size = number(size)?;
+        let shape = Rectangle::new(Vec2::new(number(x)?, number(y)?), size, size, WHITE, BLACK);
+        Ok(add_object(&shared, shape))
+    });
+
+    //Changing the properties of objects
+    let shared = objects.clone();
+    engine.register_fn("set_type", move |handle: INT, name: &str| -> ScriptResult<()> {
+        let phys_type = match name {
+            "static" => PhysicsType::Static,
+            "dynamic" => PhysicsType::Dynamic,
+            "kinematic" => PhysicsType::Kinematic,
+            other => return Err(format!("unknown physics type '{}'", other).into()),
+        };
+        with_object(&shared, handle, |object| object.set_physics_type(phys_type))
+    });
+    let shared = objects.clone();
+    engine.register_fn("set_mass", move |handle: INT, mass: Dynamic| -> ScriptResult<()> {
+        let mass = number(mass)?;
+        if mass <= 0.0 {
+            return Err("mass must be greater than 0".into());
+        }
+        with_object(&shared, handle, |object| {
+            object.get_material().mass = mass;
+            object.update_material();
+            object.wake();
+        })
+    });
+    let shared = objects.clone();
+    engine.register_fn("set_gravity", move |handle: INT, gravity: Dynamic| -> ScriptResult<()> {
+        let gravity = number(gravity)?;
+        with_object(&shared, handle, |object| {
+            *object.get_gravity() = gravity;
+            object.wake();
+        })
+    });
+    let shared = objects.clone();
+    engine.register_fn("set_velocity", move |handle: INT, vx: Dynamic, vy: Dynamic| -> ScriptResult<()> {
+        let velocity = Vec2::new(number(vx)?, number(vy)?);
+        with_object(&shared, handle, |object| object.set_velocity(velocity))
+    });
+    let shared = objects.clone();
+    engine.register_fn("set_air_resistance", move |handle: INT, air: bool| -> ScriptResult<()> {
+        with_object(&shared, handle, |object| *object.set_do_air_resistance() = air)
+    });
+    let shared = objects.clone();
+    engine.register_fn("set_sensor", move |handle: INT, sensor: bool| -> ScriptResult<()> {
+        with_object(&shared, handle, |object| *object.get_sensor() = sensor)
+    });
+    let shared = objects.clone();
+    engine.register_fn("set_charge", move |handle: INT, charge: Dynamic| -> ScriptResult<()> {
+        let charge = number(charge)?;
+        with_object(&shared, handle, |object| *object.get_charge() = charge)
+    });
+    let shared = objects.clone();
+    engine.register_fn("set_break", move |handle: INT, impulse: Dynamic| -> ScriptResult<()> {
+        let impulse = number(impulse)?;
+        with_object(&shared, handle, |object| *object.get_break_impulse() = impulse)
+    });
+    let shared = objects.clone();
+    engine.register_fn("set_friction", move |handle: INT, friction: Dynamic| -> ScriptResult<()> {
+        let friction = number(friction)?;
+        with_object(&shared, handle, |object| *object.get_friction() = friction)
+    });
+    let shared = objects.clone();
+    engine.register_fn("set_restitution", move |handle: INT, restitution: Dynamic| -> ScriptResult<()> {
+        let restitution = number(restitution)?;
+        with_object(&shared, handle, |object| *object.get_restitution() = restitution)
+    });
+    let shared = objects.clone();
+    engine.register_fn("set_belt", move |handle: INT, speed: Dynamic| -> ScriptResult<()> {
+        let speed = number(speed)?;
+        with_object(&shared, handle, |object| set_belt_speed(object.as_mut(), speed))
+    });
+
+    //Forces change the velocity over one step (F = ma), and impulses change it straight away (J = mv)
+    let shared = objects.clone();
+    engine.register_fn("apply_force", move |handle: INT, fx: Dynamic, fy: Dynamic| -> ScriptResult<()> {
+        let force = Vec2::new(number(fx)?, number(fy)?);
+        with_object(&shared, handle, |object| {
+            let mass = object.get_material().mass;
+            let velocity = object.get_velocity() + force / mass * dt();
+            object.set_velocity(velocity);
+        })
+    });
+    let shared = objects.clone();
+    engine.register_fn("apply_impulse", move |handle: INT, jx: Dynamic, jy: Dynamic| -> ScriptResult<()> {
+        let impulse = Vec2::new(number(jx)?, number(jy)?);
+        with_object(&shared, handle, |object| apply_impulse(object.as_mut(), impulse))
+    });
+
+    //Adding force generators, which act on the objects every step until the scene is cleared
+    let shared = objects.clone();
+    let shared_forces = forces.clone();
+    engine.register_fn("spring", move |handle: INT, x: Dynamic, y: Dynamic, stiffness: Dynamic, length: Dynamic, damping: Dynamic| -> ScriptResult<()> {
+        let spring = Spring {
+            handle: with_object(&shared, handle, |object| object.get_handle())?,
+            anchor: Vec2::new(number(x)?, number(y)?),
+            stiffness: number(stiffness)?,
+            rest_length: number(length)?,
+            damping: number(damping)?,
+        };
+        shared_forces.borrow_mut().push(Box::new(spring));
+        Ok(())
+    });
+    let shared = forces.clone();
+    engine.register_fn("wind", move |vx: Dynamic, vy: Dynamic| -> ScriptResult<()> {
+        let velocity = Vec2::new(number(vx)?, number(vy)?);
+        shared.borrow_mut().push(Box::new(Wind { velocity, density: AIR_DENSITY }));
+        Ok(())
+    });
+    let shared = forces.clone();
+    engine.register_fn("attractor", move |x: Dynamic, y: Dynamic, strength: Dynamic| -> ScriptResult<()> {
+        let centre = Vec2::new(number(x)?, number(y)?);
+        shared.borrow_mut().push(Box::new(RadialAttractor { centre, strength: number(strength)?, softening: 0.1 }));
+        Ok(())
+    });
+
+    //Reading the state of objects and the simulation
+    let shared = objects.clone();
+    engine.register_fn("get_x", move |handle: INT| -> ScriptResult<f64> {
+        with_object(&shared, handle, |object| object.get_render_shape_reference().get_pos().x as f64)
+    });
+    let shared = objects.clone();
+    engine.register_fn("get_y", move |handle: INT| -> ScriptResult<f64> {
+        with_object(&shared, handle, |object| object.get_render_shape_reference().get_pos().y as f64)
+    });
+    let shared = objects.clone();
+    engine.register_fn("get_vx", move |handle: INT| -> ScriptResult<f64> {
+        with_object(&shared, handle, |object| object.get_velocity().x as f64)
+    });
+    let shared = objects.clone();
+    engine.register_fn("get_vy", move |handle: INT| -> ScriptResult<f64> {
+        with_object(&shared, handle, |object| object.get_velocity().y as f64)
+    });
+    let shared = objects.clone();
+    engine.register_fn("get_mass", move |handle: INT| -> ScriptResult<f64> {
+        with_object(&shared, handle, |object| object.get_material().mass as f64)
+    });
+    let shared = objects.clone();
+    engine.register_fn("get_density", move |handle: INT| -> ScriptResult<f64> {
+        with_object(&shared, handle, |object| object.get_material().density as f64)
+    });
+    let shared = objects.clone();
+    let shared_forces = forces.clone();
+    engine.register_fn("get_fx", move |handle: INT| -> ScriptResult<f64> {
+        Ok(get_total_force(&shared, &shared_forces, handle)?.x as f64)
+    });
+    let shared = objects.clone();
+    let shared_forces = forces.clone();
+    engine.register_fn("get_fy", move |handle: INT| -> ScriptResult<f64> {
+        Ok(get_total_force(&shared, &shared_forces, handle)?.y as f64)
+    });
+    let shared = time.clone();
+    engine.register_fn("time", move || shared.get() as f64);
+
+    //Callbacks run every step, and whenever two objects start touching
+    let shared = step_callbacks.clone();
+    engine.register_fn("on_step", move |callback: FnPtr| shared.borrow_mut().push(callback));
+    let shared = contact_callbacks.clone();
+    engine.register_fn("on_contact", move |callback: FnPtr| shared.borrow_mut().push(callback));
+}
+
+impl Script {
+    //Read and compile a script, without running it yet
+    pub(crate) fn load(path: &str) -> Result<Script, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+
+        let objects: SharedObjects = Rc::new(RefCell::new(Vec::new()));
+        let forces: SharedForces = Rc::new(RefCell::new(Vec::new()));
+        let time = Rc::new(Cell::new(0.0));
+        let step_callbacks = Rc::new(RefCell::new(Vec::new()));
+        let contact_callbacks = Rc::new(RefCell::new(Vec::new()));
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        register_functions(&mut engine, &objects, &forces, &time, &step_callbacks, &contact_callbacks);
+        let ast = engine.compile(text).map_err(|error| format!("{}: {}", path, error))?;
+
+        Ok(Script { engine, ast, path: path.to_string(), objects, forces, time, step_callbacks, contact_callbacks })
+    }
+
+    //Get the name of the file the script was loaded from
+    pub(crate) fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    //Run a function with the objects and forces moved into the script, moving them back out afterwards (even if it fails)
+    fn with_objects(&mut self, objects: &mut Vec<Box<dyn PhysicsObject>>, forces: &mut Vec<Box<dyn ForceGenerator>>,
+                    function: impl FnOnce(&Script) -> ScriptResult<()>) -> Result<(), String> {
+        *self.objects.borrow_mut() = std::mem::take(objects);
+        *self.forces.borrow_mut() = std::mem::take(forces);
+        let result = function(self);
+        *objects = std::mem::take(&mut *self.objects.borrow_mut());
+        *forces = std::mem::take(&mut *self.forces.borrow_mut());
+        result.map_err(|error| format!("{}: {}", self.path, error))
+    }
+
+    //Run the top level of the script, which sets up the scene and registers any callbacks
+    pub(crate) fn setup(&mut self, objects: &mut Vec<Box<dyn PhysicsObject>>, forces: &mut Vec<Box<dyn ForceGenerator>>,
+                        time: f32) -> Result<(), String> {
+        self.time.set(time);
+        self.with_objects(objects, forces, |script| script.engine.run_ast(&script.ast))
+    }
+
+    //Move the simulation forward by one step, running the step callbacks before it,
+    //and the contact callbacks for every pair of objects that started touching during it
+    pub(crate) fn step(&mut self, objects: &mut Vec<Box<dyn PhysicsObject>>, forces: &mut Vec<Box<dyn ForceGenerator>>,
+                       events: &mut EventLog) -> Result<(), String> {
+        self.time.set(events.time);
+        let step_callbacks = self.step_callbacks.borrow().clone();
+        self.with_objects(objects, forces, |script| {
+            for callback in &step_callbacks {
+                let _: Dynamic = callback.call(&script.engine, &script.ast, (events.time as f64, dt() as f64))?;
+            }
+            Ok(())
+        })?;
+
+        let first_event = events.events.len();
+        step(objects, forces, events);
+        self.time.set(events.time);
+
+        let contact_callbacks = self.contact_callbacks.borrow().clone();
+        if contact_callbacks.is_empty() {
+            return Ok(());
+        }
+        let contacts: Vec<(INT, INT, bool)> = events.events[first_event..].iter()
+            .filter(|event| matches!(event.event_type, EventType::BeginContact | EventType::SensorEnter))
+            .map(|event| (event.object_1 as INT, event.object_2 as INT, event.event_type == EventType::SensorEnter))
+            .collect();
+        self.with_objects(objects, forces, |script| {
+            for (object_1, object_2, sensor) in contacts {
+                for callback in &contact_callbacks {
+                    let _: Dynamic = callback.call(&script.engine, &script.ast, (object_1, object_2, sensor))?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::objects::events::EventLog;
+    use crate::objects::forces::default_forces;
+    use crate::objects::scene::Scene;
+    use super::Script;
+
+    //Load a script, run its setup on an empty scene, then run it for a number of steps
+    fn run(name: &str, text: &str, steps: usize) -> Scene {
+        let path = std::env::temp_dir().join(format!("rv_script_test_{}_{}.rhai", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let script = Script::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let mut script = script.unwrap();
+        let mut scene = Scene::load_test(name, "");
+        let mut events = EventLog::new();
+        script.setup(&mut scene.objects, &mut scene.forces, events.time).unwrap();
+        for _ in 0..steps {
+            script.step(&mut scene.objects, &mut scene.forces, &mut events).unwrap();
+        }
+        scene
+    }
+
+    #[test]
+    fn step_callbacks_push_every_step() {
+        //2 N on 2 kg for 1s takes it to 1 m/s
+        let mut scene = run("on_step", "let ball = circle(0, 0, 0.1);\nset_type(ball, \"dynamic\");\nset_gravity(ball, 0);\n\
+            set_air_resistance(ball, false);\nset_mass(ball, 2);\non_step(|time, dt| apply_force(ball, 2, 0));\n", 60);
+        let ball = &mut scene.objects[0];
+        assert!((ball.get_velocity().x - 1.0).abs() < 1e-3, "vx is {}", ball.get_velocity().x);
+        //Changing the mass keeps the density in step with it
+        let material = ball.get_material();
+        assert!((material.density - 2.0 / material.area).abs() < 1e-3);
+    }
+
+    #[test]
+    fn contact_callbacks_get_the_pair() {
+        let mut scene = run("on_contact", "let ball = circle(0, 0, 0.1);\nset_type(ball, \"dynamic\");\n\
+            let floor = rectangle(-1, 0.5, 2, 0.5);\n\
+            on_contact(|a, b, sensor| if !sensor && ((a == ball && b == floor) || (a == floor && b == ball)) { set_mass(ball, 5); });\n", 60);
+        assert_eq!(scene.objects[0].get_material().mass, 5.0);
+    }
+
+    #[test]
+    fn scripts_can_add_and_read_forces() {
+        const BALL: &str = "let ball = circle(2, 0, 0.1);\nset_type(ball, \"dynamic\");\nset_gravity(ball, 0);\n\
+            set_air_resistance(ball, false);\nset_mass(ball, 1);\nspring(ball, 0, 0, 10, 1, 0);\n";
+        //Cancelling the spring's pull every step leaves the ball where it started
+        let mut scene = run("forces", &format!("{}on_step(|time, dt| apply_force(ball, -get_fx(ball), -get_fy(ball)));\n", BALL), 60);
+        assert_eq!(scene.forces.len(), default_forces().len() + 1);
+        assert!((scene.objects[0].get_render_shape_reference().get_centre().x - 2.0).abs() < 1e-3);
+
+        //Without it the spring pulls the ball in
+        let mut scene = run("spring", BALL, 10);
+        assert!(scene.objects[0].get_render_shape_reference().get_centre().x < 1.9);
+    }
+}
@@ -109,6 +109,7 @@ impl Render for Square {
                                                        self.pos.x + self.size, self.pos.y + self.size));
         items
     }
+    fn get_centre(&self) -> Vec2 { self.pos + Vec2::new(self.size, self.size) / 2. }
 }
 
 //Implementing Render for Rectangle
@@ -182,7 +183,7 @@ impl Render for Rectangle {
         let items: Vec<Point> = qtree.query(&boundary);
         items
     }
-
+    fn get_centre(&self) -> Vec2 { self.pos + Vec2::new(self.width, self.length) / 2. }
 }
 //Implement Render for circle
 impl Render for Circle {
@@ -248,4 +249,5 @@ impl Render for Circle {
         let items: Vec<Point> = qtree.query(&boundary);
         items
     }
+    fn get_centre(&self) -> Vec2 { self.pos }
 }
@@ -0,0 +1,118 @@
+use macroquad::camera::Camera2D;
+use macroquad::color::{Color, ORANGE, WHITE};
+use macroquad::input::{is_mouse_button_down, is_mouse_button_pressed, mouse_position, MouseButton};
+use macroquad::math::Vec2;
+use macroquad::shapes::draw_line;
+use macroquad::text::draw_text;
+use macroquad::ui::root_ui;
+use crate::measurements::{meter, vec2_meter};
+use crate::objects::physics::{PhysicsObject, PhysicsType};
+
+//The launch speed (m/s) for every metre the slingshot is pulled back
+#[allow(dead_code)]
+const LAUNCH_SCALE: f32 = 5.0;
+//How long (s) of the predicted path is drawn, and how many lines it is drawn with
+#[allow(dead_code)]
+const PREDICTION_TIME: f32 = 3.0;
+#[allow(dead_code)]
+const PREDICTION_STEPS: usize = 60;
+
+//The velocity an object is launched at when the slingshot is pulled from its centre back to the mouse
+#[allow(dead_code)]
+pub(crate) fn get_launch_velocity(centre: Vec2, mouse: Vec2) -> Vec2 {
+    (centre - mouse) * LAUNCH_SCALE
+}
+
+//The speed (m/s) and angle above the horizontal (degrees) of a launch. The y axis points down, so it is flipped for the angle
+#[allow(dead_code)]
+pub(crate) fn get_speed_and_angle(velocity: Vec2) -> (f32, f32) {
+    (velocity.length(), (-velocity.y).atan2(velocity.x).to_degrees())
+}
+
+//Where a projectile will be after a time, from s = ut + 1/2 at^2 with only gravity acting on it
+#[allow(dead_code)]
+pub(crate) fn predict_position(start: Vec2, velocity: Vec2, gravity: f32, time: f32) -> Vec2 {
+    start + velocity * time + Vec2::new(0., gravity) * time * time / 2.
+}
+
+//Launch dynamic objects by dragging back from them, like a slingshot. While dragging, the launch speed and angle are shown,
+//with the parabola the object would follow under uniform gravity alone. Letting go gives the object that velocity.
+//The handle and physics type of the object being pulled back are kept between frames. Like dragging, the object is made
+//static while the user is aiming, so it stays where it is, and its type is put back when it is launched
+#[allow(dead_code)]
+pub fn slingshot_process(grabbed: &mut Option<(usize, PhysicsType)>, camera: &Camera2D, objects: &mut [Box<dyn PhysicsObject>]) {
+    let mouse = camera.screen_to_world(Vec2::from(mouse_position()));
+    let Some((handle, before_phys_type)) = *grabbed else {
+        //Ignore clicks on the hotbar and any panels. Only dynamic objects can be launched, as nothing else falls
+        let on_ui = mouse_position().1 <= 40. || root_ui().is_mouse_over(Vec2::from(mouse_position()));
+        if is_mouse_button_pressed(MouseButton::Left) && !on_ui {
+            let index = objects.iter_mut()
+                .position(|object| object.get_render_shape_reference().mouse_in_area(mouse) && matches!(object.get_physics_type(), PhysicsType::Dynamic));
+            if let Some(index) = index {
+                let object = &mut objects[index];
+                *grabbed = Some((object.get_handle(), *object.get_physics_type()));
+                object.set_physics_type(PhysicsType::Static);
+                object.set_velocity(Vec2::ZERO);
+            }
+        }
+        return;
+    };
+    let Some(object) = objects.iter_mut().find(|object| object.get_handle() == handle) else {
+        *grabbed = None;
+        return;
+    };
+    let centre = object.get_render_shape_reference().get_centre();
+    let velocity = get_launch_velocity(centre, vec2_meter(mouse));
+
+    if !is_mouse_button_down(MouseButton::Left) {
+        *grabbed = None;
+        object.set_physics_type(before_phys_type);
+        object.set_velocity(velocity);
+        return;
+    }
+
+    //Draw the band of the slingshot, and the path the object will take as a dashed line
+    let colour = Color::new(ORANGE.r, ORANGE.g, ORANGE.b, 0.8);
+    draw_line(meter(centre.x), meter(centre.y), mouse.x, mouse.y, 2., colour);
+    let gravity = *object.get_gravity();
+    let step_time = PREDICTION_TIME / PREDICTION_STEPS as f32;
+    for step in (0..PREDICTION_STEPS).step_by(2) {
+        let a = predict_position(centre, velocity, gravity, step as f32 * step_time);
+        let b = predict_position(centre, velocity, gravity, (step + 1) as f32 * step_time);
+        draw_line(meter(a.x), meter(a.y), meter(b.x), meter(b.y), 2., WHITE);
+    }
+    let (speed, angle) = get_speed_and_angle(velocity);
+    draw_text(&format!("{:.2} m/s at {:.1} deg", speed, angle), mouse.x + 10., mouse.y + 20., 20., WHITE);
+    //The path doesn't include air resistance, N-body gravity or any other force, so it is labelled as only a guide
+    draw_text("path: uniform gravity only", mouse.x + 10., mouse.y + 40., 16., WHITE);
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::math::Vec2;
+    use crate::objects::events::EventLog;
+    use crate::objects::scene::Scene;
+    use super::{get_launch_velocity, get_speed_and_angle, predict_position, LAUNCH_SCALE};
+
+    #[test]
+    fn launch_goes_opposite_the_pull() {
+        //Pulling back down and to the left launches up and to the right, at 45 degrees above the horizontal
+        let velocity = get_launch_velocity(Vec2::new(1.0, 1.0), Vec2::new(0.0, 2.0));
+        assert_eq!(velocity, Vec2::new(1.0, -1.0) * LAUNCH_SCALE);
+        let (speed, angle) = get_speed_and_angle(velocity);
+        assert!((speed - 2_f32.sqrt() * LAUNCH_SCALE).abs() < 1e-4);
+        assert!((angle - 45.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn prediction_matches_the_flight() {
+        let mut scene = Scene::load_test("slingshot", "circle x=0 y=0 radius=0.1 mass=1 type=dynamic air=false vx=3 vy=-4\n");
+        let start = scene.objects[0].get_render_shape_reference().get_centre();
+        let gravity = *scene.objects[0].get_gravity();
+        scene.run(&mut EventLog::new(), 60);
+        //The simulation steps through time, so it only lands close to the exact parabola
+        let predicted = predict_position(start, Vec2::new(3.0, -4.0), gravity, 1.0);
+        let centre = scene.objects[0].get_render_shape_reference().get_centre();
+        assert!(centre.distance(predicted) < 0.1, "landed {} m from the prediction", centre.distance(predicted));
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::{PI, TAU};
+use macroquad::color::{Color, BLACK};
+use macroquad::math::Vec2;
+use macroquad::shapes::{draw_line, draw_triangle};
+use crate::measurements::meter;
+use crate::objects::forces::{Body, ForceGenerator};
+use crate::objects::physics::{Material, PhysicsObject, PhysicsType, SLEEP_VELOCITY};
+use crate::objects::shapes::Circle;
+use crate::objects::{Object, Render};
+
+//How far apart the point masses are made (m)
+const SOFT_SPACING: f32 = 0.25;
+//The stiffness of the springs for each kg of a point mass (N/m/kg). Much stiffer and the springs become unstable
+const STIFFNESS_PER_KG: f32 = 400.0;
+const DAMPING_PER_KG: f32 = 2.0;
+
+//A damped spring between two point masses of a soft body
+#[derive(Clone, Copy)]
+pub(crate) struct SoftSpring {
+    pub(crate) a: usize,
+    pub(crate) b: usize,
+    pub(crate) rest_length: f32,
+}
+
+//A soft body made of point masses (small circle objects) joined by damped springs (F = -k x - c v).
+//The outside points make a hull, which can be filled with gas pushing outwards (P = P0 A0 / A), like a balloon
+pub(crate) struct SoftBody {
+    pub(crate) points: Vec<usize>,
+    //The handles of the outside points, in order around the body
+    pub(crate) hull: Vec<usize>,
+    pub(crate) springs: Vec<SoftSpring>,
+    pub(crate) stiffness: f32,
+    pub(crate) damping: f32,
+    //The pressure (N/m) of the gas inside when the body is its original size, 0 for no gas
+    pub(crate) pressure: f32,
+    pub(crate) rest_area: f32,
+    pub(crate) colour: Color,
+    //The centre and velocity of every point at the start of the step
+    states: HashMap<usize, (Vec2, Vec2)>,
+    //The force on every point this step, worked out once in prepare
+    point_forces: HashMap<usize, Vec2>,
+    moving: bool,
+}
+
+impl SoftBody {
+    //The gas is the pressure and the area it has that pressure at
+    pub(crate) fn new(points: Vec<usize>, hull: Vec<usize>, springs: Vec<SoftSpring>, stiffness: f32, damping: f32, gas: (f32, f32),
+                      colour: Color) -> SoftBody {
+        let (pressure, rest_area) = gas;
+        SoftBody { points, hull, springs, stiffness, damping, pressure, rest_area, colour, states: HashMap::new(),
+                   point_forces: HashMap::new(), moving: false }
+    }
+
+    //Get the centres of the hull points that still exist
+    fn get_hull_centres(&self) -> Vec<Vec2> {
+        self.hull.iter().filter_map(|handle| self.states.get(handle).map(|(centre, _)| *centre)).collect()
+    }
+}
+
+//The area inside a polygon (the shoelace formula)
+pub(crate) fn get_polygon_area(corners: &[Vec2]) -> f32 {
+    let mut area = 0.0;
+    for (index, corner) in corners.iter().enumerate() {
+        area += corner.perp_dot(corners[(index + 1) % corners.len()]);
+    }
+    (area / 2.).abs()
+}
+
+//Move an object and change its velocity, waking it up so it moves on from there
+fn nudge(object: &mut Box<dyn PhysicsObject>, offset: Vec2, impulse: Vec2, mass: f32) {
+    *object.get_render_shape_reference().get_pos() += offset;
+    let velocity = object.get_velocity() + impulse / mass;
+    object.set_velocity(velocity);
+}
+
+impl ForceGenerator for SoftBody {
+    //Push anything overlapping an edge of the hull back out, as only the points are objects and small objects could
+    //otherwise slip between them. The edge is as thick as the points, and is pushed back by its two points
+    fn drive(&mut self, objects: &mut [Box<dyn PhysicsObject>]) {
+        let indexes: HashMap<usize, usize> = objects.iter().enumerate().map(|(index, object)| (object.get_handle(), index)).collect();
+        let Some(hull) = self.hull.iter().map(|handle| indexes.get(handle).copied()).collect::<Option<Vec<usize>>>() else { return; };
+        if hull.len() < 3 {
+            return;
+        }
+        let points: HashSet<usize> = self.points.iter().copied().collect();
+
+        for index in 0..objects.len() {
+            if points.contains(&objects[index].get_handle()) || *objects[index].get_sensor() {
+                continue;
+            }
+            let middle = hull.iter().map(|point| objects[*point].get_render_shape_reference().get_centre()).sum::<Vec2>() / hull.len() as f32;
+            for edge in 0..hull.len() {
+                let (a, b) = (hull[edge], hull[(edge + 1) % hull.len()]);
+                let (object, point_a, point_b) = (Body::new(objects[index].as_mut()), Body::new(objects[a].as_mut()), Body::new(objects[b].as_mut()));
+                if objects[index].get_sleeping() && objects[a].get_sleeping() && objects[b].get_sleeping() {
+                    continue;
+                }
+                let length = point_a.centre.distance(point_b.centre);
+                if length == 0.0 {
+                    continue;
+                }
+                let along = (point_b.centre - point_a.centre) / length;
+                let mut normal = along.perp();
+                if normal.dot(point_a.centre - middle) < 0.0 {
+                    normal = -normal;
+                }
+
+                //How far the object reaches out from its centre across the edge
+                let reach = if object.circle { object.half_size.x } else { (object.half_size * normal).abs().element_sum() };
+                let offset = object.centre - point_a.centre;
+                let (distance_along, distance_out) = (offset.dot(along), offset.dot(normal));
+                //Objects already right inside the hull are left alone
+                let depth = point_a.half_size.x + reach - distance_out;
+                if depth <= 0.0 || distance_out < -reach {
+                    continue;
+                }
+                //The edge is a capsule with the points as its ends, so anything touching an end is left to the points.
+                //A box is only pushed out across the edge if that is the shortest way out of it
+                if object.circle {
+                    if distance_along < 0.0 || distance_along > length {
+                        continue;
+                    }
+                } else {
+                    let radius = Vec2::splat(point_a.half_size.x);
+                    let overlap = (point_a.centre.max(point_b.centre) + radius).min(object.centre + object.half_size)
+                        - (point_a.centre.min(point_b.centre) - radius).max(object.centre - object.half_size);
+                    if overlap.min_element() <= 0.0 || depth > overlap.min_element() + 1e-4 {
+                        continue;
+                    }
+                }
+
+                //Share the push between the object and the two points, by how easily each one moves
+                let share = (distance_along / length).clamp(0.0, 1.0);
+                let object_weight = if object.dynamic { 1.0 / object.mass } else { 0.0 };
+                let edge_weight = if point_a.dynamic && point_b.dynamic { ((1.0 - share).powi(2) + share * share) / point_a.mass } else { 0.0 };
+                if object_weight + edge_weight == 0.0 {
+                    continue;
+                }
+                let push = normal * depth / (object_weight + edge_weight);
+                //Stop them closing, without a bounce, then let friction take away as much of the sliding as it can
+                let edge_velocity = point_a.velocity * (1.0 - share) + point_b.velocity * share;
+                let (closing, sliding) = ((object.velocity - edge_velocity).dot(normal), (object.velocity - edge_velocity).dot(along));
+                let normal_impulse = (-closing / (object_weight + edge_weight)).max(0.0);
+                let friction = objects[index].get_friction().max(*objects[a].get_friction());
+                let friction_impulse = (sliding / (object_weight + edge_weight)).clamp(-friction * normal_impulse, friction * normal_impulse);
+                let impulse = normal * normal_impulse - along * friction_impulse;
+                if object.dynamic {
+                    nudge(&mut objects[index], push * object_weight, impulse, object.mass);
+                }
+                if edge_weight > 0.0 {
+                    nudge(&mut objects[a], -push * (1.0 - share) / point_a.mass, -impulse * (1.0 - share), point_a.mass);
+                    nudge(&mut objects[b], -push * share / point_b.mass, -impulse * share, point_b.mass);
+                }
+            }
+        }
+    }
+
+    //Work out the force on every point from the springs and the gas, as each spring pulls on two points
+    fn prepare(&mut self, bodies: &[Body]) {
+        self.states.clear();
+        self.point_forces.clear();
+        self.moving = false;
+        for body in bodies {
+            if self.points.contains(&body.handle) {
+                self.states.insert(body.handle, (body.centre, body.velocity));
+                self.point_forces.insert(body.handle, Vec2::ZERO);
+                self.moving |= body.velocity.length() > SLEEP_VELOCITY;
+            }
+        }
+
+        for spring in &self.springs {
+            let (Some((centre_a, velocity_a)), Some((centre_b, velocity_b))) = (self.states.get(&spring.a), self.states.get(&spring.b)) else { continue; };
+            let offset = *centre_b - *centre_a;
+            let direction = offset.normalize_or_zero();
+            //Damping only resists the points moving apart or together along the spring
+            let extension = offset.length() - spring.rest_length;
+            let stretch_speed = (*velocity_b - *velocity_a).dot(direction);
+            let force = direction * (self.stiffness * extension + self.damping * stretch_speed);
+            *self.point_forces.get_mut(&spring.a).unwrap() += force;
+            *self.point_forces.get_mut(&spring.b).unwrap() -= force;
+        }
+
+        //The gas pushes out on every edge of the hull (F = P L), shared between the two points at its ends
+        let hull = self.get_hull_centres();
+        if self.pressure == 0.0 || hull.len() < 3 || hull.len() != self.hull.len() {
+            return;
+        }
+        let area = get_polygon_area(&hull);
+        if area <= 0.0 {
+            return;
+        }
+        let pressure = self.pressure * self.rest_area / area;
+        let middle = hull.iter().sum::<Vec2>() / hull.len() as f32;
+        for index in 0..hull.len() {
+            let next = (index + 1) % hull.len();
+            let edge = hull[next] - hull[index];
+            let mut normal = edge.perp();
+            if normal.dot((hull[index] + hull[next]) / 2. - middle) < 0.0 {
+                normal = -normal;
+            }
+            //The normal is as long as the edge, so this is P L
+            let force = normal.normalize_or_zero() * pressure * edge.length() / 2.;
+            *self.point_forces.get_mut(&self.hull[index]).unwrap() += force;
+            *self.point_forces.get_mut(&self.hull[next]).unwrap() += force;
+        }
+    }
+
+    fn get_force(&self, body: &Body) -> Vec2 {
+        self.point_forces.get(&body.handle).copied().unwrap_or(Vec2::ZERO)
+    }
+
+    //Every point is kept awake while any of them is moving, so the body doesn't freeze part of the way through squashing
+    fn keep_awake(&self, body: &Body) -> bool {
+        self.moving && self.points.contains(&body.handle)
+    }
+
+    //E = 1/2 k x^2 for every spring, counted on the first point of the spring
+    fn get_stored_energy(&self, body: &Body) -> f32 {
+        let mut energy = 0.0;
+        for spring in self.springs.iter().filter(|spring| spring.a == body.handle) {
+            let (Some((centre_a, _)), Some((centre_b, _))) = (self.states.get(&spring.a), self.states.get(&spring.b)) else { continue; };
+            let extension = centre_a.distance(*centre_b) - spring.rest_length;
+            energy += 0.5 * self.stiffness * extension * extension;
+        }
+        energy
+    }
+
+    //Fill the hull, and draw its outline
+    fn draw(&self, bodies: &[Body]) {
+        let centres: HashMap<usize, Vec2> = bodies.iter().map(|body| (body.handle, body.centre)).collect();
+        let hull: Vec<Vec2> = self.hull.iter().filter_map(|handle| centres.get(handle).copied()).collect();
+        if hull.len() < 3 {
+            return;
+        }
+        let middle = hull.iter().sum::<Vec2>() / hull.len() as f32;
+        let fill = Color::new(self.colour.r, self.colour.g, self.colour.b, 0.35);
+        for index in 0..hull.len() {
+            let (start, end) = (hull[index], hull[(index + 1) % hull.len()]);
+            draw_triangle(middle * meter(1.), start * meter(1.), end * meter(1.), fill);
+            draw_line(meter(start.x), meter(start.y), meter(end.x), meter(end.y), 3., self.colour);
+        }
+    }
+
+    fn to_line(&self, index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        let join = |handles: &[usize]| handles.iter().filter_map(|handle| index_of(*handle)).map(|index| index.to_string()).collect::<Vec<String>>().join(",");
+        let springs: Vec<String> = self.springs.iter()
+            .filter_map(|spring| Some(format!("{}:{}:{}", index_of(spring.a)?, index_of(spring.b)?, spring.rest_length)))
+            .collect();
+        Some(format!("softbody points={} hull={} springs={} stiffness={} damping={} pressure={} area={} colour={},{},{},{}",
+                     join(&self.points), join(&self.hull), springs.join(","), self.stiffness, self.damping, self.pressure,
+                     self.rest_area, self.colour.r, self.colour.g, self.colour.b, self.colour.a))
+    }
+}
+
+//The point positions, the springs between them (as indexes), and the hull (as indexes in order) of a circle.
+//A balloon has no springs through the middle, so only the gas holds it up
+fn circle_lattice(centre: Vec2, radius: f32, balloon: bool) -> (Vec<Vec2>, Vec<(usize, usize)>, Vec<usize>) {
+    let count = ((TAU * radius / SOFT_SPACING).round() as usize).clamp(12, 32);
+    let mut positions: Vec<Vec2> = (0..count).map(|index| centre + Vec2::from_angle(index as f32 / count as f32 * TAU) * radius).collect();
+    let hull: Vec<usize> = (0..count).collect();
+    let mut springs = Vec::new();
+    for index in 0..count {
+        //Springs around the edge, and to the point after next to stop the edge folding
+        springs.push((index, (index + 1) % count));
+        springs.push((index, (index + 2) % count));
+    }
+    if !balloon {
+        //Spokes from the middle point to every point on the edge
+        positions.push(centre);
+        for index in 0..count {
+            springs.push((count, index));
+        }
+    }
+    (positions, springs, hull)
+}
+
+//The point positions, springs and hull of a rectangle, as a grid with springs across the diagonals so it can't shear flat
+fn rectangle_lattice(corner: Vec2, size: Vec2) -> (Vec<Vec2>, Vec<(usize, usize)>, Vec<usize>) {
+    let columns = ((size.x / SOFT_SPACING).round() as usize).max(1) + 1;
+    let rows = ((size.y / SOFT_SPACING).round() as usize).max(1) + 1;
+    let index = |column: usize, row: usize| row * columns + column;
+
+    let mut positions = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            positions.push(corner + size * Vec2::new(column as f32 / (columns - 1) as f32, row as f32 / (rows - 1) as f32));
+        }
+    }
+
+    let mut springs = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            if column + 1 < columns { springs.push((index(column, row), index(column + 1, row))); }
+            if row + 1 < rows { springs.push((index(column, row), index(column, row + 1))); }
+            if column + 1 < columns && row + 1 < rows {
+                springs.push((index(column, row), index(column + 1, row + 1)));
+                springs.push((index(column + 1, row), index(column, row + 1)));
+            }
+        }
+    }
+
+    //Go round the outside: along the top, down the right, back along the bottom, and up the left
+    let mut hull = Vec::new();
+    hull.extend((0..columns).map(|column| index(column, 0)));
+    hull.extend((1..rows).map(|row| index(columns - 1, row)));
+    hull.extend((0..columns - 1).rev().map(|column| index(column, rows - 1)));
+    hull.extend((1..rows - 1).rev().map(|row| index(0, row)));
+    (positions, springs, hull)
+}
+
+//Turn a circle or rectangle into a soft body with the same mass, adding its point masses to the objects.
+//The original object is removed, and the point nearest its centre takes its handle, so any springs, joints or actuators
+//on the object are moved onto the soft body. Returns None for shapes that can't be made soft
+#[allow(dead_code)]
+pub(crate) fn make_soft_body(objects: &mut Vec<Box<dyn PhysicsObject>>, index: usize, balloon: bool) -> Option<SoftBody> {
+    let object = &mut objects[index];
+    let mass = object.get_material().mass;
+    let velocity = object.get_velocity();
+    let gravity = *object.get_gravity();
+    let (id, pos, measurements, colour) = {
+        let shape = object.get_render_shape_reference();
+        (shape.get_id().to_string(), *shape.get_pos(), shape.get_measurements(), shape.get_colour())
+    };
+    let (positions, springs, hull) = match id.as_str() {
+        "Circle" => circle_lattice(pos, measurements.0, balloon),
+        "Rectangle" => {
+            let corner = pos.min(pos + Vec2::new(measurements.0, measurements.1));
+            rectangle_lattice(corner, Vec2::new(measurements.0, measurements.1).abs())
+        }
+        _ => return None,
+    };
+    let (handle, centre) = (object.get_handle(), object.get_render_shape_reference().get_centre());
+    objects.remove(index);
+
+    //Share the mass between the points, and make the springs as stiff as they can be for that mass
+    let point_mass = mass / positions.len() as f32;
+    let shortest = springs.iter().map(|(a, b)| positions[*a].distance(positions[*b])).fold(f32::MAX, f32::min);
+    let point_radius = shortest * 0.3;
+    let nearest = (0..positions.len()).min_by(|a, b| positions[*a].distance(centre).total_cmp(&positions[*b].distance(centre))).unwrap();
+    let mut handles = Vec::new();
+    for (point_index, position) in positions.iter().enumerate() {
+        let shape = Circle::new(*position, point_radius, colour, BLACK);
+        let area = shape.get_area();
+        let mut point = Object::new(shape, Material::new(point_mass, area), PhysicsType::Dynamic);
+        point.dx = velocity.x;
+        point.dy = velocity.y;
+        *point.get_gravity() = gravity;
+        if point_index == nearest {
+            point.handle = handle;
+        }
+        handles.push(point.get_handle());
+        objects.push(Box::new(point));
+    }
+
+    let stiffness = STIFFNESS_PER_KG * point_mass;
+    let hull_positions: Vec<Vec2> = hull.iter().map(|index| positions[*index]).collect();
+    let rest_area = get_polygon_area(&hull_positions);
+    //Enough gas to stretch the edge springs by about a tenth of their length. The tension round a ring is T = P r
+    let pressure = if balloon { 0.1 * stiffness * shortest / (rest_area / PI).sqrt() } else { 0.0 };
+    let springs = springs.iter()
+        .map(|(a, b)| SoftSpring { a: handles[*a], b: handles[*b], rest_length: positions[*a].distance(positions[*b]) })
+        .collect();
+    let hull = hull.iter().map(|index| handles[*index]).collect();
+    Some(SoftBody::new(handles, hull, springs, stiffness, DAMPING_PER_KG * point_mass, (pressure, rest_area), colour))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+    use macroquad::math::Vec2;
+    use crate::objects::events::EventLog;
+    use crate::objects::forces::Spring;
+    use crate::objects::scene::Scene;
+    use super::make_soft_body;
+
+    #[test]
+    fn small_objects_cant_slip_between_the_points() {
+        //A 4 m balloon has 32 points about 0.4 m apart, which a 0.1 m ball fits between
+        let angle = TAU / 64.;
+        let start = Vec2::from_angle(angle) * 2.6;
+        let mut scene = Scene::load_test("soft_gap", &format!("circle x=0 y=0 radius=2 mass=1 type=dynamic gravity=0 air=false\n\
+            circle x={} y={} radius=0.05 mass=0.1 type=dynamic gravity=0 air=false vx={} vy={}\n",
+            start.x, start.y, -5. * angle.cos(), -5. * angle.sin()));
+        let balloon = make_soft_body(&mut scene.objects, 0, true).unwrap();
+        scene.forces.push(Box::new(balloon));
+        //Half a second is long enough for the ball to reach the middle if nothing stops it
+        scene.run(&mut EventLog::new(), 30);
+
+        //The ball can dent the balloon and push it along, but it must still be outside the hull (crossing number test)
+        let ball = scene.objects[0].get_render_shape_reference().get_centre();
+        let hull: Vec<Vec2> = scene.objects[1..].iter_mut().map(|point| point.get_render_shape_reference().get_centre()).collect();
+        let crossings = (0..hull.len()).filter(|index| {
+            let (a, b) = (hull[*index], hull[(index + 1) % hull.len()]);
+            (a.y > ball.y) != (b.y > ball.y) && ball.x < a.x + (ball.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        }).count();
+        assert_eq!(crossings % 2, 0, "the ball got inside the balloon, to {}", ball);
+    }
+
+    #[test]
+    fn springs_move_onto_the_soft_body() {
+        let mut scene = Scene::load_test("soft_spring", "circle x=0 y=0 radius=1 mass=1 type=dynamic\n\
+            spring object=0 x=0 y=-2 stiffness=5 length=2\n");
+        let handle = scene.objects[0].get_handle();
+        let soft_body = make_soft_body(&mut scene.objects, 0, false).unwrap();
+
+        //The original circle is gone, and the middle point has its handle, so the spring holds the soft body up
+        assert_eq!(scene.objects.len(), soft_body.points.len());
+        let middle = scene.objects.iter_mut().find(|object| object.get_handle() == handle).unwrap();
+        assert_eq!(middle.get_render_shape_reference().get_centre(), Vec2::ZERO);
+        let spring = crate::objects::forces::find_force::<Spring>(&mut scene.forces).unwrap();
+        assert_eq!(spring.handle, handle);
+    }
+}
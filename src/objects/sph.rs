@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use macroquad::color::{Color, YELLOW};
+use macroquad::math::Vec2;
+use macroquad::shapes::draw_circle;
+use macroquad::text::draw_text;
+use crate::measurements::{dt, meter, Point, QuadTree, Rect};
+use crate::objects::forces::{Body, ForceGenerator};
+use crate::objects::particles::get_contact;
+
+//The smoothing radius is this many times the spacing between particles, so each one has about 12 neighbours
+const SMOOTHING_RATIO: f32 = 2.0;
+//The fastest a particle can move, as a fraction of the speed of sound
+const MAX_SPEED_RATIO: f32 = 0.5;
+//The most steps the fluid is split into each frame, so it can't freeze the program
+const MAX_SUBSTEPS: usize = 40;
+//The most time (s) the fluid can fall behind the rest of the simulation. Time it can't catch up in later frames is carried
+//over up to this, and anything more is dropped (slowing the fluid down), so it can't fall further and further behind
+const MAX_LAG: f32 = 0.25;
+//Particles further than this (m) from where the fluid started are removed, so fluid falling off the world doesn't slow the program down
+const MAX_DISTANCE: f32 = 50.0;
+//Neighbours are looked for this fraction of the smoothing radius further out, so the same lists can be used for several substeps
+//until a particle has moved half of the extra distance, instead of building a new QuadTree every substep
+const NEIGHBOUR_SKIN: f32 = 0.3;
+//Fluid particles aren't objects, so they are given a handle no object can have
+const FLUID_HANDLE: usize = usize::MAX;
+
+//A single particle of the fluid, carrying a little of its mass
+struct SphParticle {
+    pos: Vec2,
+    velocity: Vec2,
+    density: f32,
+    pressure: f32,
+}
+
+//The particles and boundary points near every particle, and where the particles were when they were found
+struct Neighbours {
+    particles: Vec<Vec<usize>>,
+    boundary: Vec<Vec<usize>>,
+    positions: Vec<Vec2>,
+}
+
+//A point on the surface of an object near the fluid, which counts as fluid when finding the density and pressure,
+//so objects are pushed by the pressure of the fluid around them (e.g. so they float)
+struct BoundaryPoint {
+    pos: Vec2,
+    //The index of the object in the bodies list
+    body: usize,
+    //How much fluid the point stands in for (kg), so a surface counts the same however closely the points are spaced
+    mass: f32,
+}
+
+//A fluid made of particles, using smoothed-particle hydrodynamics (SPH). Each particle's density is found from the particles
+//around it, the pressure pushes particles from dense areas to less dense ones, and viscosity smooths out their velocities.
+//The particles are pushed by every other force generator, and the surfaces of objects count as fluid so objects are pushed
+//by the pressure around them (so they float or sink) and push the fluid back
+pub(crate) struct SphFluid {
+    //The block the fluid started as, which is what is written to scene files
+    pub(crate) pos: Vec2,
+    pub(crate) size: Vec2,
+    pub(crate) spacing: f32,
+    //The density (kg/m^2) the fluid settles at
+    pub(crate) rest_density: f32,
+    //The speed of sound (m/s) in the fluid, how strongly it pushes back when it is squashed (p = c^2 (density - rest density))
+    pub(crate) sound_speed: f32,
+    pub(crate) viscosity: f32,
+    //The acceleration (m/s^2) of gravity on the particles, the same as an object's gravity
+    pub(crate) gravity: f32,
+    particles: Vec<SphParticle>,
+    particle_mass: f32,
+    //The force on every object from the fluid this step
+    body_forces: HashMap<usize, Vec2>,
+    //The time (s) the fluid is behind the rest of the simulation, and whether it fell so far behind that time was dropped
+    lag: f32,
+    dropped: bool,
+}
+
+//The 2D poly6 kernel, used for finding the density: W = 4 / (pi h^8) (h^2 - r^2)^3
+fn kernel(distance_sqr: f32, h: f32) -> f32 {
+    if distance_sqr >= h * h {
+        return 0.0;
+    }
+    4.0 / (PI * h.powi(8)) * (h * h - distance_sqr).powi(3)
+}
+
+//The size of the gradient of the 2D spiky kernel, used for pressure as it doesn't go to 0 when particles are close
+fn spiky_gradient(distance: f32, h: f32) -> f32 {
+    if distance >= h {
+        return 0.0;
+    }
+    -30.0 / (PI * h.powi(5)) * (h - distance).powi(2)
+}
+
+//The laplacian of the 2D viscosity kernel
+fn viscosity_laplacian(distance: f32, h: f32) -> f32 {
+    if distance >= h {
+        return 0.0;
+    }
+    40.0 / (PI * h.powi(5)) * (h - distance)
+}
+
+//Put every point into a QuadTree, so the neighbours of each particle can be found quickly
+fn build_quad_tree(positions: &[Vec2], spacing: f32) -> QuadTree {
+    let corner1 = positions.iter().fold(Vec2::splat(f32::MAX), |corner, pos| corner.min(*pos));
+    let corner2 = positions.iter().fold(Vec2::splat(f32::MIN), |corner, pos| corner.max(*pos));
+    let centre = (corner1 + corner2) / 2.;
+    //Points outside the tree are left out of it, so it is made a little bigger than the points in case of rounding errors
+    let half_size = (corner2 - corner1) / 2. + Vec2::splat(spacing);
+    let mut qtree = QuadTree::new_unique(Rect::new(centre.x, centre.y, half_size.x, half_size.y), 8);
+    for (index, pos) in positions.iter().enumerate() {
+        qtree.insert(Point::new(pos.x, pos.y, index));
+    }
+    qtree
+}
+
+impl SphFluid {
+    //Fill a block with fluid particles in a grid, turning a negative width or height into a positive one
+    pub(crate) fn new(pos: Vec2, size: Vec2, spacing: f32, rest_density: f32, sound_speed: f32, viscosity: f32) -> SphFluid {
+        let pos = pos.min(pos + size);
+        let size = size.abs();
+        let mut particles = Vec::new();
+        let columns = (size.x / spacing).floor().max(1.0) as usize;
+        let rows = (size.y / spacing).floor().max(1.0) as usize;
+        for column in 0..columns {
+            for row in 0..rows {
+                let particle_pos = pos + Vec2::new(column as f32 + 0.5, row as f32 + 0.5) * spacing;
+                particles.push(SphParticle { pos: particle_pos, velocity: Vec2::ZERO, density: rest_density, pressure: 0.0 });
+            }
+        }
+
+        //Choose the mass so a particle in the middle of the grid is exactly at the rest density, so the fluid starts still
+        let h = spacing * SMOOTHING_RATIO;
+        let reach = SMOOTHING_RATIO.ceil() as i32;
+        let mut total = 0.0;
+        for x in -reach..=reach {
+            for y in -reach..=reach {
+                total += kernel((Vec2::new(x as f32, y as f32) * spacing).length_squared(), h);
+            }
+        }
+        SphFluid { pos, size, spacing, rest_density, sound_speed, viscosity, gravity: 9.81, particles, particle_mass: rest_density / total,
+                   body_forces: HashMap::new(), lag: 0.0, dropped: false }
+    }
+
+    //Describe a particle the same way as an object, so the other generators can push it
+    fn get_body(&self, particle: &SphParticle) -> Body {
+        Body {
+            handle: FLUID_HANDLE,
+            centre: particle.pos,
+            velocity: particle.velocity,
+            mass: self.particle_mass,
+            area: self.spacing * self.spacing,
+            gravity: self.gravity,
+            drag_coefficient: 0.0,
+            do_air_resistance: false,
+            half_size: Vec2::splat(self.spacing / 2.),
+            circle: true,
+            charge: 0.0,
+            dynamic: true,
+        }
+    }
+
+    //Put points along the surface of every object close to the fluid, spaced the same as the particles
+    fn get_boundary(&self, bodies: &[Body]) -> Vec<BoundaryPoint> {
+        let h = self.spacing * SMOOTHING_RATIO;
+        let corner1 = self.particles.iter().fold(Vec2::splat(f32::MAX), |corner, particle| corner.min(particle.pos)) - 2.0 * h;
+        let corner2 = self.particles.iter().fold(Vec2::splat(f32::MIN), |corner, particle| corner.max(particle.pos)) + 2.0 * h;
+        let mut boundary = Vec::new();
+        for (index, body) in bodies.iter().enumerate() {
+            let mut points = Vec::new();
+            if body.circle {
+                let count = (2.0 * PI * body.half_size.x / self.spacing).ceil().max(3.0) as usize;
+                for step in 0..count {
+                    points.push(body.centre + Vec2::from_angle(2.0 * PI * step as f32 / count as f32) * body.half_size.x);
+                }
+            } else {
+                let corners = [Vec2::new(-1.0, -1.0), Vec2::new(1.0, -1.0), Vec2::new(1.0, 1.0), Vec2::new(-1.0, 1.0)]
+                    .map(|corner| body.centre + corner * body.half_size);
+                for side in 0..4 {
+                    let (start, end) = (corners[side], corners[(side + 1) % 4]);
+                    let count = (start.distance(end) / self.spacing).ceil().max(1.0) as usize;
+                    for step in 0..count {
+                        points.push(start.lerp(end, step as f32 / count as f32));
+                    }
+                }
+            }
+            //Only the points near the fluid are needed, but the ones just outside are kept to find the mass of the others
+            points.retain(|point| point.cmpge(corner1).all() && point.cmple(corner2).all());
+            //Points closer together stand in for less fluid each (m = rest density / sum of W)
+            for point in &points {
+                let total: f32 = points.iter().map(|other| kernel(point.distance_squared(*other), h)).sum();
+                boundary.push(BoundaryPoint { pos: *point, body: index, mass: self.rest_density / total });
+            }
+        }
+        boundary
+    }
+
+    //Find the particles and boundary points within reach of every particle. The kernels are 0 past the smoothing radius,
+    //so the extra ones found because of the skin don't change anything
+    fn find_neighbours(&self, boundary_tree: &mut QuadTree) -> Neighbours {
+        let reach = self.spacing * SMOOTHING_RATIO * (1.0 + NEIGHBOUR_SKIN);
+        let positions: Vec<Vec2> = self.particles.iter().map(|particle| particle.pos).collect();
+        let mut qtree = build_quad_tree(&positions, self.spacing);
+        let find = |tree: &mut QuadTree| -> Vec<Vec<usize>> {
+            positions.iter().map(|pos| tree.query(&Rect::new(pos.x, pos.y, reach, reach)).iter().map(|point| point.index).collect()).collect()
+        };
+        Neighbours { particles: find(&mut qtree), boundary: find(boundary_tree), positions }
+    }
+
+    //Check if any particle has moved far enough that the neighbour lists could be missing something
+    fn neighbours_moved(&self, neighbours: &Neighbours) -> bool {
+        let limit = self.spacing * SMOOTHING_RATIO * NEIGHBOUR_SKIN / 2.0;
+        self.particles.iter().zip(&neighbours.positions).any(|(particle, pos)| particle.pos.distance_squared(*pos) > limit * limit)
+    }
+
+    //Find the acceleration of every particle from the pressure and viscosity of the fluid, the objects around it and the
+    //other generators, and the force of the fluid on each of the objects
+    fn get_accelerations(&mut self, external: &[Vec2], boundary: &[BoundaryPoint], neighbours: &Neighbours,
+                         body_velocities: &[Vec2]) -> (Vec<Vec2>, Vec<Vec2>) {
+        let h = self.spacing * SMOOTHING_RATIO;
+        let (boundary_neighbours, neighbours) = (&neighbours.boundary, &neighbours.particles);
+
+        //Find the density of every particle from the particles around it, and the pressure from how squashed it is.
+        //Negative pressures are ignored, so the particles don't clump together at the surface. A particle that has splashed away
+        //from the rest has almost no density, so it is never counted as less than the rest density or dividing by it would fling it
+        for index in 0..self.particles.len() {
+            let pos = self.particles[index].pos;
+            let density: f32 = neighbours[index].iter()
+                .map(|other| self.particle_mass * kernel(pos.distance_squared(self.particles[*other].pos), h))
+                .sum::<f32>()
+                + boundary_neighbours[index].iter().map(|point| boundary[*point].mass * kernel(pos.distance_squared(boundary[*point].pos), h))
+                .sum::<f32>();
+            self.particles[index].density = density.max(self.rest_density);
+            self.particles[index].pressure = self.sound_speed * self.sound_speed * (self.particles[index].density - self.rest_density);
+        }
+
+        //Add up the pressure and viscosity forces on every particle, and accelerate it by a = F / density
+        let mut accelerations = Vec::with_capacity(self.particles.len());
+        let mut body_forces = vec![Vec2::ZERO; body_velocities.len()];
+        for (index, particle) in self.particles.iter().enumerate() {
+            let mut force = Vec2::ZERO;
+            for other_index in &neighbours[index] {
+                let other = &self.particles[*other_index];
+                if *other_index == index {
+                    continue;
+                }
+                //Particles pushed onto the same point by an object are pushed apart sideways, in an order that is always the same
+                let offset = particle.pos - other.pos;
+                let distance = offset.length();
+                let direction = if distance > 0.0 { offset / distance } else if index < *other_index { Vec2::X } else { -Vec2::X };
+                force -= direction * self.particle_mass * (particle.pressure + other.pressure) / (2.0 * other.density)
+                    * spiky_gradient(distance, h);
+                force += (other.velocity - particle.velocity) * self.viscosity * self.particle_mass / other.density
+                    * viscosity_laplacian(distance, h);
+            }
+            //The surface of an object pushes back with the same pressure as the particle, and slows it down by viscosity.
+            //The object is pushed the opposite way by the same force
+            for point_index in &boundary_neighbours[index] {
+                let point = &boundary[*point_index];
+                let offset = particle.pos - point.pos;
+                let distance = offset.length();
+                let direction = if distance > 0.0 { offset / distance } else { Vec2::ZERO };
+                let point_force = -direction * point.mass * particle.pressure / particle.density * spiky_gradient(distance, h)
+                    + (body_velocities[point.body] - particle.velocity) * self.viscosity * point.mass / particle.density
+                    * viscosity_laplacian(distance, h);
+                force += point_force;
+                body_forces[point.body] -= point_force / particle.density * self.particle_mass;
+            }
+            accelerations.push(force / particle.density + external[index]);
+        }
+        (accelerations, body_forces)
+    }
+
+    //Move the fluid forward by part of a step, then push the particles out of any objects they have gone into.
+    //The objects' velocities are changed as the particles hit them, so a light object isn't pushed faster than the fluid
+    fn move_particles(&mut self, accelerations: &[Vec2], bodies: &[Body], velocities: &mut [Vec2], timestep: f32) {
+        let radius = self.spacing / 2.;
+        for (particle, acceleration) in self.particles.iter_mut().zip(accelerations) {
+            particle.velocity += *acceleration * timestep;
+            //The fluid is only nearly incompressible while it moves much slower than sound, so splashes are kept below that
+            particle.velocity = particle.velocity.clamp_length_max(self.sound_speed * MAX_SPEED_RATIO);
+            particle.pos += particle.velocity * timestep;
+            //Push the particle out of any object it has gone into, and stop them moving into each other (a perfectly
+            //inelastic collision, sharing the change in velocity by mass). Objects that can't move only stop the particle
+            for (body, body_velocity) in bodies.iter().zip(velocities.iter_mut()) {
+                let Some((normal, depth)) = get_contact(body, particle.pos, radius) else { continue; };
+                particle.pos += normal * depth;
+                let closing_speed = (particle.velocity - *body_velocity).dot(normal);
+                if closing_speed >= 0.0 {
+                    continue;
+                }
+                let particle_share = if body.dynamic { body.mass / (body.mass + self.particle_mass) } else { 1.0 };
+                particle.velocity -= normal * closing_speed * particle_share;
+                if body.dynamic {
+                    *body_velocity += normal * closing_speed * (1.0 - particle_share);
+                }
+            }
+        }
+    }
+}
+
+impl ForceGenerator for SphFluid {
+    //The push of the fluid on an object, from the particles that hit it this step
+    fn get_force(&self, body: &Body) -> Vec2 {
+        self.body_forces.get(&body.handle).copied().unwrap_or(Vec2::ZERO)
+    }
+
+    //Wake any object the fluid is pushing, e.g. one floating on the surface
+    fn keep_awake(&self, body: &Body) -> bool {
+        self.body_forces.contains_key(&body.handle)
+    }
+
+    fn update(&mut self, others: &dyn Fn(&Body) -> Vec2, bodies: &[Body]) {
+        let start = self.pos + self.size / 2.;
+        self.particles.retain(|particle| particle.pos.distance(start) < MAX_DISTANCE);
+        self.body_forces.clear();
+        if self.particles.is_empty() {
+            self.lag = 0.0;
+            return;
+        }
+
+        //The other generators (e.g. gravity) are only worked out once a step, as they change slowly
+        let external: Vec<Vec2> = self.particles.iter().map(|particle| others(&self.get_body(particle)) / self.particle_mass).collect();
+
+        //Split the step up so pressure waves and fast particles can't cross more than part of a particle each time
+        //(the CFL condition), and so neither the pressure nor the viscosity can overshoot and add energy
+        let h = self.spacing * SMOOTHING_RATIO;
+        let viscous_limit = 0.125 * h * h * self.rest_density / self.viscosity.max(f32::EPSILON);
+        let boundary = self.get_boundary(bodies);
+        let positions: Vec<Vec2> = boundary.iter().map(|point| point.pos).collect();
+        let mut boundary_tree = build_quad_tree(&positions, self.spacing);
+        let mut velocities: Vec<Vec2> = bodies.iter().map(|body| body.velocity).collect();
+        let mut remaining = dt() + self.lag;
+        let mut neighbours = self.find_neighbours(&mut boundary_tree);
+        for _ in 0..MAX_SUBSTEPS {
+            if self.neighbours_moved(&neighbours) {
+                neighbours = self.find_neighbours(&mut boundary_tree);
+            }
+            let (accelerations, body_forces) = self.get_accelerations(&external, &boundary, &neighbours, &velocities);
+            let fastest = self.particles.iter().fold(self.sound_speed, |fastest, particle| fastest.max(particle.velocity.length()));
+            let strongest = accelerations.iter().fold(0.0f32, |strongest, acceleration| strongest.max(acceleration.length()));
+            let timestep = (0.25 * h / fastest).min(0.25 * (h / strongest.max(f32::EPSILON)).sqrt()).min(viscous_limit).min(remaining);
+            for ((body, velocity), force) in bodies.iter().zip(velocities.iter_mut()).zip(body_forces) {
+                if body.dynamic {
+                    *velocity += force / body.mass * timestep;
+                }
+            }
+            self.move_particles(&accelerations, bodies, &mut velocities, timestep);
+            remaining -= timestep;
+            if remaining <= 0.0 {
+                break;
+            }
+        }
+        //If the fluid needs too many steps, the rest of the time is carried over to the next frame rather than making it unstable
+        self.lag = remaining.max(0.0);
+        self.dropped = self.lag > MAX_LAG;
+        self.lag = self.lag.min(MAX_LAG);
+
+        //Turn how much the fluid has changed each object's velocity into a force over the step, F = m dv / dt
+        for (body, velocity) in bodies.iter().zip(velocities) {
+            if body.dynamic && velocity != body.velocity {
+                self.body_forces.insert(body.handle, (velocity - body.velocity) * body.mass / dt());
+            }
+        }
+    }
+
+    //Draw every particle, lighter the faster it is moving
+    fn draw(&self, _bodies: &[Body]) {
+        for particle in &self.particles {
+            let speed = (particle.velocity.length() / 5.0).min(1.0);
+            let colour = Color::new(0.2 + 0.6 * speed, 0.5 + 0.4 * speed, 1.0, 0.9);
+            draw_circle(meter(particle.pos.x), meter(particle.pos.y), meter(self.spacing * 0.6), colour);
+        }
+        if self.dropped && let Some(particle) = self.particles.first() {
+            draw_text("Fluid can't keep up, running slowly", meter(particle.pos.x), meter(particle.pos.y) - 10., 20., YELLOW);
+        }
+    }
+
+    fn hash_state(&self, add: &mut dyn FnMut(u32)) {
+        add(self.lag.to_bits());
+        for particle in &self.particles {
+            for value in [particle.pos.x, particle.pos.y, particle.velocity.x, particle.velocity.y] {
+                add(value.to_bits());
+            }
+        }
+    }
+
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        Some(format!("sph x={} y={} width={} height={} spacing={} density={} sound_speed={} viscosity={} gravity={}", self.pos.x, self.pos.y,
+                     self.size.x, self.size.y, self.spacing, self.rest_density, self.sound_speed, self.viscosity, self.gravity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::math::Vec2;
+    use crate::measurements::{dt, set_fixed_dt};
+    use crate::objects::forces::ForceGenerator;
+    use super::{SphFluid, MAX_LAG};
+
+    #[test]
+    fn fluid_carries_over_time_it_cant_catch_up() {
+        set_fixed_dt(Some(1. / 60.));
+        //An easily handled fluid keeps up with every frame
+        let mut fluid = SphFluid::new(Vec2::ZERO, Vec2::splat(0.5), 0.1, 1.0, 15.0, 0.1);
+        fluid.update(&|_| Vec2::ZERO, &[]);
+        assert_eq!(fluid.lag, 0.0);
+
+        //A very stiff one needs tiny substeps, so it falls behind and catches up in later frames until it reaches the limit
+        let mut fluid = SphFluid::new(Vec2::ZERO, Vec2::splat(0.5), 0.1, 1.0, 1000.0, 0.1);
+        fluid.update(&|_| Vec2::ZERO, &[]);
+        let first = fluid.lag;
+        assert!(first > 0.0 && first < dt());
+        fluid.update(&|_| Vec2::ZERO, &[]);
+        assert!(fluid.lag > first && !fluid.dropped);
+        for _ in 0..30 {
+            fluid.update(&|_| Vec2::ZERO, &[]);
+        }
+        assert_eq!(fluid.lag, MAX_LAG);
+        assert!(fluid.dropped);
+    }
+}
@@ -0,0 +1,360 @@
+use macroquad::math::Vec2;
+use crate::measurements::{dt, get_random_state, meter, seed_random, set_random_state, Point, QuadTree, Rect};
+use crate::objects::events::{EventLog, EventType};
+use crate::objects::forces::{find_force, get_bodies, total_force, Body, ForceGenerator};
+use crate::objects::fracture::fracture_objects;
+use crate::objects::joints::JointSystem;
+use crate::objects::physics::{PhysicsObject, PhysicsType, SLEEP_TIME};
+
+//Settings for how the simulation should be stepped
+#[derive(Clone, Copy)]
+pub(crate) struct SimulationSettings {
+    pub(crate) deterministic: bool,
+    pub(crate) seed: u64,
+    pub(crate) timestep: f32,
+    //The state of the random number generator when the scene was saved, so a loaded scene carries on with the same numbers
+    pub(crate) random_state: Option<u64>,
+}
+
+impl SimulationSettings {
+    pub(crate) fn new() -> SimulationSettings {
+        SimulationSettings { deterministic: false, seed: 0, timestep: 1. / 60., random_state: None }
+    }
+
+    //Start the random numbers from the saved state if there is one, otherwise from the seed
+    pub(crate) fn start_random(&self) {
+        match self.random_state {
+            Some(state) => set_random_state(state),
+            None => seed_random(self.seed),
+        }
+    }
+}
+
+//Create a QuadTree that covers every object, with each object's position inserted into it
+pub(crate) fn build_quad_tree(objects: &mut [Box<dyn PhysicsObject>]) -> QuadTree {
+    //Find the corners of the area covering all the objects
+    let mut corner1 = Vec2::new(0., 0.);
+    let mut corner2 = Vec2::new(0., 0.);
+    for (index, object) in objects.iter_mut().enumerate() {
+        let p = *object.get_render_shape_reference().get_pos();
+        let p = Vec2::new(meter(p.x), meter(p.y));
+        if index == 0 {
+            corner1 = p;
+            corner2 = p;
+        }
+        corner1 = corner1.min(p);
+        corner2 = corner2.max(p);
+    }
+
+    let w = (corner2.x - corner1.x).abs();
+    let h = (corner2.y - corner1.y).abs();
+    //Leave a pixel around the edge, so rounding can't leave the objects on the edge outside the tree
+    let boundary = Rect::new(corner1.x + w/2., corner2.y - h/2., w/2. + 1., h/2. + 1.);
+
+    let mut qtree = QuadTree::new(boundary, 4);
+    for (index, object) in objects.iter_mut().enumerate() {
+        let p = *object.get_render_shape_reference().get_pos();
+        qtree.insert(Point::new(meter(p.x), meter(p.y), index));
+    }
+    qtree
+}
+
+//Find every pair of objects that might be touching. Each pair is only given once, and the pairs are sorted
+//by the objects' handles, so collisions are always resolved in the same order
+pub(crate) fn find_pairs(objects: &mut [Box<dyn PhysicsObject>]) -> Vec<(usize, usize)> {
+    let mut qtree = build_quad_tree(objects);
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+
+    for index in 0..objects.len() {
+        for point in objects[index].get_render_shape_reference().detect_near_object(&mut qtree) {
+            if point.index != index && point.index < objects.len() {
+                pairs.push((index.min(point.index), index.max(point.index)));
+            }
+        }
+    }
+
+    let handles: Vec<usize> = objects.iter().map(|object| object.get_handle()).collect();
+    pairs.sort_by_key(|(a, b)| (handles[*a], handles[*b]));
+    pairs.dedup();
+    pairs
+}
+
+//Check every pair of objects that might be touching, and push apart any that overlap
+pub(crate) fn resolve_collisions(objects: &mut [Box<dyn PhysicsObject>], events: &mut EventLog) {
+    for (a, b) in find_pairs(objects) {
+        let (left, right) = objects.split_at_mut(b);
+        left[a].check_collisions(&mut right[0], events);
+    }
+    events.end_step();
+}
+
+//Wake every sleeping object touching one of the given objects, as it may no longer be held up
+fn wake_touching(objects: &mut [Box<dyn PhysicsObject>], events: &EventLog, handles: &[usize]) {
+    let mut woken: Vec<usize> = Vec::new();
+    for (a, b) in events.get_contacts() {
+        if handles.contains(&a) { woken.push(b); }
+        if handles.contains(&b) { woken.push(a); }
+    }
+    for object in objects.iter_mut() {
+        if woken.contains(&object.get_handle()) {
+            object.wake();
+        }
+    }
+}
+
+//Wake anything resting on an object that has been moved (dragged, edited or driven along) since the last step
+fn wake_on_moved_objects(objects: &mut [Box<dyn PhysicsObject>], events: &EventLog) {
+    let mut moved: Vec<usize> = Vec::new();
+    for object in objects.iter_mut() {
+        let last_pos = object.get_sleep_state().2;
+        if !matches!(object.get_physics_type(), PhysicsType::Dynamic) && *object.get_render_shape_reference().get_pos() != last_pos {
+            moved.push(object.get_handle());
+        }
+    }
+    if !moved.is_empty() {
+        wake_touching(objects, events, &moved);
+    }
+}
+
+//Put resting objects to sleep in groups. Dynamic objects touching each other are in the same group, which only falls
+//asleep once every object in it has been still for long enough, so a stack sleeps as one instead of waking itself back up
+pub(crate) fn sleep_groups(objects: &mut [Box<dyn PhysicsObject>], events: &EventLog) {
+    fn find(group: &mut [usize], mut index: usize) -> usize {
+        while group[index] != index {
+            group[index] = group[group[index]];
+            index = group[index];
+        }
+        index
+    }
+
+    //Join the groups of every pair of touching dynamic objects. Static objects don't join groups, or everything on the floor would be one group
+    let handles: Vec<usize> = objects.iter().map(|object| object.get_handle()).collect();
+    let dynamic: Vec<bool> = objects.iter_mut().map(|object| matches!(object.get_physics_type(), PhysicsType::Dynamic)).collect();
+    let mut group: Vec<usize> = (0..objects.len()).collect();
+    for (a, b) in events.get_contacts() {
+        let (Some(a), Some(b)) = (handles.iter().position(|handle| *handle == a), handles.iter().position(|handle| *handle == b)) else { continue; };
+        if dynamic[a] && dynamic[b] {
+            let (root_a, root_b) = (find(&mut group, a), find(&mut group, b));
+            group[root_a] = root_b;
+        }
+    }
+
+    //A group can only sleep if every object in it is asleep, or has been still for long enough
+    let mut ready = vec![true; objects.len()];
+    for index in 0..objects.len() {
+        if dynamic[index] {
+            let (asleep, still, _) = objects[index].get_sleep_state();
+            let root = find(&mut group, index);
+            ready[root] &= asleep || still >= SLEEP_TIME;
+        }
+    }
+    for index in 0..objects.len() {
+        if dynamic[index] && !objects[index].get_sleeping() && ready[find(&mut group, index)] {
+            objects[index].sleep();
+        }
+    }
+}
+
+//Move the whole simulation forward by one step, with every force generator acting on the dynamic objects
+pub(crate) fn step(objects: &mut Vec<Box<dyn PhysicsObject>>, forces: &mut [Box<dyn ForceGenerator>], events: &mut EventLog) {
+    events.time += dt();
+    wake_on_moved_objects(objects, events);
+    for generator in forces.iter_mut() {
+        generator.drive(objects);
+    }
+    let bodies = get_bodies(objects);
+    for generator in forces.iter_mut() {
+        generator.prepare(&bodies);
+    }
+    //Split the generators around each one, so it can be pushed by all the others
+    for index in 0..forces.len() {
+        let (before, rest) = forces.split_at_mut(index);
+        let (generator, after) = rest.split_first_mut().unwrap();
+        let others = |body: &Body| total_force(before, body) + total_force(after, body);
+        generator.update(&others, &bodies);
+    }
+    //Log every joint that snapped, with the impulse it had to give over the step
+    if let Some(joints) = find_force::<JointSystem>(forces) {
+        for (a, b, tension, point) in joints.take_snaps() {
+            events.record_break(a, b, tension.abs() * dt(), point);
+        }
+    }
+    for (object, body) in objects.iter_mut().zip(&bodies) {
+        if object.get_sleeping() && forces.iter().any(|generator| generator.keep_awake(body)) {
+            object.wake();
+        }
+    }
+    for object in objects.iter_mut() {
+        object.physics_process(forces);
+    }
+    let event_count = events.events.len();
+    resolve_collisions(objects, events);
+    //Anything that stopped touching something may no longer be held up, e.g. if the object under it was deleted
+    let ended: Vec<usize> = events.events[event_count..].iter()
+        .filter(|event| event.event_type == EventType::EndContact)
+        .flat_map(|event| [event.object_1, event.object_2])
+        .collect();
+    for object in objects.iter_mut() {
+        if ended.contains(&object.get_handle()) {
+            object.wake();
+        }
+    }
+    sleep_groups(objects, events);
+    //Split anything that was hit hard enough to break
+    fracture_objects(objects);
+}
+
+//Create a checksum of the position, velocity and sleep state of every object (FNV-1a),
+//so two runs of the same scene can be checked to be identical
+pub(crate) fn checksum(objects: &mut [Box<dyn PhysicsObject>], forces: &[Box<dyn ForceGenerator>]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut add = |bits: u32| {
+        for byte in bits.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+
+    for object in objects.iter_mut() {
+        let pos = *object.get_render_shape_reference().get_pos();
+        let velocity = object.get_velocity();
+        add(pos.x.to_bits());
+        add(pos.y.to_bits());
+        add(velocity.x.to_bits());
+        add(velocity.y.to_bits());
+        add(object.get_sleeping() as u32);
+    }
+    //Particles, cloths and the like aren't objects, so each generator adds its own state
+    for generator in forces {
+        generator.hash_state(&mut add);
+    }
+    //Two runs that have drawn different random numbers will go on differently, even if nothing has moved yet
+    let random_state = get_random_state();
+    add(random_state as u32);
+    add((random_state >> 32) as u32);
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::math::Vec2;
+    use crate::objects::events::EventLog;
+    use crate::objects::physics::PhysicsType;
+    use crate::objects::scene::Scene;
+    use super::checksum;
+
+    const STACK: &str = "deterministic true\nseed 7\n\
+        rectangle x=-3 y=4 width=8 height=0.5 type=static friction=0.4\n\
+        rectangle x=0 y=2 width=0.5 height=0.5 type=dynamic\n\
+        rectangle x=0.1 y=1 width=0.5 height=0.5 type=dynamic bounce=0.3\n\
+        circle x=0.5 y=0 radius=0.3 type=dynamic vx=1\n\
+        circle x=-1 y=1 radius=0.2 type=dynamic vy=-2 friction=0.2\n";
+
+    #[test]
+    fn same_seed_gives_same_checksum() {
+        let mut checksums = Vec::new();
+        for _ in 0..2 {
+            let mut scene = Scene::load_test("stack", STACK);
+            scene.settings.start_random();
+            let start = checksum(&mut scene.objects, &scene.forces);
+            scene.run(&mut EventLog::new(), 300);
+            let end = checksum(&mut scene.objects, &scene.forces);
+            assert_ne!(start, end);
+            checksums.push(end);
+        }
+        assert_eq!(checksums[0], checksums[1]);
+    }
+
+    const BOX_STACK: &str = "rectangle x=-1 y=3 width=4 height=0.5 type=static\n\
+        rectangle x=0 y=2.5 width=0.5 height=0.5 type=dynamic\n\
+        rectangle x=0 y=2 width=0.5 height=0.5 type=dynamic\n\
+        rectangle x=0 y=1.5 width=0.5 height=0.5 type=dynamic\n";
+
+    #[test]
+    fn checksum_covers_force_generators() {
+        //A rope has no objects, so only its own points can tell the runs apart
+        const ROPE: &str = "deterministic true\nrope x=0 y=0 x2=1 y2=0\n";
+        let mut first = Scene::load_test("rope_first", ROPE);
+        let mut second = Scene::load_test("rope_second", ROPE);
+        first.run(&mut EventLog::new(), 10);
+        second.run(&mut EventLog::new(), 10);
+        assert_eq!(checksum(&mut first.objects, &first.forces), checksum(&mut second.objects, &second.forces));
+        second.run(&mut EventLog::new(), 1);
+        assert_ne!(checksum(&mut first.objects, &first.forces), checksum(&mut second.objects, &second.forces));
+    }
+
+    #[test]
+    fn stack_falls_asleep() {
+        let mut scene = Scene::load_test("box_stack", BOX_STACK);
+        scene.run(&mut EventLog::new(), 300);
+        for object in &mut scene.objects[1..] {
+            assert!(object.get_sleeping(), "a box in the stack is still awake");
+            assert_eq!(object.get_velocity(), Vec2::ZERO);
+        }
+    }
+
+    #[test]
+    fn sleeping_objects_fall_when_their_support_goes() {
+        //The floor is deleted in one run, and dragged away in the other
+        let remove: fn(&mut Scene) = |scene| { scene.objects.remove(0); };
+        let drag: fn(&mut Scene) = |scene| { scene.objects[0].get_render_shape_reference().get_pos().x += 10.; };
+        for (name, take_away) in [("support_deleted", remove), ("support_dragged", drag)] {
+            let mut scene = Scene::load_test(name, BOX_STACK);
+            let mut events = EventLog::new();
+            scene.run(&mut events, 300);
+            take_away(&mut scene);
+            scene.run(&mut events, 30);
+            for object in scene.objects.iter_mut() {
+                if !matches!(object.get_physics_type(), PhysicsType::Dynamic) {
+                    continue;
+                }
+                assert!(!object.get_sleeping(), "a box is still asleep after its support went ({})", name);
+                assert!(object.get_render_shape_reference().get_pos().y > 1.6, "a box was left floating ({})", name);
+            }
+        }
+    }
+
+    #[test]
+    fn joint_keeps_its_length() {
+        let mut scene = Scene::load_test("joint", "circle x=0 y=0 radius=0.1 type=static\ncircle x=1 y=0 radius=0.1 type=dynamic air=false\njoint a=0 b=1\n");
+        //A quarter of a swing takes about 0.59s, so the bob should be near the bottom, still a metre from the anchor
+        scene.run(&mut EventLog::new(), 35);
+        let bob = scene.objects[1].get_render_shape_reference().get_centre();
+        assert!((bob.length() - 1.0).abs() < 0.01, "bob is {} m from the anchor", bob.length());
+        assert!(bob.y > 0.9, "bob only fell to y = {}", bob.y);
+    }
+
+    #[test]
+    fn actuator_follows_its_path() {
+        let mut scene = Scene::load_test("actuator", "rectangle x=0 y=0 width=1 height=1 type=kinematic\n\
+            actuator object=0 mode=path repeat=once keys=0:0:0,1:2:0\n");
+        scene.run(&mut EventLog::new(), 30);
+        let middle = scene.objects[0].get_render_shape_reference().get_centre();
+        assert!(middle.distance(Vec2::new(1.0, 0.0)) < 0.01, "halfway through the path it is at {}", middle);
+        scene.run(&mut EventLog::new(), 60);
+        let end = scene.objects[0].get_render_shape_reference().get_centre();
+        assert!(end.distance(Vec2::new(2.0, 0.0)) < 0.01, "after the path it is at {}", end);
+    }
+
+    //Slide a box along the floor, returning its speed and how far it went
+    fn slide(friction: f32) -> (f32, f32) {
+        let mut scene = Scene::load_test(&format!("slide_{}", friction), &format!(
+            "rectangle x=-1 y=1 width=8 height=1 type=static friction={}\n\
+             rectangle x=0 y=0.5 width=0.5 height=0.5 type=dynamic air=false vx=3 friction={}\n", friction, friction));
+        let start = scene.objects[1].get_render_shape_reference().get_centre();
+        scene.run(&mut EventLog::new(), 60);
+        let end = scene.objects[1].get_render_shape_reference().get_centre();
+        (scene.objects[1].get_velocity().x, end.x - start.x)
+    }
+
+    #[test]
+    fn friction_stops_sliding() {
+        let (speed, distance) = slide(0.5);
+        let (free_speed, free_distance) = slide(0.0);
+        //v^2 / 2 mu g = 0.92 m before it stops
+        assert!(speed.abs() < 0.01, "box is still sliding at {} m/s", speed);
+        assert!((distance - 0.92).abs() < 0.1, "box slid {} m", distance);
+        assert!((free_speed - 3.0).abs() < 0.01, "frictionless box slowed to {} m/s", free_speed);
+        assert!((free_distance - 3.0).abs() < 0.01, "frictionless box only slid {} m", free_distance);
+    }
+}
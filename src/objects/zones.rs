@@ -0,0 +1,165 @@
+use macroquad::color::Color;
+use macroquad::math::Vec2;
+use macroquad::shapes::{draw_rectangle, draw_rectangle_lines};
+use crate::measurements::meter;
+use crate::objects::electrostatics::draw_arrow;
+use crate::objects::forces::{drag_force, Body, ForceGenerator, AIR_DENSITY};
+
+//The different ways a zone can push objects
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ZoneKind {
+    //Air moving at a speed (m/s) in a direction, like a fan or blower
+    Wind,
+    //The same acceleration (m/s^2) in a direction everywhere, like a conveyor or a current
+    Push,
+    //Acceleration (m/s^2) around the centre of the zone, anticlockwise on screen when positive
+    Vortex,
+    //Acceleration (m/s^2) away from the centre of the zone, or towards it when negative
+    Radial,
+}
+
+//A rectangle that pushes every dynamic object whose centre is inside it
+pub(crate) struct ForceZone {
+    //The top left corner and size of the zone, the same as a rectangle
+    pub(crate) pos: Vec2,
+    pub(crate) size: Vec2,
+    pub(crate) kind: ZoneKind,
+    pub(crate) strength: f32,
+    //The direction of wind and push zones (degrees, clockwise on screen from the right)
+    pub(crate) angle: f32,
+}
+
+impl ZoneKind {
+    pub(crate) fn get_name(&self) -> &str {
+        match self {
+            ZoneKind::Wind => "wind",
+            ZoneKind::Push => "push",
+            ZoneKind::Vortex => "vortex",
+            ZoneKind::Radial => "radial",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<ZoneKind> {
+        match name {
+            "wind" => Some(ZoneKind::Wind),
+            "push" => Some(ZoneKind::Push),
+            "vortex" => Some(ZoneKind::Vortex),
+            "radial" => Some(ZoneKind::Radial),
+            _ => None,
+        }
+    }
+
+    //The kind after this one, for swapping between them with a button
+    #[allow(dead_code)]
+    pub(crate) fn next(&self) -> ZoneKind {
+        match self {
+            ZoneKind::Wind => ZoneKind::Push,
+            ZoneKind::Push => ZoneKind::Vortex,
+            ZoneKind::Vortex => ZoneKind::Radial,
+            ZoneKind::Radial => ZoneKind::Wind,
+        }
+    }
+}
+
+impl ForceZone {
+    //Create a zone, turning a negative width or height (from drawing it backwards) into a positive one
+    pub(crate) fn new(pos: Vec2, size: Vec2, kind: ZoneKind, strength: f32, angle: f32) -> ForceZone {
+        ForceZone { pos: pos.min(pos + size), size: size.abs(), kind, strength, angle }
+    }
+
+    //Check if a point is inside the zone
+    fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.pos).all() && point.cmple(self.pos + self.size).all()
+    }
+
+    //The wind velocity (m/s), or the acceleration (m/s^2) for the other kinds, at a point in the zone
+    fn get_field(&self, point: Vec2) -> Vec2 {
+        let offset = point - (self.pos + self.size / 2.);
+        match self.kind {
+            ZoneKind::Wind | ZoneKind::Push => Vec2::from_angle(self.angle.to_radians()) * self.strength,
+            //The y axis points down, so this perpendicular goes anticlockwise on screen
+            ZoneKind::Vortex => offset.perp().normalize_or_zero() * -self.strength,
+            ZoneKind::Radial => offset.normalize_or_zero() * self.strength,
+        }
+    }
+}
+
+impl ForceGenerator for ForceZone {
+    fn get_force(&self, body: &Body) -> Vec2 {
+        if !self.contains(body.centre) {
+            return Vec2::ZERO;
+        }
+        let field = self.get_field(body.centre);
+        match self.kind {
+            //Wind pushes as drag on the velocity relative to the air, so objects can't go faster than it
+            ZoneKind::Wind => drag_force(body, body.velocity - field, AIR_DENSITY),
+            _ => field * body.mass,
+        }
+    }
+
+    //Draw the zone with arrows showing which way it pushes
+    fn draw(&self, _bodies: &[Body]) {
+        let colour = Color::from_rgba(150, 255, 150, 200);
+        draw_rectangle(meter(self.pos.x), meter(self.pos.y), meter(self.size.x), meter(self.size.y), Color::from_rgba(150, 255, 150, 30));
+        draw_rectangle_lines(meter(self.pos.x), meter(self.pos.y), meter(self.size.x), meter(self.size.y), 2., colour);
+        //One arrow for every half meter, at most 10 in each direction
+        let columns = (self.size.x * 2.).clamp(1., 10.) as usize;
+        let rows = (self.size.y * 2.).clamp(1., 10.) as usize;
+        for column in 0..columns {
+            for row in 0..rows {
+                let centre = self.pos + self.size * Vec2::new((column as f32 + 0.5) / columns as f32, (row as f32 + 0.5) / rows as f32);
+                let direction = self.get_field(centre).normalize_or_zero();
+                if direction != Vec2::ZERO {
+                    draw_arrow(centre - direction * 0.15, centre + direction * 0.15, colour);
+                }
+            }
+        }
+    }
+
+    fn to_line(&self, _index_of: &dyn Fn(usize) -> Option<usize>) -> Option<String> {
+        Some(format!("zone x={} y={} width={} height={} kind={} strength={} angle={}", self.pos.x, self.pos.y, self.size.x,
+                     self.size.y, self.kind.get_name(), self.strength, self.angle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::math::Vec2;
+    use crate::objects::events::EventLog;
+    use crate::objects::forces::{Body, ForceGenerator};
+    use crate::objects::scene::Scene;
+    use super::{ForceZone, ZoneKind};
+
+    #[test]
+    fn push_and_wind_zones_move_objects() {
+        //Push zones give every object the same acceleration, whatever its mass
+        let mut scene = Scene::load_test("push_zone", "zone x=-2 y=-2 width=4 height=4 kind=push strength=2 angle=90\n\
+            circle x=0 y=0 radius=0.1 mass=1 type=dynamic gravity=0 air=false\n\
+            circle x=1 y=0 radius=0.1 mass=5 type=dynamic gravity=0 air=false\n");
+        scene.run(&mut EventLog::new(), 30);
+        for object in &scene.objects {
+            assert!((object.get_velocity() - Vec2::new(0.0, 1.0)).length() < 1e-3);
+        }
+
+        //Wind speeds objects up towards its own speed, but never past it
+        let mut scene = Scene::load_test("wind_zone", "zone x=-50 y=-2 width=100 height=4 kind=wind strength=5 angle=0\n\
+            circle x=-45 y=0 radius=0.2 mass=0.01 type=dynamic gravity=0 air=false\n");
+        scene.run(&mut EventLog::new(), 600);
+        let speed = scene.objects[0].get_velocity().x;
+        assert!(speed > 4.0 && speed <= 5.0, "the wind blew the ball at {} m/s", speed);
+    }
+
+    #[test]
+    fn vortex_and_radial_zones_push_around_the_centre() {
+        let body = Body { handle: 0, centre: Vec2::new(1.0, 0.0), velocity: Vec2::ZERO, mass: 2.0, area: 1.0, gravity: 0.0,
+                          drag_coefficient: 1.0, do_air_resistance: false, half_size: Vec2::splat(0.1), circle: true, charge: 0.0, dynamic: true };
+        //The y axis points down, so anticlockwise on screen is up (negative y) on the right of the centre
+        let vortex = ForceZone::new(Vec2::splat(-2.0), Vec2::splat(4.0), ZoneKind::Vortex, 3.0, 0.0);
+        assert!((vortex.get_force(&body) - Vec2::new(0.0, -6.0)).length() < 1e-4);
+        let radial = ForceZone::new(Vec2::splat(-2.0), Vec2::splat(4.0), ZoneKind::Radial, -3.0, 0.0);
+        assert!((radial.get_force(&body) - Vec2::new(-6.0, 0.0)).length() < 1e-4);
+        //Nothing outside the zone is pushed
+        let outside = ForceZone::new(Vec2::new(2.0, 2.0), Vec2::splat(1.0), ZoneKind::Radial, 3.0, 0.0);
+        assert_eq!(outside.get_force(&body), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,101 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::actuators::{Actuator, ActuatorMode, ActuatorSettings};
+use crate::objects::physics::{PhysicsObject, PhysicsType};
+
+//Build the panel for making the selected object follow a path, move at a velocity or orbit a pivot, and running every actuator.
+//Returns a new actuator if the selected object was given its first keyframe in that mode
+pub(crate) fn create_actuator_panel(actuators: &mut [&mut Actuator], selected: Option<&mut Box<dyn PhysicsObject>>,
+                                    settings: &mut ActuatorSettings) -> Option<Actuator> {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    let mut new_actuator = None;
+    root_ui().window(
+        hash!(),
+        panel_position(1, 0.54),
+        Vec2::new(PANEL_WIDTH, 330.),
+        |ui| {
+            ui.label(None, "Actuators");
+            if let Some(object) = selected {
+                let handle = object.get_handle();
+                ui.label(None, &format!("Selected object: #{}", handle));
+                if ui.button(None, format!("Mode: {}", settings.mode.get_name())) {
+                    settings.mode = settings.mode.next();
+                }
+                //Paths are made by dragging the object to each point, and the others by setting each keyframe's value.
+                //Use groups to control the size of the sliders
+                match settings.mode {
+                    ActuatorMode::Path => ui.label(None, "Drag the object to each point of its path"),
+                    ActuatorMode::Velocity => {
+                        ui.label(None, &format!("Velocity: ({:.1}, {:.1}) m/s", settings.velocity.x, settings.velocity.y));
+                        ui.group(hash!(), Vec2::new(330., 25.), |ui| {
+                            ui.slider(hash!(), "vx", -10.0..10.0, &mut settings.velocity.x);
+                        });
+                        ui.group(hash!(), Vec2::new(330., 25.), |ui| {
+                            ui.slider(hash!(), "vy", -10.0..10.0, &mut settings.velocity.y);
+                        });
+                    }
+                    ActuatorMode::Orbit => {
+                        ui.label(None, &format!("Angular velocity: {:.2} rad/s", settings.angular_velocity));
+                        ui.group(hash!(), Vec2::new(330., 25.), |ui| {
+                            ui.slider(hash!(), "", -5.0..5.0, &mut settings.angular_velocity);
+                        });
+                        ui.label(None, &format!("Pivot distance: {:.2} m", settings.radius));
+                        ui.group(hash!(), Vec2::new(330., 25.), |ui| {
+                            ui.slider(hash!(), "", 0.1..10.0, &mut settings.radius);
+                        });
+                    }
+                }
+                if ui.button(None, "add keyframe") {
+                    let centre = object.get_render_shape_reference().get_centre();
+                    let value = settings.get_keyframe_value(centre);
+                    match actuators.iter_mut().find(|actuator| actuator.handle == handle && actuator.mode == settings.mode) {
+                        Some(actuator) => actuator.add_keyframe(value),
+                        None => {
+                            //Only kinematic objects are driven, so they push everything else out of the way
+                            object.set_physics_type(PhysicsType::Kinematic);
+                            object.set_velocity(Vec2::ZERO);
+                            let mut actuator = Actuator::new(handle, settings.mode, Vec::new());
+                            //The pivot is fixed when the orbit is made, so later keyframes only change the speed
+                            actuator.pivot = centre + Vec2::new(settings.radius, 0.0);
+                            actuator.add_keyframe(value);
+                            actuator.running = false;
+                            new_actuator = Some(actuator);
+                        }
+                    }
+                }
+            }
+
+            //Put every actuator in a group that scrolls, as there can be any number of them
+            ui.group(hash!(), Vec2::new(350., 130.), |ui| {
+                for (index, actuator) in actuators.iter_mut().enumerate() {
+                    ui.label(None, &format!("Actuator {}: #{} {}, {} keyframes", index + 1, actuator.handle, actuator.mode.get_name(),
+                                            actuator.keyframes.len()));
+                    ui.label(None, &format!("Time: {:.2} / {:.2} s", actuator.get_time(), actuator.get_duration()));
+                    //Running always starts from the first keyframe, so the motion is the same every time
+                    if actuator.running {
+                        if ui.button(None, "stop") {
+                            actuator.running = false;
+                        }
+                    } else if ui.button(None, "run") {
+                        actuator.running = true;
+                        actuator.reset();
+                    }
+                    ui.same_line(0.);
+                    if ui.button(None, "restart") {
+                        actuator.reset();
+                    }
+                    ui.same_line(0.);
+                    if ui.button(None, format!("repeat: {}", actuator.repeat.get_name())) {
+                        actuator.repeat = actuator.repeat.next();
+                    }
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+    new_actuator
+}
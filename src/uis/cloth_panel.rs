@@ -0,0 +1,47 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::cloth::Cloth;
+
+//Build the panel showing the tension in every rope and cloth, with settings for when they snap
+pub(crate) fn create_cloth_panel(cloths: &mut [&mut Cloth]) {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(2, 0.41),
+        Vec2::new(PANEL_WIDTH, 250.),
+        |ui| {
+            ui.label(None, "Ropes and cloths");
+            //Put every rope and cloth in a group that scrolls, as there can be any number of them
+            ui.group(hash!(), Vec2::new(350., 215.), |ui| {
+                for (index, cloth) in cloths.iter_mut().enumerate() {
+                    let (links, snapped) = cloth.get_link_counts();
+                    ui.label(None, &format!("{} {}: {} links, {} snapped", cloth.kind.get_name(), index + 1, links, snapped));
+                    ui.label(None, &format!("Highest tension: {:.2} N", cloth.get_max_tension()));
+                    //Join every snapped link back together, and hang it from its anchors again
+                    if ui.button(None, "reset") {
+                        cloth.reset();
+                    }
+
+                    //Use groups to control the size of the sliders, and give each slider its own id
+                    if cloth.tear_tension > 0.0 {
+                        ui.label(None, &format!("Snaps at: {:.1} N", cloth.tear_tension));
+                    } else {
+                        ui.label(None, "Snaps at: never");
+                    }
+                    ui.group(hash!(("cloth_tear_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("cloth_tear", index)), "", 0.0..100., &mut cloth.tear_tension);
+                    });
+                    ui.label(None, &format!("Mass of each point: {:.3} kg", cloth.point_mass));
+                    ui.group(hash!(("cloth_mass_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("cloth_mass", index)), "", 0.005..0.2, &mut cloth.point_mass);
+                    });
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+}
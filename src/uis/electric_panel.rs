@@ -0,0 +1,42 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::electrostatics::ElectricField;
+
+//Build the panel for changing the strength and direction of every electric field region
+pub(crate) fn create_electric_panel(fields: &mut [&mut ElectricField]) {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(3, 0.3),
+        Vec2::new(PANEL_WIDTH, 200.),
+        |ui| {
+            ui.label(None, "Electric fields: F = q E");
+            //Put every field in a group that scrolls, as there can be any number of them
+            ui.group(hash!(), Vec2::new(350., 165.), |ui| {
+                for (index, field) in fields.iter_mut().enumerate() {
+                    //The field is stored as a vector, so it is split into a strength (kN/C) and an angle to be changed
+                    let (old_strength, old_angle) = (field.field.length() / 1000., field.field.y.atan2(field.field.x).to_degrees().rem_euclid(360.));
+                    let (mut strength, mut angle) = (old_strength, old_angle);
+                    ui.label(None, &format!("Field {}: {:.0} kN/C", index + 1, strength));
+                    //Use groups to control the size of the sliders, and give each slider its own id
+                    ui.group(hash!(("electric_strength_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("electric_strength", index)), "", 0.0..1000., &mut strength);
+                    });
+                    ui.label(None, &format!("Direction: {:.0} degrees", angle));
+                    ui.group(hash!(("electric_angle_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("electric_angle", index)), "", 0.0..360., &mut angle);
+                    });
+                    //Only put the field back together if a slider moved, so it doesn't drift from rounding every frame
+                    if strength != old_strength || angle != old_angle {
+                        field.field = Vec2::from_angle(angle.to_radians()) * strength * 1000.;
+                    }
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+}
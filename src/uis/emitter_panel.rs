@@ -0,0 +1,81 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::particles::{EmitterPreset, ParticleEmitter};
+
+//Build the panel for changing the settings of every particle emitter
+pub(crate) fn create_emitter_panel(emitters: &mut [&mut ParticleEmitter]) {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(2, 0.),
+        Vec2::new(PANEL_WIDTH, 300.),
+        |ui| {
+            ui.label(None, "Particle emitters");
+            //Put every emitter in a group that scrolls, as there can be any number of them
+            ui.group(hash!(), Vec2::new(350., 265.), |ui| {
+                for (index, emitter) in emitters.iter_mut().enumerate() {
+                    ui.label(None, &format!("Emitter {}: {} particles", index + 1, emitter.count()));
+                    //Set up the emitter for one of the effects, or remove all its particles
+                    for preset in [EmitterPreset::Smoke, EmitterPreset::Sparks, EmitterPreset::Gas] {
+                        if ui.button(None, preset.get_name()) {
+                            emitter.set_preset(preset);
+                        }
+                        ui.same_line(0.);
+                    }
+                    if ui.button(None, "clear") {
+                        emitter.clear();
+                    }
+                    //Turn collisions with objects and air resistance on and off
+                    if ui.button(None, if emitter.collide { "collide: on" } else { "collide: off" }) {
+                        emitter.collide = !emitter.collide;
+                    }
+                    ui.same_line(0.);
+                    if ui.button(None, if emitter.do_air_resistance { "air: on" } else { "air: off" }) {
+                        emitter.do_air_resistance = !emitter.do_air_resistance;
+                    }
+
+                    //Use groups to control the size of the sliders, and give each slider its own id
+                    ui.label(None, &format!("Rate: {:.0} per second", emitter.rate));
+                    ui.group(hash!(("emitter_rate_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("emitter_rate", index)), "", 0.0..200., &mut emitter.rate);
+                    });
+                    ui.label(None, &format!("Speed: {:.1} +/- {:.1} m/s", emitter.speed, emitter.speed_spread));
+                    ui.group(hash!(("emitter_speed_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("emitter_speed", index)), "", 0.0..20., &mut emitter.speed);
+                    });
+                    ui.group(hash!(("emitter_speed_spread_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("emitter_speed_spread", index)), "", 0.0..10., &mut emitter.speed_spread);
+                    });
+                    ui.label(None, &format!("Direction: {:.0} degrees, spread {:.0} degrees", emitter.angle, emitter.angle_spread));
+                    ui.group(hash!(("emitter_angle_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("emitter_angle", index)), "", 0.0..360., &mut emitter.angle);
+                    });
+                    ui.group(hash!(("emitter_angle_spread_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("emitter_angle_spread", index)), "", 0.0..360., &mut emitter.angle_spread);
+                    });
+                    ui.label(None, &format!("Lifetime: {:.1} s", emitter.lifetime));
+                    ui.group(hash!(("emitter_lifetime_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("emitter_lifetime", index)), "", 0.1..60., &mut emitter.lifetime);
+                    });
+                    ui.label(None, &format!("Size: {:.2} m", emitter.size));
+                    ui.group(hash!(("emitter_size_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("emitter_size", index)), "", 0.01..0.3, &mut emitter.size);
+                    });
+                    ui.label(None, &format!("Gravity: {:.2} m/s^2", emitter.gravity));
+                    ui.group(hash!(("emitter_gravity_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("emitter_gravity", index)), "", -10.0..20., &mut emitter.gravity);
+                    });
+                    ui.label(None, &format!("Bounce: {:.2}", emitter.bounce));
+                    ui.group(hash!(("emitter_bounce_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("emitter_bounce", index)), "", 0.0..1., &mut emitter.bounce);
+                    });
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+}
@@ -0,0 +1,56 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::measurements::diagnostics::Diagnostics;
+
+//Show a drift percentage, or a dash when there is nothing to compare against
+fn format_drift(drift: Option<f32>) -> String {
+    match drift {
+        Some(percent) => format!("{:+.2}%", percent),
+        None => String::from("-"),
+    }
+}
+
+//Build the HUD showing the energy and momentum of the scene, and how much they have drifted
+pub(crate) fn create_energy_hud(diagnostics: &mut Diagnostics) {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(0, 0.),
+        Vec2::new(PANEL_WIDTH, 330.),
+        |ui| {
+            let totals = diagnostics.totals;
+            //Show the totals for the whole scene
+            ui.label(None, &format!("Kinetic: {:.3} J", totals.kinetic));
+            ui.label(None, &format!("Potential: {:.3} J", totals.potential));
+            ui.label(None, &format!("Spring: {:.3} J", totals.spring));
+            ui.label(None, &format!("Total: {:.3} J  ({})", totals.get_energy(), format_drift(diagnostics.get_energy_drift())));
+            ui.label(None, &format!("Momentum: ({:.3}, {:.3}) Ns  ({})", totals.momentum.x, totals.momentum.y,
+                                    format_drift(diagnostics.get_momentum_drift())));
+            ui.label(None, &format!("Angular momentum: {:.3} Nms  ({})", totals.angular_momentum,
+                                    format_drift(diagnostics.get_angular_momentum_drift())));
+
+            //Show the time being compared against, and allow the user to change it
+            match diagnostics.reference {
+                Some(reference) => ui.label(None, &format!("Drift since {:.2}s", reference.time)),
+                None => ui.label(None, "No reference yet"),
+            }
+            ui.same_line(0.);
+            if ui.button(None, "set reference") {
+                diagnostics.set_reference();
+            }
+
+            //Show the energy and momentum of every object
+            ui.group(hash!(), Vec2::new(350., 130.), |ui| {
+                for object in &diagnostics.objects {
+                    ui.label(None, &format!("#{}  KE {:.2} J  GPE {:.2} J  p ({:.2}, {:.2})", object.handle, object.kinetic,
+                                            object.potential, object.momentum.x, object.momentum.y));
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+}
@@ -0,0 +1,50 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use macroquad::window::{screen_height, screen_width};
+use crate::uis::{panel_skin, SIDEBAR_WIDTH};
+use crate::objects::events::EventLog;
+
+//The most events shown in the panel at once, so very long logs don't slow down the UI
+const MAX_SHOWN_EVENTS: usize = 200;
+//The file the log is exported to
+const EXPORT_PATH: &str = "collision_log.csv";
+
+//Build the panel showing the collision and sensor events, with buttons to export and clear the log
+pub(crate) fn create_event_log_panel(events: &mut EventLog, export_status: &mut String) {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        Vec2::new(0., screen_height() - 260.),
+        Vec2::new((screen_width() - SIDEBAR_WIDTH).min(700.), 260.),
+        |ui| {
+            //Create the buttons to export and clear the log
+            ui.label(None, &format!("Events: {}", events.events.len()));
+            ui.same_line(0.);
+            if ui.button(None, "export") {
+                *export_status = match events.export(EXPORT_PATH) {
+                    Ok(()) => format!("Saved to {}", EXPORT_PATH),
+                    Err(error) => format!("Could not save: {}", error),
+                };
+            }
+            ui.same_line(0.);
+            if ui.button(None, "clear") {
+                events.clear();
+                export_status.clear();
+            }
+            ui.same_line(0.);
+            ui.label(None, export_status);
+
+            //Show the newest events in a scrollable list
+            ui.group(hash!(), Vec2::new(690., 210.), |ui| {
+                let start = events.events.len().saturating_sub(MAX_SHOWN_EVENTS);
+                for event in &events.events[start..] {
+                    ui.label(None, &event.describe());
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+}
@@ -0,0 +1,36 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::fluids::FluidRegion;
+
+//Build the panel for changing the density and viscosity of every fluid region
+pub(crate) fn create_fluid_panel(fluids: &mut [&mut FluidRegion]) {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(1, 0.8),
+        Vec2::new(PANEL_WIDTH, 180.),
+        |ui| {
+            ui.label(None, "Fluids: F = p A g up");
+            //Put every fluid in a group that scrolls, as there can be any number of them
+            ui.group(hash!(), Vec2::new(350., 145.), |ui| {
+                for (index, fluid) in fluids.iter_mut().enumerate() {
+                    //Objects less dense than the fluid float in it
+                    ui.label(None, &format!("Fluid {}: density {:.2} kg/m^2", index + 1, fluid.density));
+                    //Use groups to control the size of the sliders, and give each slider its own id
+                    ui.group(hash!(("fluid_density_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("fluid_density", index)), "", 0.0..20., &mut fluid.density);
+                    });
+                    ui.label(None, &format!("Viscosity: {:.2} Ns/m^3", fluid.viscosity));
+                    ui.group(hash!(("fluid_viscosity_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("fluid_viscosity", index)), "", 0.0..10., &mut fluid.viscosity);
+                    });
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+}
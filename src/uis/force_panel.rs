@@ -0,0 +1,62 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::forces::{RadialAttractor, Spring, Wind};
+
+//Build the panel for changing every spring, wind and attractor
+pub(crate) fn create_force_panel(springs: &mut [&mut Spring], winds: &mut [&mut Wind], attractors: &mut [&mut RadialAttractor]) {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(0, 0.68),
+        Vec2::new(PANEL_WIDTH, 250.),
+        |ui| {
+            ui.label(None, "Forces");
+            //Put every force in a group that scrolls, as there can be any number of them
+            ui.group(hash!(), Vec2::new(350., 215.), |ui| {
+                for (index, spring) in springs.iter_mut().enumerate() {
+                    ui.label(None, &format!("Spring {}: F = -k x", index + 1));
+                    //Use groups to control the size of the sliders, and give each slider its own id
+                    ui.label(None, &format!("Stiffness: {:.1} N/m", spring.stiffness));
+                    ui.group(hash!(("spring_stiffness_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("spring_stiffness", index)), "", 0.0..200., &mut spring.stiffness);
+                    });
+                    ui.label(None, &format!("Rest length: {:.2} m", spring.rest_length));
+                    ui.group(hash!(("spring_length_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("spring_length", index)), "", 0.0..10., &mut spring.rest_length);
+                    });
+                    ui.label(None, &format!("Damping: {:.1} Ns/m", spring.damping));
+                    ui.group(hash!(("spring_damping_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("spring_damping", index)), "", 0.0..20., &mut spring.damping);
+                    });
+                }
+                for (index, wind) in winds.iter_mut().enumerate() {
+                    ui.label(None, &format!("Wind {}: {:.1} m/s", index + 1, wind.velocity.length()));
+                    ui.label(None, &format!("Across: {:.1} m/s", wind.velocity.x));
+                    ui.group(hash!(("wind_x_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("wind_x", index)), "", -30.0..30., &mut wind.velocity.x);
+                    });
+                    ui.label(None, &format!("Down: {:.1} m/s", wind.velocity.y));
+                    ui.group(hash!(("wind_y_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("wind_y", index)), "", -30.0..30., &mut wind.velocity.y);
+                    });
+                }
+                for (index, attractor) in attractors.iter_mut().enumerate() {
+                    //A negative strength pushes objects away instead
+                    ui.label(None, &format!("Attractor {}: {:.1} m^3/s^2", index + 1, attractor.strength));
+                    ui.group(hash!(("attractor_strength_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("attractor_strength", index)), "", -50.0..50., &mut attractor.strength);
+                    });
+                    ui.label(None, &format!("Softening: {:.2} m", attractor.softening));
+                    ui.group(hash!(("attractor_softening_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("attractor_softening", index)), "", 0.01..1., &mut attractor.softening);
+                    });
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+}
@@ -0,0 +1,107 @@
+use macroquad::color::{Color, SKYBLUE};
+use macroquad::math::Rect;
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::gas::{maxwell_boltzmann, GasBox, BOLTZMANN, MIN_WIDTH, MOLECULE_MASS};
+
+//The number of bars in the speed histogram
+const HISTOGRAM_BINS: usize = 20;
+//Pressures are shown in this unit on the slider, as the real values are tiny
+const PRESSURE_UNIT: f32 = 1e-20;
+
+//Draw the speed of every molecule as a histogram, with the Maxwell-Boltzmann distribution at the gas's temperature on top
+fn draw_speed_histogram(ui: &mut Ui, gas: &GasBox, size: Vec2) {
+    let temperature = gas.get_temperature();
+    //Show speeds up to 4 times the most likely speed
+    let max_speed = 4.0 * (BOLTZMANN * temperature.max(1.0) / MOLECULE_MASS).sqrt();
+    let histogram = gas.get_speed_histogram(HISTOGRAM_BINS, max_speed);
+    //The curve is highest at the most likely speed, so leave some room above it
+    let top = histogram.iter().fold(maxwell_boltzmann(max_speed / 4.0, temperature), |top, value| top.max(*value)) * 1.1;
+
+    let mut canvas = ui.canvas();
+    let origin = canvas.cursor();
+    canvas.request_space(size);
+    canvas.rect(Rect::new(origin.x, origin.y, size.x, size.y), Color::from_rgba(85, 85, 85, 255), Color::from_rgba(36, 36, 36, 255));
+    if top <= 0.0 {
+        return;
+    }
+    let bar_width = size.x / HISTOGRAM_BINS as f32;
+    for (bin, value) in histogram.iter().enumerate() {
+        let height = value / top * size.y;
+        canvas.rect(Rect::new(origin.x + bin as f32 * bar_width, origin.y + size.y - height, bar_width - 1., height), None,
+                    Color::from_rgba(120, 120, 200, 255));
+    }
+    let point = |step: usize| {
+        let speed = max_speed * step as f32 / 60.0;
+        origin + Vec2::new(step as f32 / 60.0 * size.x, size.y - maxwell_boltzmann(speed, temperature) / top * size.y)
+    };
+    for step in 0..60 {
+        canvas.line(point(step), point(step + 1), SKYBLUE);
+    }
+}
+
+//Build the panel showing the temperature, pressure and volume of every container of gas, with settings for the piston
+pub(crate) fn create_gas_panel(gases: &mut [&mut GasBox]) {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(3, 0.),
+        Vec2::new(PANEL_WIDTH, 420.),
+        |ui| {
+            ui.label(None, "Ideal gas: PV = NkT");
+            //Put every container in a group that scrolls, as there can be any number of them
+            ui.group(hash!(), Vec2::new(350., 385.), |ui| {
+                for (index, gas) in gases.iter_mut().enumerate() {
+                    ui.label(None, &format!("Gas {}: {} molecules", index + 1, gas.get_count()));
+                    ui.label(None, &format!("T = {:.0} K   V = {:.2} m^2", gas.get_temperature(), gas.get_volume()));
+                    ui.label(None, &format!("P = {:.3e} N/m   PV/NkT = {:.2}", gas.get_pressure(), gas.get_ideal_ratio()));
+                    draw_speed_histogram(ui, gas, Vec2::new(330., 80.));
+
+                    //Keep the temperature constant for Boyle's law, or the pressure constant with a free piston for Charles's law
+                    if ui.button(None, if gas.thermostat { "thermostat: on" } else { "thermostat: off" }) {
+                        gas.thermostat = !gas.thermostat;
+                    }
+                    ui.same_line(0.);
+                    if ui.button(None, if gas.piston_free { "piston: free" } else { "piston: fixed" }) {
+                        gas.piston_free = !gas.piston_free;
+                        //Start by holding the gas at the pressure it is at now
+                        gas.outside_pressure = gas.get_pressure();
+                    }
+
+                    //Use groups to control the size of the sliders, and give each slider its own id
+                    let mut count = gas.get_count() as f32;
+                    ui.label(None, &format!("Molecules: {}", gas.get_count()));
+                    ui.group(hash!(("gas_count_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("gas_count", index)), "", 1.0..500., &mut count);
+                    });
+                    if count as usize != gas.get_count() {
+                        gas.set_count(count as usize);
+                    }
+                    ui.label(None, &format!("Thermostat temperature: {:.0} K", gas.target_temperature));
+                    ui.group(hash!(("gas_temperature_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("gas_temperature", index)), "", 10.0..1000., &mut gas.target_temperature);
+                    });
+                    if gas.piston_free {
+                        let mut pressure = gas.outside_pressure / PRESSURE_UNIT;
+                        ui.label(None, &format!("Outside pressure: {:.1} x 10^-20 N/m", pressure));
+                        ui.group(hash!(("gas_pressure_group", index)), Vec2::new(330., 25.), |ui| {
+                            ui.slider(hash!(("gas_pressure", index)), "", 0.0..500., &mut pressure);
+                        });
+                        gas.outside_pressure = pressure * PRESSURE_UNIT;
+                    } else {
+                        ui.label(None, &format!("Piston: {:.2} m", gas.size.x));
+                        let max_width = gas.max_width;
+                        ui.group(hash!(("gas_piston_group", index)), Vec2::new(330., 25.), |ui| {
+                            ui.slider(hash!(("gas_piston", index)), "", MIN_WIDTH..max_width, &mut gas.size.x);
+                        });
+                    }
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+}
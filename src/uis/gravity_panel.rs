@@ -0,0 +1,39 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::gravitation::NBodyGravity;
+
+//Build the panel for changing the settings of N-body gravitation
+pub(crate) fn create_gravity_panel(nbody: &mut NBodyGravity) {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(0, 0.45),
+        Vec2::new(PANEL_WIDTH, 170.),
+        |ui| {
+            ui.label(None, "N-body gravity: F = G m1 m2 / r^2");
+            //Use groups to control the size of the sliders
+            ui.label(None, &format!("G: {:.3}", nbody.gravitational_constant));
+            ui.group(hash!(), Vec2::new(350., 25.), |ui| {
+                ui.slider(hash!(), "", 0.01..50., &mut nbody.gravitational_constant);
+            });
+            ui.label(None, &format!("Softening: {:.3} m", nbody.softening));
+            ui.group(hash!(), Vec2::new(350., 25.), |ui| {
+                ui.slider(hash!(), "", 0.0..1., &mut nbody.softening);
+            });
+            //Barnes-Hut is turned off with a theta of 0, which adds up every pair exactly
+            if nbody.theta > 0.0 {
+                ui.label(None, &format!("Barnes-Hut theta: {:.2}", nbody.theta));
+            } else {
+                ui.label(None, "Barnes-Hut off (exact)");
+            }
+            ui.group(hash!(), Vec2::new(350., 25.), |ui| {
+                ui.slider(hash!(), "", 0.0..1., &mut nbody.theta);
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+}
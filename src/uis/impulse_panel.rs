@@ -0,0 +1,48 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::explosions::{ImpulseMode, ImpulseSettings};
+
+//Build the panel for the settings of the impulse tool, shown while it is being used
+pub(crate) fn create_impulse_panel(settings: &mut ImpulseSettings) {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(2, 0.75),
+        Vec2::new(PANEL_WIDTH, 190.),
+        |ui| {
+            //Swap between an explosion where the user clicks, and hitting one object the way the user drags
+            if ui.button(None, format!("Mode: {}", settings.mode.get_name())) {
+                settings.mode = if settings.mode == ImpulseMode::Radial { ImpulseMode::Directional } else { ImpulseMode::Radial };
+            }
+            if settings.mode == ImpulseMode::Radial {
+                ui.label(None, "Click to push everything nearby away");
+            } else {
+                ui.label(None, "Drag from an object to hit it that way");
+            }
+            //Use groups to control the size of the sliders
+            if settings.mode == ImpulseMode::Radial {
+                ui.label(None, &format!("Impulse: {:.1} Ns", settings.strength));
+            } else {
+                ui.label(None, &format!("Impulse: {:.1} Ns per m dragged", settings.strength));
+            }
+            ui.group(hash!(), Vec2::new(350., 25.), |ui| {
+                ui.slider(hash!(), "", 0.1..50., &mut settings.strength);
+            });
+            //The radius and falloff only matter for explosions
+            if settings.mode == ImpulseMode::Radial {
+                ui.label(None, &format!("Radius: {:.2} m", settings.radius));
+                ui.group(hash!(), Vec2::new(350., 25.), |ui| {
+                    ui.slider(hash!(), "", 0.1..10., &mut settings.radius);
+                });
+                if ui.button(None, format!("Falloff: {}", settings.falloff.get_name())) {
+                    settings.falloff = settings.falloff.next();
+                }
+            }
+        },
+    );
+
+    root_ui().pop_skin();
+}
@@ -0,0 +1,43 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::joints::JointSystem;
+
+//Build the panel showing the force in every joint, with settings for when they snap
+pub(crate) fn create_joint_panel(joints: &mut JointSystem) {
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(3, 0.57),
+        Vec2::new(PANEL_WIDTH, 250.),
+        |ui| {
+            let snapped = joints.joints.iter().filter(|joint| joint.broken).count();
+            ui.label(None, &format!("Joints: {}, {} snapped", joints.joints.len() - snapped, snapped));
+            //Join every snapped joint back together, at the length it was first made
+            if ui.button(None, "mend all") {
+                for joint in joints.joints.iter_mut() {
+                    joint.broken = false;
+                }
+            }
+            //Put every joint in a group that scrolls, as a structure can have any number of them
+            ui.group(hash!(), Vec2::new(350., 180.), |ui| {
+                for (index, joint) in joints.joints.iter_mut().enumerate().filter(|(_, joint)| !joint.broken) {
+                    ui.label(None, &format!("Joint {}: #{} - #{}, {:.1} N", index + 1, joint.a, joint.b, joint.tension));
+                    //Use groups to control the size of the sliders, and give each slider its own id
+                    if joint.break_force > 0.0 {
+                        ui.label(None, &format!("Snaps at: {:.0} N", joint.break_force));
+                    } else {
+                        ui.label(None, "Snaps at: never");
+                    }
+                    ui.group(hash!(("joint_break_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("joint_break", index)), "", 0.0..500., &mut joint.break_force);
+                    });
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+}
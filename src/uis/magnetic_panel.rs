@@ -0,0 +1,38 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::magnetism::MagneticField;
+
+//Build the panel for changing the strength of every magnetic field region. Returns the field to delete, if any
+pub(crate) fn create_magnetic_panel(fields: &mut [&mut MagneticField]) -> Option<usize> {
+    let mut delete = None;
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(1, 0.),
+        Vec2::new(PANEL_WIDTH, 170.),
+        |ui| {
+            ui.label(None, "Magnetic fields: F = q v x B");
+            //Put every field in a group that scrolls, as there can be any number of them
+            ui.group(hash!(), Vec2::new(350., 135.), |ui| {
+                for (index, field) in fields.iter_mut().enumerate() {
+                    let direction = if field.strength >= 0.0 { "out of screen" } else { "into screen" };
+                    ui.label(None, &format!("Field {}: {:.2} T {}", index + 1, field.strength.abs(), direction));
+                    ui.same_line(0.);
+                    if ui.button(None, "X delete") {
+                        delete = Some(index);
+                    }
+                    //Use groups to control the size of the sliders, and give each slider its own id
+                    ui.group(hash!(("magnetic_field", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("magnetic_strength", index)), "", -10.0..10., &mut field.strength);
+                    });
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+    delete
+}
@@ -2,10 +2,111 @@ use macroquad::hash;
 use macroquad::prelude::*;
 use macroquad::ui::{Ui, Skin, Style, root_ui};
 
-use crate::MouseMode;
+use crate::objects::create_objects::MouseMode;
 use crate::objects::physics::PhysicsObject;
+use crate::objects::events::EventLog;
+use crate::measurements::diagnostics::Diagnostics;
+use crate::objects::gravitation::NBodyGravity;
+use crate::objects::magnetism::MagneticField;
+use crate::objects::fluids::FluidRegion;
+use crate::objects::electrostatics::ElectricField;
+use crate::objects::cloth::Cloth;
+use crate::objects::explosions::ImpulseSettings;
+use crate::objects::gas::GasBox;
+use crate::objects::actuators::{Actuator, ActuatorSettings};
+use crate::objects::joints::JointSystem;
+use crate::objects::particles::ParticleEmitter;
+use crate::objects::zones::ForceZone;
+use crate::objects::forces::{RadialAttractor, Spring, Wind};
+
+//Which extra panels and overlays are shown, toggled from the hotbar
+pub(crate) struct Overlays {
+    pub(crate) log: bool,
+    pub(crate) energy: bool,
+    pub(crate) field: bool,
+    pub(crate) trails: bool,
+}
+
+impl Overlays {
+    pub(crate) fn new() -> Overlays {
+        Overlays { log: false, energy: false, field: false, trails: false }
+    }
+}
+
+//The settings for the orbit tool in the sidebar, kept between frames
+pub(crate) struct OrbitSettings {
+    pub(crate) centre: Option<usize>,
+    pub(crate) eccentricity: f32,
+}
+
+impl OrbitSettings {
+    pub(crate) fn new() -> OrbitSettings {
+        OrbitSettings { centre: None, eccentricity: 0.5 }
+    }
+}
+
 mod guidlines;
 mod sidebar;
+mod event_log;
+mod energy_hud;
+mod gravity_panel;
+mod magnetic_panel;
+mod fluid_panel;
+mod electric_panel;
+mod zone_panel;
+mod emitter_panel;
+mod gas_panel;
+mod cloth_panel;
+mod joint_panel;
+mod actuator_panel;
+mod impulse_panel;
+mod force_panel;
+
+//The sidebar's width and the hotbar's height, which the panels are kept clear of
+pub(crate) const SIDEBAR_WIDTH: f32 = 400.;
+const HOTBAR_HEIGHT: f32 = 40.;
+//The width of every panel, and the gap between them
+pub(crate) const PANEL_WIDTH: f32 = 360.;
+const PANEL_GAP: f32 = 10.;
+
+//Build the normal skin for the panels in the whole project
+pub(crate) fn panel_skin() -> Skin {
+    let window_style = root_ui()
+        .style_builder()
+        .color(Color::from_rgba(46, 46, 46, 255))
+        .color_inactive(Color::from_rgba(46, 46, 46, 255))
+        .text_color(WHITE)
+        .build();
+
+    let button_style = root_ui()
+        .style_builder()
+        .background_margin(RectOffset::new(0.0, 16.0, 0.0, 16.0))
+        .margin(RectOffset::new(16.0, 0.0, 16.0, -16.0))
+        .color(Color::from_rgba(42, 42, 42, 255))
+        .color_inactive(Color::from_rgba(42, 42, 42, 255))
+        .color_hovered(Color::from_rgba(85, 85, 85, 255))
+        .color_clicked(PURPLE)
+        .text_color(WHITE)
+        .text_color_hovered(WHITE)
+        .text_color_clicked(WHITE)
+        .build();
+
+    Skin {
+        label_style: window_style.clone(),
+        group_style: window_style.clone(),
+        window_style,
+        button_style,
+        ..root_ui().default_skin()
+    }
+}
+
+//Where to put a panel, in a column counted from the left and a fraction of the way down the screen below the hotbar.
+//Columns that would go under the sidebar are moved left, so on a small screen panels overlap each other instead of the sidebar
+pub(crate) fn panel_position(column: usize, down: f32) -> Vec2 {
+    let furthest = (screen_width() - SIDEBAR_WIDTH - PANEL_WIDTH).max(0.);
+    let x = (column as f32 * (PANEL_WIDTH + PANEL_GAP)).min(furthest);
+    Vec2::new(x, HOTBAR_HEIGHT + down * (screen_height() - HOTBAR_HEIGHT))
+}
 
 //Function to produce a button that changes colour based on a variable
 fn active_button(ui: &mut Ui,is_active: bool, active: &Style, mut inactive: Skin, label: &'static str) -> bool {
@@ -30,7 +131,8 @@ fn active_button(ui: &mut Ui,is_active: bool, active: &Style, mut inactive: Skin
 }
 
 //Build the hotbar for the UI
-pub(crate) fn build_hot_bar(simulate: &mut bool, mouse_mode: &mut MouseMode) -> (bool, bool) {
+pub(crate) fn build_hot_bar(simulate: &mut bool, mouse_mode: &mut MouseMode, overlays: &mut Overlays,
+                            deterministic: &mut bool, nbody: &mut bool, status: &str) -> (bool, bool, bool, bool, bool) {
     let mut self_return = false;
     //Use the defult bar style for the whole of the project
     let bar_style = root_ui()
@@ -77,6 +179,9 @@ pub(crate) fn build_hot_bar(simulate: &mut bool, mouse_mode: &mut MouseMode) ->
 
     //Construct the top selection bar
     let mut return_2 = false;
+    let mut save = false;
+    let mut load = false;
+    let mut script = false;
     root_ui().push_skin(&bar_skin);
     root_ui().window(
         hash!(),
@@ -101,6 +206,102 @@ pub(crate) fn build_hot_bar(simulate: &mut bool, mouse_mode: &mut MouseMode) ->
                 else { *mouse_mode = MouseMode::DrawBall; }
             }
             ui.same_line(0.0);
+            //Use an active button for the mouse mode being for drawing a fluid region
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawFluid), &button_bar_active, bar_skin.clone(), "Fluid") {
+                if matches!(mouse_mode, MouseMode::DrawFluid) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawFluid; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for drawing a fluid region with any shape, one corner at a time
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawFluidPolygon), &button_bar_active, bar_skin.clone(), "Fluid poly") {
+                if matches!(mouse_mode, MouseMode::DrawFluidPolygon) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawFluidPolygon; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for drawing a magnetic field region
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawMagnetic), &button_bar_active, bar_skin.clone(), "B-field") {
+                if matches!(mouse_mode, MouseMode::DrawMagnetic) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawMagnetic; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for drawing an electric field region
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawElectric), &button_bar_active, bar_skin.clone(), "E-region") {
+                if matches!(mouse_mode, MouseMode::DrawElectric) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawElectric; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for drawing a force zone
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawZone), &button_bar_active, bar_skin.clone(), "Zone") {
+                if matches!(mouse_mode, MouseMode::DrawZone) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawZone; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for placing a particle emitter
+            if active_button(ui, matches!(mouse_mode, MouseMode::PlaceEmitter), &button_bar_active, bar_skin.clone(), "Emitter") {
+                if matches!(mouse_mode, MouseMode::PlaceEmitter) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::PlaceEmitter; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for drawing a container of gas
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawGas), &button_bar_active, bar_skin.clone(), "Gas") {
+                if matches!(mouse_mode, MouseMode::DrawGas) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawGas; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for drawing a block of SPH fluid
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawSph), &button_bar_active, bar_skin.clone(), "SPH") {
+                if matches!(mouse_mode, MouseMode::DrawSph) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawSph; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for drawing a rope between two anchors
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawRope), &button_bar_active, bar_skin.clone(), "Rope") {
+                if matches!(mouse_mode, MouseMode::DrawRope) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawRope; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for drawing a cloth hanging from two anchors
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawCloth), &button_bar_active, bar_skin.clone(), "Cloth") {
+                if matches!(mouse_mode, MouseMode::DrawCloth) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawCloth; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for joining two objects together
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawJoint), &button_bar_active, bar_skin.clone(), "Joint") {
+                if matches!(mouse_mode, MouseMode::DrawJoint) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawJoint; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for attaching an object to an anchor with a spring
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawSpring), &button_bar_active, bar_skin.clone(), "Spring") {
+                if matches!(mouse_mode, MouseMode::DrawSpring) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawSpring; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for making wind across the whole scene
+            if active_button(ui, matches!(mouse_mode, MouseMode::DrawWind), &button_bar_active, bar_skin.clone(), "Wind") {
+                if matches!(mouse_mode, MouseMode::DrawWind) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::DrawWind; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for placing a point that pulls everything towards it
+            if active_button(ui, matches!(mouse_mode, MouseMode::PlaceAttractor), &button_bar_active, bar_skin.clone(), "Attractor") {
+                if matches!(mouse_mode, MouseMode::PlaceAttractor) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::PlaceAttractor; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for hitting objects with an impulse or an explosion
+            if active_button(ui, matches!(mouse_mode, MouseMode::Impulse), &button_bar_active, bar_skin.clone(), "Impulse") {
+                if matches!(mouse_mode, MouseMode::Impulse) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::Impulse; }
+            }
+            ui.same_line(0.0);
+            //Use an active button for the mouse mode being for launching objects by pulling them back
+            if active_button(ui, matches!(mouse_mode, MouseMode::Slingshot), &button_bar_active, bar_skin.clone(), "Slingshot") {
+                if matches!(mouse_mode, MouseMode::Slingshot) { *mouse_mode = MouseMode::Drag; }
+                else { *mouse_mode = MouseMode::Slingshot; }
+            }
+            ui.same_line(0.0);
             //Check the game should be simulating the game, and display the pause/play button accordingly
             if *simulate {
                 if ui.button(None, "pause") {
@@ -115,20 +316,132 @@ pub(crate) fn build_hot_bar(simulate: &mut bool, mouse_mode: &mut MouseMode) ->
                 self_return = true;
             }
             ui.same_line(0.0);
-            return_2 = if ui.button(None, "clear") { true } else { false }
+            return_2 = ui.button(None, "clear");
+            //Show or hide the collision event log
+            ui.same_line(0.0);
+            if active_button(ui, overlays.log, &button_bar_active, bar_skin.clone(), "log") {
+                overlays.log = !overlays.log;
+            }
+            //Show or hide the energy and momentum HUD
+            ui.same_line(0.0);
+            if active_button(ui, overlays.energy, &button_bar_active, bar_skin.clone(), "energy") {
+                overlays.energy = !overlays.energy;
+            }
+            //Show or hide the electric field lines and potential
+            ui.same_line(0.0);
+            if active_button(ui, overlays.field, &button_bar_active, bar_skin.clone(), "E-field") {
+                overlays.field = !overlays.field;
+            }
+            //Show or hide the paths every moving object has taken
+            ui.same_line(0.0);
+            if active_button(ui, overlays.trails, &button_bar_active, bar_skin.clone(), "trails") {
+                overlays.trails = !overlays.trails;
+            }
+            //Use a fixed timestep and seed, so the same scene always gives the same result
+            ui.same_line(0.0);
+            if active_button(ui, *deterministic, &button_bar_active, bar_skin.clone(), "fixed") {
+                *deterministic = !*deterministic;
+            }
+            //Make every object attract every other object, instead of falling downwards
+            ui.same_line(0.0);
+            if active_button(ui, *nbody, &button_bar_active, bar_skin.clone(), "n-body") {
+                *nbody = !*nbody;
+            }
+            //Save and load the scene
+            ui.same_line(0.0);
+            save = ui.button(None, "save");
+            ui.same_line(0.0);
+            load = ui.button(None, "load");
+            //Run the scene script
+            ui.same_line(0.0);
+            script = ui.button(None, "script");
+            ui.same_line(0.0);
+            ui.label(None, status);
         },
     );
 
     root_ui().pop_skin();
-    (self_return, return_2)
+    (self_return, return_2, save, load, script)
 }
 
-//Construct the UI from the build_ui function
-pub fn build_ui(camera: &Camera2D, ui_id: &mut String,
-                objects: &mut Vec<Box<dyn PhysicsObject>>, selected_index: Option<usize>, ui_save_text: &mut String) {
+//Construct the UI from the build_ui function. Returns if the soft body and balloon buttons were pressed
+pub fn build_ui(camera: &Camera2D, ui_id: &mut String, objects: &mut Vec<Box<dyn PhysicsObject>>, selected_index: Option<usize>,
+                ui_save_text: &mut String, gravitational_constant: Option<f32>, orbit: &mut OrbitSettings) -> (bool, bool) {
     guidlines::draw_guidelines(camera);
     //Make sure an object is selected, and then allow the sidebar to be created
     if let Some(selected_object_index) = selected_index {
-        sidebar::create_side_bar(ui_id, objects, selected_object_index, ui_save_text);
+        return sidebar::create_side_bar(ui_id, objects, selected_object_index, ui_save_text, gravitational_constant, orbit);
     }
+    (false, false)
+}
+
+//Construct the event log panel from the create_event_log_panel function
+pub fn build_event_log(events: &mut EventLog, export_status: &mut String) {
+    event_log::create_event_log_panel(events, export_status);
+}
+
+//Construct the energy HUD from the create_energy_hud function
+pub fn build_energy_hud(diagnostics: &mut Diagnostics) {
+    energy_hud::create_energy_hud(diagnostics);
+}
+
+//Construct the N-body gravity settings from the create_gravity_panel function
+pub fn build_gravity_panel(nbody: &mut NBodyGravity) {
+    gravity_panel::create_gravity_panel(nbody);
+}
+
+//Construct the fluid settings from the create_fluid_panel function
+pub fn build_fluid_panel(fluids: &mut [&mut FluidRegion]) {
+    fluid_panel::create_fluid_panel(fluids);
+}
+
+//Construct the magnetic field settings from the create_magnetic_panel function. Returns the field to delete, if any
+pub fn build_magnetic_panel(fields: &mut [&mut MagneticField]) -> Option<usize> {
+    magnetic_panel::create_magnetic_panel(fields)
+}
+
+//Construct the electric field settings from the create_electric_panel function
+pub fn build_electric_panel(fields: &mut [&mut ElectricField]) {
+    electric_panel::create_electric_panel(fields);
+}
+
+//Construct the force zone settings from the create_zone_panel function. Returns the zone to delete, if any
+pub fn build_zone_panel(zones: &mut [&mut ForceZone]) -> Option<usize> {
+    zone_panel::create_zone_panel(zones)
+}
+
+//Construct the particle emitter settings from the create_emitter_panel function
+pub fn build_emitter_panel(emitters: &mut [&mut ParticleEmitter]) {
+    emitter_panel::create_emitter_panel(emitters);
+}
+
+//Construct the gas readouts and settings from the create_gas_panel function
+pub fn build_gas_panel(gases: &mut [&mut GasBox]) {
+    gas_panel::create_gas_panel(gases);
+}
+
+//Construct the rope and cloth settings from the create_cloth_panel function
+pub fn build_cloth_panel(cloths: &mut [&mut Cloth]) {
+    cloth_panel::create_cloth_panel(cloths);
+}
+
+//Construct the joint settings from the create_joint_panel function
+pub fn build_joint_panel(joints: &mut JointSystem) {
+    joint_panel::create_joint_panel(joints);
+}
+
+//Construct the actuator settings from the create_actuator_panel function. Returns a new actuator for the selected object
+pub fn build_actuator_panel(actuators: &mut [&mut Actuator], selected: Option<&mut Box<dyn PhysicsObject>>,
+                            settings: &mut ActuatorSettings) -> Option<Actuator> {
+    actuator_panel::create_actuator_panel(actuators, selected, settings)
+}
+
+//Construct the impulse tool settings from the create_impulse_panel function
+pub fn build_impulse_panel(settings: &mut ImpulseSettings) {
+    impulse_panel::create_impulse_panel(settings);
+}
+
+//Construct the spring, wind and attractor settings from the create_force_panel function
+pub fn build_force_panel(springs: &mut [&mut Spring], winds: &mut [&mut Wind], attractors: &mut [&mut RadialAttractor]) {
+    force_panel::create_force_panel(springs, winds, attractors);
 }
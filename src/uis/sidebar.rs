@@ -3,8 +3,9 @@ use macroquad::math::RectOffset;
 use macroquad::prelude::Vec2;
 use macroquad::ui::*;
 use macroquad::window::{screen_height, screen_width};
-use crate::objects::physics::{PhysicsObject, PhysicsType};
-use crate::uis::active_button;
+use crate::objects::orbits::{find_primary, get_mu, get_orbit_velocity, get_orbital_elements};
+use crate::objects::physics::{set_belt_speed, PhysicsObject, PhysicsType};
+use crate::uis::{active_button, panel_skin, OrbitSettings, SIDEBAR_WIDTH};
 
 //Check if the given string is only comprised of numbers
 fn is_only_numbers(s: &str) -> bool {
@@ -158,6 +159,7 @@ fn create_x_and_y_input(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>,
         let new_pos = Vec2::new(x_str.trim().parse::<f32>().unwrap(), y_str.trim().parse::<f32>().unwrap());
         if x_str != x_original || y_str != y_original {
             *objects.get_mut(selected_index).unwrap().get_render_shape_reference().get_pos() = new_pos;
+            objects.get_mut(selected_index).unwrap().wake();
         }
     }
 }
@@ -228,6 +230,7 @@ fn create_mass_material_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObj
             let new_density = new_mass/area;
             objects.get_mut(selected_index).unwrap().get_material().mass = new_mass;
             objects.get_mut(selected_index).unwrap().get_material().density = new_density;
+            objects.get_mut(selected_index).unwrap().wake();
         }
     }
     //Check if user has changed the density value
@@ -242,6 +245,7 @@ fn create_mass_material_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObj
             let new_mass = new_density * area;
             objects.get_mut(selected_index).unwrap().get_material().mass = new_mass;
             objects.get_mut(selected_index).unwrap().get_material().density = new_density;
+            objects.get_mut(selected_index).unwrap().wake();
         }
     }
 }
@@ -265,6 +269,119 @@ fn build_gravity_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>,
         *ui_text_save = gravity_str.clone();
         if is_only_numbers(&gravity_str) {
             *objects.get_mut(selected_index).unwrap().get_gravity() = gravity_str.trim().parse::<f32>().unwrap();
+            objects.get_mut(selected_index).unwrap().wake();
+        }
+    }
+}
+
+//Create the charge input for the selected object, in coulombs. Small charges can be written like 1e-6
+fn build_charge_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>, selected_index: usize,
+                       ui_id: &mut String, ui_text_save: &mut String) {
+    //Declare the variable for the user to edit
+    let mut charge_str: String = if ui_id == "text_input_charge" { ui_text_save.to_string() }
+    else { objects.get_mut(selected_index).unwrap().get_charge().to_string() };
+    let charge_original: String = charge_str.clone();
+
+    //Create UI and inputs for charge
+    ui.label(None, "Charge (C):");
+    ui.same_line(0.);
+    ui.editbox(hash!(), Vec2::new(100., 20.), &mut charge_str);
+
+    //Check if user has changed the value of the charge
+    if charge_str != charge_original {
+        *ui_id = "text_input_charge".into();
+        *ui_text_save = charge_str.clone();
+        if is_only_numbers(&charge_str) {
+            *objects.get_mut(selected_index).unwrap().get_charge() = charge_str.trim().parse::<f32>().unwrap();
+            objects.get_mut(selected_index).unwrap().wake();
+        }
+    }
+}
+
+//Create the input for how hard the selected object must be hit to break (Ns), 0 for never. Circles never break
+fn build_break_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>, selected_index: usize,
+                      ui_id: &mut String, ui_text_save: &mut String) {
+    if objects.get_mut(selected_index).unwrap().get_render_shape_reference().get_id() == "Circle" {
+        return;
+    }
+    //Declare the variable for the user to edit
+    let mut break_str: String = if ui_id == "text_input_break" { ui_text_save.to_string() }
+    else { objects.get_mut(selected_index).unwrap().get_break_impulse().to_string() };
+    let break_original: String = break_str.clone();
+
+    //Create UI and inputs for the break impulse
+    ui.label(None, "Breaks at (Ns):");
+    ui.same_line(0.);
+    ui.editbox(hash!(), Vec2::new(100., 20.), &mut break_str);
+
+    //Check if user has changed the value of the break impulse
+    if break_str != break_original {
+        *ui_id = "text_input_break".into();
+        *ui_text_save = break_str.clone();
+        if is_only_numbers(&break_str) {
+            *objects.get_mut(selected_index).unwrap().get_break_impulse() = break_str.trim().parse::<f32>().unwrap();
+        }
+    }
+}
+
+//Create the friction and restitution inputs for the selected object, and the belt speed for static objects used as conveyor belts
+fn build_friction_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>, selected_index: usize,
+                         ui_id: &mut String, ui_text_save: &mut String) {
+    //Declare the variable for the user to edit
+    let mut friction_str: String = if ui_id == "text_input_friction" { ui_text_save.to_string() }
+    else { objects.get_mut(selected_index).unwrap().get_friction().to_string() };
+    let friction_original: String = friction_str.clone();
+
+    //Create UI and inputs for the coefficient of friction
+    ui.label(None, "Friction:");
+    ui.same_line(0.);
+    ui.editbox(hash!(), Vec2::new(100., 20.), &mut friction_str);
+
+    //Check if user has changed the value of the friction, which can't be negative
+    if friction_str != friction_original {
+        *ui_id = "text_input_friction".into();
+        *ui_text_save = friction_str.clone();
+        if is_only_numbers(&friction_str) {
+            *objects.get_mut(selected_index).unwrap().get_friction() = friction_str.trim().parse::<f32>().unwrap().max(0.0);
+        }
+    }
+
+    let mut bounce_str: String = if ui_id == "text_input_bounce" { ui_text_save.to_string() }
+    else { objects.get_mut(selected_index).unwrap().get_restitution().to_string() };
+    let bounce_original: String = bounce_str.clone();
+
+    //Create UI and inputs for the coefficient of restitution
+    ui.label(None, "Restitution:");
+    ui.same_line(0.);
+    ui.editbox(hash!(), Vec2::new(100., 20.), &mut bounce_str);
+
+    //Check if user has changed the value of the restitution, which must be between 0 (no bounce) and 1 (elastic)
+    if bounce_str != bounce_original {
+        *ui_id = "text_input_bounce".into();
+        *ui_text_save = bounce_str.clone();
+        if is_only_numbers(&bounce_str) {
+            *objects.get_mut(selected_index).unwrap().get_restitution() = bounce_str.trim().parse::<f32>().unwrap().clamp(0.0, 1.0);
+        }
+    }
+
+    if !matches!(objects.get_mut(selected_index).unwrap().get_physics_type(), PhysicsType::Static) {
+        return;
+    }
+    let mut belt_str: String = if ui_id == "text_input_belt" { ui_text_save.to_string() }
+    else { objects.get_mut(selected_index).unwrap().get_surface_velocity().to_string() };
+    let belt_original: String = belt_str.clone();
+
+    //Create UI and inputs for the belt speed, negative for anticlockwise
+    ui.label(None, "Belt speed (m/s):");
+    ui.same_line(0.);
+    ui.editbox(hash!(), Vec2::new(100., 20.), &mut belt_str);
+
+    //Check if user has changed the value of the belt speed
+    if belt_str != belt_original {
+        *ui_id = "text_input_belt".into();
+        *ui_text_save = belt_str.clone();
+        if is_only_numbers(&belt_str) {
+            set_belt_speed(objects.get_mut(selected_index).unwrap().as_mut(), belt_str.trim().parse::<f32>().unwrap());
         }
     }
 }
@@ -291,6 +408,7 @@ fn create_shape_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>, s
                 if is_only_numbers(&text_box_1) {
                     objects.get_mut(selected_index).unwrap().get_render_shape_reference().set_measurements((text_box_1.trim().parse::<f32>().unwrap(), -1.));
                     objects.get_mut(selected_index).unwrap().update_material();
+                    objects.get_mut(selected_index).unwrap().wake();
                 }
             }
         }
@@ -320,6 +438,7 @@ fn create_shape_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>, s
                     );
                     //Update the material of the object (density)
                     objects.get_mut(selected_index).unwrap().update_material();
+                    objects.get_mut(selected_index).unwrap().wake();
                 }
             }
             //Check if the user has changed text_box_2
@@ -333,6 +452,7 @@ fn create_shape_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>, s
                     );
                     //Update the material of the object (density)
                     objects.get_mut(selected_index).unwrap().update_material();
+                    objects.get_mut(selected_index).unwrap().wake();
                 }
             }
         }
@@ -353,6 +473,7 @@ fn create_shape_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>, s
                 if is_only_numbers(&text_box_1) {
                     objects.get_mut(selected_index).unwrap().get_render_shape_reference().set_measurements((text_box_1.trim().parse::<f32>().unwrap(), -1.));
                     objects.get_mut(selected_index).unwrap().update_material();
+                    objects.get_mut(selected_index).unwrap().wake();
                 }
             }
         }
@@ -378,6 +499,20 @@ fn build_transparency_slider(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObjec
     objects.get_mut(selected_index).unwrap().get_render_shape_reference().set_colour(colour);
 }
 
+//Create the buttons to turn the selected circle or rectangle into a soft body, or a balloon filled with gas.
+//Returns if each button was pressed
+fn build_soft_body_buttons(ui: &mut Ui, objects: &mut [Box<dyn PhysicsObject>], selected_index: usize) -> (bool, bool) {
+    if !matches!(objects[selected_index].get_render_shape_reference().get_id(), "Circle" | "Rectangle") {
+        return (false, false);
+    }
+    ui.label(None, "Make soft:");
+    ui.same_line(0.);
+    let soft = ui.button(None, "soft body");
+    ui.same_line(0.);
+    let balloon = ui.button(None, "balloon");
+    (soft, balloon)
+}
+
 //Create the delete button for the selected object
 fn build_bin_button(ui: &mut Ui, _ui_id: &mut String, default_skin: &mut Skin, bin_button_style: Style, objects: &mut Vec<Box<dyn PhysicsObject>>, selected_index: usize) {
     //Hold the defult skin for the user
@@ -393,6 +528,7 @@ fn build_bin_button(ui: &mut Ui, _ui_id: &mut String, default_skin: &mut Skin, b
 fn build_air_resistance_checkbox(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>, active: &Style, skin: &Skin, selected_index: usize) {
     //Get the current value for air resistance
     let value = objects.get_mut(selected_index).unwrap().set_do_air_resistance();
+    let before = *value;
     ui.label(None, "Air Resistance: ");
     ui.same_line(0.);
     if *value {
@@ -404,33 +540,146 @@ fn build_air_resistance_checkbox(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsO
             *value = true;
         }
     }
+    let changed = before != *value;
     *objects.get_mut(selected_index).unwrap().set_do_air_resistance() = *value;
+    if changed {
+        objects.get_mut(selected_index).unwrap().wake();
+    }
 }
 
-//Create the sidebar for the user
-pub(crate) fn create_side_bar(ui_id: &mut String, objects: &mut Vec<Box<dyn PhysicsObject>>, selected_index: usize, ui_text_save: &mut String) {
-    //Use the normal style for the window for the whole project
-    let window_style = root_ui()
-        .style_builder()
-        .color(Color::from_rgba(46, 46, 46, 255))
-        .color_inactive(Color::from_rgba(46, 46, 46, 255))
-        .text_color(WHITE)
-        .build();
+//Get two different objects from the list at the same time
+fn get_pair(objects: &mut [Box<dyn PhysicsObject>], first: usize, second: usize) -> (&mut Box<dyn PhysicsObject>, &mut Box<dyn PhysicsObject>) {
+    if first < second {
+        let (left, right) = objects.split_at_mut(second);
+        (&mut left[first], &mut right[0])
+    } else {
+        let (left, right) = objects.split_at_mut(first);
+        (&mut right[0], &mut left[second])
+    }
+}
 
-    //Use the normal button style for the whole project
-    let button_style = root_ui()
-        .style_builder()
-        .background_margin(RectOffset::new(0.0, 16.0, 0.0, 16.0))
-        .margin(RectOffset::new(16.0, 0.0, 16.0, -16.0))
-        .color(Color::from_rgba(42, 42, 42, 255))
-        .color_inactive(Color::from_rgba(42, 42, 42, 255))
-        .color_hovered(Color::from_rgba(85, 85, 85, 255))
-        .color_clicked(PURPLE)
-        .text_color(WHITE)
-        .text_color_hovered(WHITE)
-        .text_color_clicked(WHITE)
-        .build();
+//Build the tools to put the selected object into orbit, and show the orbit it is on (only with N-body gravity)
+fn build_orbit_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>, selected_index: usize, ui_id: &mut String,
+                      ui_text_save: &mut String, gravitational_constant: Option<f32>, orbit: &mut OrbitSettings) {
+    let Some(gravitational_constant) = gravitational_constant else {
+        ui.label(None, "Orbits: turn on n-body gravity");
+        return;
+    };
+    //Use the object the user chose to orbit, or the one pulling hardest on the selected object
+    let chosen = orbit.centre
+        .and_then(|handle| objects.iter().position(|object| object.get_handle() == handle))
+        .filter(|index| *index != selected_index);
+    let Some(centre_index) = chosen.or_else(|| find_primary(objects, selected_index)) else {
+        ui.label(None, "Orbits: nothing to orbit");
+        return;
+    };
+
+    //Create the inputs for the object to orbit, and the eccentricity of elliptical orbits
+    let mut centre_str: String = if ui_id == "text_input_orbit_centre" { ui_text_save.clone() } else { objects[centre_index].get_handle().to_string() };
+    let mut eccentricity_str: String = if ui_id == "text_input_eccentricity" { ui_text_save.clone() } else { orbit.eccentricity.to_string() };
+    let centre_original = centre_str.clone();
+    let eccentricity_original = eccentricity_str.clone();
+    ui.label(None, "Orbit around #");
+    ui.same_line(0.);
+    ui.editbox(hash!(), Vec2::new(60., 20.), &mut centre_str);
+    ui.same_line(0.);
+    ui.label(None, "e:");
+    ui.same_line(0.);
+    ui.editbox(hash!(), Vec2::new(60., 20.), &mut eccentricity_str);
+
+    //Check if the user has changed the object to orbit
+    if centre_str != centre_original {
+        *ui_id = "text_input_orbit_centre".into();
+        *ui_text_save = centre_str.clone();
+        if let Ok(handle) = centre_str.trim().parse::<usize>() {
+            orbit.centre = Some(handle);
+        }
+    }
+    //Check if the user has changed the eccentricity, which must stay below 1 for the orbit to be closed
+    if eccentricity_str != eccentricity_original {
+        *ui_id = "text_input_eccentricity".into();
+        *ui_text_save = eccentricity_str.clone();
+        if is_only_numbers(&eccentricity_str) {
+            orbit.eccentricity = eccentricity_str.trim().parse::<f32>().unwrap().clamp(0.0, 0.99);
+        }
+    }
 
+    //Find the position and velocity of the selected object relative to the one it orbits
+    let (object, centre) = get_pair(objects, selected_index, centre_index);
+    let mu = get_mu(gravitational_constant, object, centre);
+    let centre_velocity = centre.get_velocity();
+    let relative_pos = object.get_render_shape_reference().get_centre() - centre.get_render_shape_reference().get_centre();
+    let relative_velocity = object.get_velocity() - centre_velocity;
+
+    //Give the object the exact velocity for the orbit, starting from where it is now
+    if ui.button(None, "circular orbit") {
+        object.set_velocity(centre_velocity + get_orbit_velocity(relative_pos, relative_velocity, mu, 0.0));
+    }
+    ui.same_line(0.);
+    if ui.button(None, "elliptical orbit") {
+        object.set_velocity(centre_velocity + get_orbit_velocity(relative_pos, relative_velocity, mu, orbit.eccentricity));
+    }
+
+    //Show the orbit the object is on now
+    let elements = get_orbital_elements(relative_pos, relative_velocity, mu);
+    ui.label(None, &format!("r: {:.3} m  a: {:.3} m  e: {:.3}", elements.distance, elements.semi_major_axis, elements.eccentricity));
+    match elements.period {
+        Some(period) => ui.label(None, &format!("Period: {:.3} s", period)),
+        None => ui.label(None, "Period: none (escaping)"),
+    }
+}
+
+//Labels for the collision layer buttons (only the first 8 layers are shown to the user)
+const LAYER_LABELS: [&str; 8] = ["1", "2", "3", "4", "5", "6", "7", "8"];
+
+//Build a row of toggle buttons, one for each bit in the given bitfield
+fn build_layer_buttons(ui: &mut Ui, bits: &mut u32, active: &Style, skin: &Skin) -> bool {
+    let mut changed = false;
+    for (i, label) in LAYER_LABELS.iter().enumerate() {
+        let bit = 1 << i;
+        if active_button(ui, *bits & bit != 0, active, skin.clone(), label) {
+            *bits ^= bit;
+            changed = true;
+        }
+        ui.same_line(0.);
+    }
+    ui.label(None, "");
+    changed
+}
+
+//Build the inputs for which collision layers the object is on, which layers it collides with, and if it is a sensor
+fn build_collision_filter_inputs(ui: &mut Ui, objects: &mut Vec<Box<dyn PhysicsObject>>, active: &Style, skin: &Skin, selected_index: usize) {
+    let object = objects.get_mut(selected_index).unwrap();
+    let mut changed = false;
+
+    //Create the UI for the layers the object is on
+    ui.label(None, "Layers:");
+    ui.same_line(0.);
+    changed |= build_layer_buttons(ui, object.get_collision_category(), active, skin);
+    //Create the UI for the layers the object collides with
+    ui.label(None, "Collides with:");
+    ui.same_line(0.);
+    changed |= build_layer_buttons(ui, object.get_collision_mask(), active, skin);
+
+    //Create the checkbox for whether the object is a sensor
+    let sensor = object.get_sensor();
+    ui.label(None, "Sensor: ");
+    ui.same_line(0.);
+    if active_button(ui, *sensor, active, skin.clone(), if *sensor { "[X]" } else { "[ ]" }) {
+        *sensor = !*sensor;
+        changed = true;
+    }
+
+    //Wake the object so the new filter is used straight away
+    if changed {
+        object.wake();
+    }
+}
+
+//Create the sidebar for the user
+pub(crate) fn create_side_bar(ui_id: &mut String, objects: &mut Vec<Box<dyn PhysicsObject>>, selected_index: usize, ui_text_save: &mut String,
+                              gravitational_constant: Option<f32>, orbit: &mut OrbitSettings) -> (bool, bool) {
+    let mut soft_buttons = (false, false);
     //Use the normal input style for the whole project
     let input_style = root_ui()
         .style_builder()
@@ -473,11 +722,8 @@ pub(crate) fn create_side_bar(ui_id: &mut String, objects: &mut Vec<Box<dyn Phys
 
     //Create the skin for the sidebar to use for the UI
     let mut skin = Skin {
-        label_style: window_style.clone(),
-        window_style,
-        button_style,
         editbox_style: input_style,
-        ..root_ui().default_skin()
+        ..panel_skin()
     };
 
     //Use a skin_hold so the skin will be dropped at the end of the function
@@ -501,14 +747,19 @@ pub(crate) fn create_side_bar(ui_id: &mut String, objects: &mut Vec<Box<dyn Phys
     //Create the window and hence sidebar
     root_ui().window(
         hash!(),
-        Vec2::new((screen_width()) - 400., 40.),
+        Vec2::new(screen_width() - SIDEBAR_WIDTH, 40.),
         Vec2::new(screen_width(), screen_height()),
         //Build all the inputs for the sidebar
         |ui| {
+            //Show the handle of the object, so it can be found in the event log
+            ui.label(None, &format!("Object #{}", objects.get_mut(selected_index).unwrap().get_handle()));
             create_x_and_y_input(ui, objects, selected_index, ui_id, ui_text_save);
             create_velocity_inputs(ui, objects, selected_index, ui_id, ui_text_save);
             create_mass_material_inputs(ui, objects, selected_index, ui_id, ui_text_save);
             build_gravity_inputs(ui, objects, selected_index, ui_id, ui_text_save);
+            build_charge_inputs(ui, objects, selected_index, ui_id, ui_text_save);
+            build_break_inputs(ui, objects, selected_index, ui_id, ui_text_save);
+            build_friction_inputs(ui, objects, selected_index, ui_id, ui_text_save);
             create_types_drop(ui, objects, selected_index, ui_id);
             create_shape_inputs(ui, objects, selected_index, ui_id, ui_text_save);
             //Create a variable to store the value return from the colour buttons function
@@ -522,7 +773,11 @@ pub(crate) fn create_side_bar(ui_id: &mut String, objects: &mut Vec<Box<dyn Phys
             build_transparency_slider(ui, objects, selected_index);
             ui.push_skin(&skin);
             build_air_resistance_checkbox(ui, objects, &button_active, &skin, selected_index);
+            build_collision_filter_inputs(ui, objects, &button_active, &skin, selected_index);
+            build_orbit_inputs(ui, objects, selected_index, ui_id, ui_text_save, gravitational_constant, orbit);
+            soft_buttons = build_soft_body_buttons(ui, objects, selected_index);
             build_bin_button(ui, ui_id, &mut skin, bin_button_style, objects, selected_index);
         },
     );
+    soft_buttons
 }
@@ -0,0 +1,51 @@
+use macroquad::prelude::Vec2;
+use macroquad::ui::*;
+use crate::uis::{panel_position, panel_skin, PANEL_WIDTH};
+use crate::objects::zones::{ForceZone, ZoneKind};
+
+//Build the panel for changing the kind, strength and direction of every force zone. Returns the zone to delete, if any
+pub(crate) fn create_zone_panel(zones: &mut [&mut ForceZone]) -> Option<usize> {
+    let mut delete = None;
+    let skin = panel_skin();
+    root_ui().push_skin(&skin);
+
+    root_ui().window(
+        hash!(),
+        panel_position(1, 0.24),
+        Vec2::new(PANEL_WIDTH, 220.),
+        |ui| {
+            ui.label(None, "Force zones");
+            //Put every zone in a group that scrolls, as there can be any number of them
+            ui.group(hash!(), Vec2::new(350., 185.), |ui| {
+                for (index, zone) in zones.iter_mut().enumerate() {
+                    //Click the kind of zone to swap to the next kind
+                    ui.label(None, &format!("Zone {}:", index + 1));
+                    ui.same_line(0.);
+                    if ui.button(None, zone.kind.get_name()) {
+                        zone.kind = zone.kind.next();
+                    }
+                    ui.same_line(0.);
+                    if ui.button(None, "X delete") {
+                        delete = Some(index);
+                    }
+                    let unit = if zone.kind == ZoneKind::Wind { "m/s" } else { "m/s^2" };
+                    ui.label(None, &format!("Strength: {:.1} {}", zone.strength, unit));
+                    //Use groups to control the size of the sliders, and give each slider its own id
+                    ui.group(hash!(("zone_strength_group", index)), Vec2::new(330., 25.), |ui| {
+                        ui.slider(hash!(("zone_strength", index)), "", -50.0..50., &mut zone.strength);
+                    });
+                    //Only wind and push zones have a direction
+                    if matches!(zone.kind, ZoneKind::Wind | ZoneKind::Push) {
+                        ui.label(None, &format!("Direction: {:.0} degrees", zone.angle));
+                        ui.group(hash!(("zone_angle_group", index)), Vec2::new(330., 25.), |ui| {
+                            ui.slider(hash!(("zone_angle", index)), "", 0.0..360., &mut zone.angle);
+                        });
+                    }
+                }
+            });
+        },
+    );
+
+    root_ui().pop_skin();
+    delete
+}